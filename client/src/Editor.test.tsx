@@ -5,16 +5,16 @@ import { decodeMessage } from './serverCommunication';
 
 jest.mock('react-use-websocket');
 const mockUseWebSocket = useWebSocket as jest.Mock;
-const sendMessageMock = jest.fn();
+const sendJsonMessageMock = jest.fn();
 
 jest.mock('./serverCommunication');
 const decodeMessageMock = decodeMessage as jest.Mock;
 
 describe('the Editor', () => {
     beforeEach(() => {
-        sendMessageMock.mockReset();
+        sendJsonMessageMock.mockReset();
         decodeMessageMock.mockReset();
-        mockUseWebSocket.mockReturnValue({ sendMessage: sendMessageMock });
+        mockUseWebSocket.mockReturnValue({ sendJsonMessage: sendJsonMessageMock });
     });
 
     test('should display the initial text', () => {
@@ -24,4 +24,15 @@ describe('the Editor', () => {
 
         expect(inputField).toHaveTextContent('1+2+3+4');
     });
+
+    test('should open the document and ask for its tokens after the handshake', () => {
+        render(<Editor />);
+        const options = mockUseWebSocket.mock.calls[mockUseWebSocket.mock.calls.length - 1][1];
+
+        options.onOpen();
+
+        expect(sendJsonMessageMock).toHaveBeenNthCalledWith(1, { type: 'hello', version: 1 });
+        expect(sendJsonMessageMock).toHaveBeenNthCalledWith(2, { type: 'open', id: 1, version: 1, document: '1+2+3+4' });
+        expect(sendJsonMessageMock).toHaveBeenNthCalledWith(3, { type: 'tokens', id: 2, version: 1 });
+    });
 });
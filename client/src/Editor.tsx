@@ -1,7 +1,27 @@
 import useWebSocket from 'react-use-websocket';
-import { PropsWithChildren, useState } from 'react';
-import { decodeMessage } from './serverCommunication';
-import CodeMirror from '@uiw/react-codemirror';
+import { PropsWithChildren, useRef, useState } from 'react';
+import {
+    alignToLines,
+    Change,
+    ClassifiedToken,
+    ClientMessage,
+    decodeMessage,
+    Diagnostic,
+    protocolVersion,
+    toDocumentPosition,
+    toDocumentPositions,
+    toEdits,
+} from './serverCommunication';
+import CodeMirror, {
+    Decoration,
+    DecorationSet,
+    EditorView,
+    ReactCodeMirrorRef,
+    StateEffect,
+    StateField,
+    ViewUpdate,
+} from '@uiw/react-codemirror';
+import { Diagnostic as EditorDiagnostic, lintGutter, setDiagnostics } from '@codemirror/lint';
 import { Box } from '@mui/material';
 
 type EditorState = {
@@ -11,21 +31,110 @@ type EditorState = {
 
 export const websocketUrl = 'ws://' + window.location.host + '/api/math';
 
+const initialDocument = '1+2+3+4';
+
+/**
+ * Larger changes, like replacing many selections, send the whole document instead.
+ */
+const maxEditsPerMessage = 100;
+
 const Editor = () => {
     const [state, setState] = useState<EditorState>({ editorContent: '', viewContent: '' });
+    const editor = useRef<ReactCodeMirrorRef>(null);
+
+    const requestId = useRef(0);
+    // the id of the latest open or edit, whose results are shown
+    const evaluationId = useRef(0);
+    // the version of the document on the server
+    const version = useRef(0);
 
-    const { sendMessage } = useWebSocket(websocketUrl, {
+    const { sendJsonMessage } = useWebSocket(websocketUrl, {
         share: true,
-        onMessage: (event) =>
-            setState((prevState) => {
-                return { ...prevState, viewContent: decodeMessage(event.data) };
-            }),
+        onOpen: () => {
+            send({ type: 'hello', version: protocolVersion });
+            open(editor.current?.view?.state.doc.toString() ?? initialDocument);
+        },
+        onMessage: (event) => {
+            const message = decodeMessage(event.data);
+            if (message === undefined) {
+                showText('Could not parse server response: ' + event.data);
+                return;
+            }
+            switch (message.type) {
+                case 'result':
+                    if (message.id === evaluationId.current) {
+                        showText(alignToLines(message.statements));
+                    }
+                    break;
+                case 'diagnostic':
+                    if (message.id === evaluationId.current) {
+                        showDiagnostics(editor.current?.view, message.diagnostics);
+                    }
+                    break;
+                case 'tokens':
+                    if (message.version === version.current) {
+                        showTokens(editor.current?.view, message.tokens);
+                    }
+                    break;
+                case 'error':
+                    // the server rejected the edits and kept its version of the document
+                    if (message.id !== null && message.id === evaluationId.current && editor.current?.view !== undefined) {
+                        open(editor.current.view.state.doc.toString());
+                        break;
+                    }
+                    showText(message.message);
+                    break;
+            }
+        },
     });
 
+    const send = (message: ClientMessage) => sendJsonMessage(message);
+
+    const showText = (text: string) =>
+        setState((prevState) => {
+            return { ...prevState, viewContent: text };
+        });
+
+    const nextRequestId = () => {
+        requestId.current += 1;
+        return requestId.current;
+    };
+
+    const open = (document: string) => {
+        evaluationId.current = nextRequestId();
+        version.current += 1;
+        send({ type: 'open', id: evaluationId.current, version: version.current, document });
+        send({ type: 'tokens', id: nextRequestId(), version: version.current });
+    };
+
+    const sendChanges = (update: ViewUpdate) => {
+        // before the document is opened, its changes are part of the opened text
+        if (!update.docChanged || version.current === 0) {
+            return;
+        }
+        const changes: Change[] = [];
+        update.changes.iterChanges((from, to, _fromB, _toB, inserted) => changes.push({ from, to, text: inserted.toString() }));
+        if (changes.length > maxEditsPerMessage) {
+            open(update.state.doc.toString());
+            return;
+        }
+        evaluationId.current = nextRequestId();
+        version.current += 1;
+        const edits = toEdits(update.startState.doc.toString(), changes);
+        send({ type: 'edit', id: evaluationId.current, version: version.current, edits });
+        send({ type: 'tokens', id: nextRequestId(), version: version.current });
+    };
+
     return (
         <Box sx={{ p: 3, display: 'flex' }}>
             <EditorContainer>
-                <CodeMirror value='1+2+3+4' height='90vh' onChange={(message: string) => sendMessage(message)} />
+                <CodeMirror
+                    ref={editor}
+                    value={initialDocument}
+                    height='90vh'
+                    extensions={[lintGutter(), tokenHighlighting, tokenTheme]}
+                    onUpdate={sendChanges}
+                />
             </EditorContainer>
             <EditorContainer>
                 <CodeMirror value={state.viewContent} height='90vh' editable={false} />
@@ -34,6 +143,83 @@ const Editor = () => {
     );
 };
 
+const setTokens = StateEffect.define<DecorationSet>();
+
+/**
+ * Highlights the tokens like the server classifies them, until the next tokens arrive they move with the text.
+ */
+const tokenHighlighting = StateField.define<DecorationSet>({
+    create: () => Decoration.none,
+    update: (tokens, transaction) => {
+        let decorations = tokens.map(transaction.changes);
+        for (const effect of transaction.effects) {
+            if (effect.is(setTokens)) {
+                decorations = effect.value;
+            }
+        }
+        return decorations;
+    },
+    provide: (field) => EditorView.decorations.from(field),
+});
+
+const tokenTheme = EditorView.baseTheme({
+    '.lyng-number': { color: '#164' },
+    '.lyng-identifier': { color: '#00f' },
+    '.lyng-operator': { color: '#708' },
+    '.lyng-paren': { color: '#555' },
+    '.lyng-comment': { color: '#940', fontStyle: 'italic' },
+    '.lyng-error': { textDecoration: 'underline wavy red' },
+});
+
+function showTokens(view: EditorView | undefined, tokens: ClassifiedToken[]) {
+    if (view === undefined) {
+        return;
+    }
+    const positions = toDocumentPositions(
+        view.state.doc.toString(),
+        tokens.flatMap((token) => [token.span.start_offset, token.span.end_offset])
+    );
+    const decorations = tokens
+        .map((token, index) => ({ from: positions[2 * index], to: positions[2 * index + 1], token }))
+        .filter(({ from, to }) => from < to)
+        .map(({ from, to, token }) => Decoration.mark({ class: 'lyng-' + token.class }).range(from, to));
+    view.dispatch({ effects: setTokens.of(Decoration.set(decorations, true)) });
+}
+
+function showDiagnostics(view: EditorView | undefined, diagnostics: Diagnostic[]) {
+    if (view === undefined) {
+        return;
+    }
+    const document = view.state.doc.toString();
+    const editorDiagnostics = diagnostics.map(
+        (diagnostic): EditorDiagnostic => ({
+            from: toDocumentPosition(document, diagnostic.span.start_offset),
+            to: toDocumentPosition(document, diagnostic.span.end_offset),
+            severity: 'error',
+            source: diagnostic.code,
+            message: [
+                diagnostic.message,
+                ...(diagnostic.help === null ? [] : ['help: ' + diagnostic.help]),
+                ...diagnostic.notes.map((note) => 'note: ' + note),
+            ].join('\n'),
+            actions: diagnostic.suggestions.map((suggestion) => ({
+                name: suggestion.message,
+                apply: (target: EditorView) => {
+                    const currentDocument = target.state.doc.toString();
+                    target.dispatch({
+                        changes: {
+                            from: toDocumentPosition(currentDocument, suggestion.span.start_offset),
+                            to: toDocumentPosition(currentDocument, suggestion.span.end_offset),
+                            insert: suggestion.replacement,
+                        },
+                    });
+                },
+            })),
+        })
+    );
+    view.dispatch(setDiagnostics(view.state, editorDiagnostics));
+}
+
 function EditorContainer(props: PropsWithChildren) {
     return <Box sx={{ width: '40%', flex: 1, m: 1 }}>{props.children}</Box>;
 }
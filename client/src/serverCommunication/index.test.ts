@@ -1,15 +1,117 @@
-import { decodeMessage } from './index';
+import { alignToLines, decodeMessage, toDocumentPosition, toDocumentPositions, toEdits, toLocalization } from './index';
+
+const span = (line: number, start: number, end: number) => ({
+    start: { line, column: start },
+    end: { line, column: end },
+    start_offset: start,
+    end_offset: end,
+});
 
 describe('decode server messages', () => {
-    test('should decode Success messages', () => {
-        let result = decodeMessage('Success("success message")');
+    test('should decode results', () => {
+        let result = decodeMessage(
+            '{"type":"result","id":3,"statements":[{"span":' +
+                JSON.stringify(span(0, 0, 5)) +
+                ',"kind":"value","text":"(1 + 2) = 3"}]}'
+        );
+
+        expect(result).toStrictEqual({
+            type: 'result',
+            id: 3,
+            statements: [{ span: span(0, 0, 5), kind: 'value', text: '(1 + 2) = 3' }],
+        });
+    });
+
+    test('should decode tokens', () => {
+        let result = decodeMessage(
+            '{"type":"tokens","id":4,"version":2,"tokens":[{"class":"number","span":' + JSON.stringify(span(0, 0, 1)) + '}]}'
+        );
+
+        expect(result).toStrictEqual({ type: 'tokens', id: 4, version: 2, tokens: [{ class: 'number', span: span(0, 0, 1) }] });
+    });
+
+    test('should decode errors without a request id', () => {
+        let result = decodeMessage('{"type":"error","id":null,"message":"unsupported protocol version 2"}');
+
+        expect(result).toStrictEqual({ type: 'error', id: null, message: 'unsupported protocol version 2' });
+    });
 
-        expect(result).toBe('success message');
+    test('should reject messages that are not JSON', () => {
+        expect(decodeMessage('[(span:(),output:Value("3"))]')).toBeUndefined();
     });
 
-    test('should decode Error messages', () => {
-        let result = decodeMessage('Error("error message")');
+    test('should reject unknown message types', () => {
+        expect(decodeMessage('{"type":"unknown"}')).toBeUndefined();
+    });
+});
+
+describe('align outputs to lines', () => {
+    test('should put values and comments on the lines they belong to', () => {
+        let result = alignToLines([
+            { span: span(0, 0, 5), kind: 'value', text: '(1 + 2) = 3' },
+            { span: span(0, 6, 13), kind: 'comment', text: '# three' },
+            { span: span(2, 0, 1), kind: 'error', text: 'error message' },
+        ]);
+
+        expect(result).toBe('(1 + 2) = 3    # three\n\nerror message');
+    });
+
+    test('should align an empty document', () => {
+        expect(alignToLines([])).toBe('');
+    });
+});
+
+describe('converting byte offsets to document positions', () => {
+    test('should keep offsets in ASCII text', () => {
+        expect(toDocumentPosition('1 + 2', 4)).toBe(4);
+    });
+
+    test('should count multibyte characters once', () => {
+        expect(toDocumentPosition('é + 2', 3)).toBe(2);
+    });
+
+    test('should count surrogate pairs twice', () => {
+        expect(toDocumentPosition('😀 + 2', 5)).toBe(3);
+    });
+
+    test('should stop at the end of the document', () => {
+        expect(toDocumentPosition('1', 5)).toBe(1);
+    });
+
+    test('should convert increasing offsets at once', () => {
+        expect(toDocumentPositions('é + 😀', [0, 2, 2, 5, 9, 12])).toStrictEqual([0, 1, 1, 4, 6, 6]);
+    });
+});
+
+describe('converting document positions to lines and columns', () => {
+    test('should count lines and columns', () => {
+        expect(toLocalization('1 + 2;\n3 * 4', 9)).toStrictEqual({ line: 1, column: 2 });
+    });
+
+    test('should count surrogate pairs once', () => {
+        expect(toLocalization('1;\n😀 + 2', 7)).toStrictEqual({ line: 1, column: 3 });
+    });
+
+    test('should place the end of a line before its line break', () => {
+        expect(toLocalization('1\n2', 1)).toStrictEqual({ line: 0, column: 1 });
+        expect(toLocalization('1\n2', 2)).toStrictEqual({ line: 1, column: 0 });
+    });
+});
+
+describe('converting changes to edits', () => {
+    test('should apply the last change first', () => {
+        const result = toEdits('1 + 2;\n3', [
+            { from: 0, to: 1, text: '10' },
+            { from: 7, to: 8, text: '4' },
+        ]);
+
+        expect(result).toStrictEqual([
+            { start: { line: 1, column: 0 }, end: { line: 1, column: 1 }, text: '4' },
+            { start: { line: 0, column: 0 }, end: { line: 0, column: 1 }, text: '10' },
+        ]);
+    });
 
-        expect(result).toBe('The server returned an error: error message');
+    test('should have no edits without changes', () => {
+        expect(toEdits('1', [])).toStrictEqual([]);
     });
 });
@@ -1,14 +1,178 @@
-import { decode, ronEnum, string, tupleStruct } from '../ron';
+/**
+ * The messages of the math websocket, see docs/math-protocol.md.
+ */
+export const protocolVersion = 1;
 
-const messageDecoder = ronEnum(tupleStruct('Success', string), tupleStruct('Error', string));
+export type Localization = { line: number; column: number };
 
-export function decodeMessage(message: string): string {
-    const result = decode(message, messageDecoder);
-    if (!result.success) {
-        return 'Could not parse server response: ' + result.error;
+export type Span = {
+    start: Localization;
+    end: Localization;
+    start_offset: number;
+    end_offset: number;
+};
+
+export type Suggestion = {
+    message: string;
+    span: Span;
+    replacement: string;
+};
+
+export type Diagnostic = {
+    severity: 'Error';
+    code: string;
+    kind: string;
+    span: Span;
+    message: string;
+    help: string | null;
+    notes: string[];
+    suggestions: Suggestion[];
+};
+
+export type TokenClass = 'number' | 'identifier' | 'operator' | 'paren' | 'comment' | 'error';
+
+export type ClassifiedToken = {
+    class: TokenClass;
+    span: Span;
+};
+
+export type StatementOutput = {
+    span: Span;
+    kind: 'value' | 'comment' | 'error';
+    text: string;
+};
+
+/**
+ * Replaces the text between `start` and `end`, which count lines and characters like spans.
+ */
+export type TextEdit = {
+    start: Localization;
+    end: Localization;
+    text: string;
+};
+
+/**
+ * A change of the editor, `from` and `to` are positions in UTF-16 code units.
+ */
+export type Change = {
+    from: number;
+    to: number;
+    text: string;
+};
+
+export type ClientMessage =
+    | { type: 'hello'; version: number }
+    | { type: 'evaluate'; id: number; document: string }
+    | { type: 'open'; id: number; version: number; document: string }
+    | { type: 'edit'; id: number; version: number; edits: TextEdit[] }
+    | { type: 'tokens'; id: number; version: number }
+    | { type: 'cancel'; id: number }
+    | { type: 'settings'; id: number; precision: number | null };
+
+export type ServerMessage =
+    | { type: 'welcome'; version: number }
+    | { type: 'result'; id: number; statements: StatementOutput[] }
+    | { type: 'diagnostic'; id: number; diagnostics: Diagnostic[] }
+    | { type: 'settings'; id: number; precision: number | null }
+    | { type: 'tokens'; id: number; version: number; tokens: ClassifiedToken[] }
+    | { type: 'error'; id: number | null; message: string };
+
+const serverMessageTypes = ['welcome', 'result', 'diagnostic', 'settings', 'tokens', 'error'];
+
+export function decodeMessage(message: string): ServerMessage | undefined {
+    try {
+        const decoded = JSON.parse(message);
+        return serverMessageTypes.includes(decoded?.type) ? decoded : undefined;
+    } catch {
+        return undefined;
+    }
+}
+
+/**
+ * Puts every output on the line of the statement it belongs to, outputs of the same line are separated by spaces.
+ */
+export function alignToLines(statements: StatementOutput[]): string {
+    const lines: string[] = [];
+    for (const { span, text } of statements) {
+        const line = span.start.line;
+        while (lines.length <= line) {
+            lines.push('');
+        }
+        lines[line] = lines[line] === '' ? text : lines[line] + '    ' + text;
+    }
+    return lines.join('\n');
+}
+
+/**
+ * The server counts the columns of positions in characters, the editor counts UTF-16 code units.
+ */
+export function toLocalization(document: string, position: number): Localization {
+    const lines = document.slice(0, position).split('\n');
+    return { line: lines.length - 1, column: Array.from(lines[lines.length - 1]).length };
+}
+
+/**
+ * The changes of `document`, which don't overlap, as edits that apply one after the other.
+ * The last change comes first, so that it doesn't move the positions of the earlier ones.
+ */
+export function toEdits(document: string, changes: Change[]): TextEdit[] {
+    return [...changes]
+        .sort((a, b) => b.from - a.from)
+        .map(({ from, to, text }) => ({
+            start: toLocalization(document, from),
+            end: toLocalization(document, to),
+            text,
+        }));
+}
+
+/**
+ * The server counts UTF-8 bytes, the editor counts UTF-16 code units.
+ */
+export function toDocumentPosition(document: string, byteOffset: number): number {
+    let bytes = 0;
+    let position = 0;
+    for (const character of document) {
+        if (bytes >= byteOffset) {
+            break;
+        }
+        bytes += utf8Length(character.codePointAt(0)!);
+        position += character.length;
+    }
+    return position;
+}
+
+/**
+ * Like `toDocumentPosition` for increasing offsets, in one pass over the document.
+ */
+export function toDocumentPositions(document: string, byteOffsets: number[]): number[] {
+    const positions: number[] = [];
+    let bytes = 0;
+    let position = 0;
+    for (const character of document) {
+        while (positions.length < byteOffsets.length && bytes >= byteOffsets[positions.length]) {
+            positions.push(position);
+        }
+        if (positions.length === byteOffsets.length) {
+            break;
+        }
+        bytes += utf8Length(character.codePointAt(0)!);
+        position += character.length;
+    }
+    while (positions.length < byteOffsets.length) {
+        positions.push(position);
+    }
+    return positions;
+}
+
+function utf8Length(codePoint: number): number {
+    if (codePoint < 0x80) {
+        return 1;
+    }
+    if (codePoint < 0x800) {
+        return 2;
     }
-    if (result.value.name === 'Success') {
-        return result.value.value[0];
+    if (codePoint < 0x10000) {
+        return 3;
     }
-    return 'The server returned an error: ' + result.value.value[0];
+    return 4;
 }
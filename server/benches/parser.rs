@@ -0,0 +1,54 @@
+//! Throughput of the parser on long documents, run with `cargo bench --bench parser`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use lyng2::math::ast::parser::lexer::tokenize;
+use lyng2::math::ast::parser::{parse_comments, parse_statements, MAX_INPUT_LENGTH};
+
+/// Lines that use every part of the grammar, repeated to build documents of any length.
+const LINES: [&str; 5] = [
+    "1 + 2 * 3 - 4 / 5;",
+    "(12.5 - x) ^ 2 mod 7; # a comment",
+    "-|3 - y|! + 2 ^ -3 ^ 2;",
+    "/* block */ 1e-5 * (2 + (3 - 4));",
+    "10!! % 4 + z;",
+];
+
+fn document(lines: usize) -> String {
+    let document: String = LINES
+        .iter()
+        .cycle()
+        .take(lines)
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert!(document.len() <= MAX_INPUT_LENGTH);
+    document
+}
+
+fn parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+
+    for lines in [1_000, 3_000] {
+        let input = document(lines);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("tokenize", lines), &input, |b, input| {
+            b.iter(|| tokenize(black_box(input)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("parse_statements", lines),
+            &input,
+            |b, input| b.iter(|| parse_statements(black_box(input.clone()))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("parse_comments", lines),
+            &input,
+            |b, input| b.iter(|| parse_comments(black_box(input))),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parser);
+criterion_main!(benches);
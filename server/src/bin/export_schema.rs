@@ -2,9 +2,15 @@ use std::io::Write;
 
 use lyng2::chat::build_schema;
 use lyng2::chat::repository::InMemoryRepository;
+use lyng2::math::ast::budget::Limits;
+use lyng2::math::evaluation_pool::EvaluationPool;
 
 fn main() {
     std::io::stdout()
-        .write_all(build_schema::<InMemoryRepository>().sdl().as_bytes())
+        .write_all(
+            build_schema::<InMemoryRepository>(EvaluationPool::new(Limits::default(), 1))
+                .sdl()
+                .as_bytes(),
+        )
         .unwrap();
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{Formatting, GotoDefinition, HoverRequest, Request as RequestType};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentFormattingParams, GotoDefinitionParams, GotoDefinitionResponse, HoverParams,
+    HoverProviderCapability, Location, OneOf, PublishDiagnosticsParams, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use lyng2::math::language_server::{to_localization, to_range, OpenDocument};
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Url, OpenDocument>,
+}
+
+impl Server {
+    fn handle_request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => params(request).map(|params: HoverParams| {
+                let position = params.text_document_position_params;
+                self.with_document(&position.text_document.uri, |document| {
+                    document.hover(to_localization(&document.text, position.position))
+                })
+            }),
+            GotoDefinition::METHOD => params(request).map(|params: GotoDefinitionParams| {
+                let position = params.text_document_position_params;
+                let uri = position.text_document.uri;
+                self.with_document(&uri, |document| {
+                    let span =
+                        document.definition(to_localization(&document.text, position.position))?;
+                    let location = Location::new(uri.clone(), to_range(&document.text, span));
+                    Some(GotoDefinitionResponse::Scalar(location))
+                })
+            }),
+            Formatting::METHOD => params(request).map(|params: DocumentFormattingParams| {
+                self.with_document(&params.text_document.uri, |document| {
+                    Some(document.formatting())
+                })
+            }),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unknown method {method}"),
+                )
+            }
+        };
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error),
+        }
+    }
+
+    /// `null` for documents that aren't open.
+    fn with_document<T: serde::Serialize>(
+        &self,
+        uri: &Url,
+        handle: impl FnOnce(&OpenDocument) -> Option<T>,
+    ) -> Value {
+        let result = self.documents.get(uri).and_then(handle);
+        serde_json::to_value(result).expect("LSP types are serializable")
+    }
+
+    /// Returns the notifications to send, the diagnostics of the changed document.
+    fn handle_notification(&mut self, notification: Notification) -> Vec<Notification> {
+        let (uri, version) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) = params::<DidOpenTextDocumentParams>(notification) else {
+                    return vec![];
+                };
+                let document = params.text_document;
+                self.documents
+                    .insert(document.uri.clone(), OpenDocument::new(document.text));
+                (document.uri, Some(document.version))
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(mut params) = params::<DidChangeTextDocumentParams>(notification) else {
+                    return vec![];
+                };
+                let uri = params.text_document.uri;
+                // with full synchronization, the last change contains the whole text
+                let (Some(document), Some(change)) =
+                    (self.documents.get_mut(&uri), params.content_changes.pop())
+                else {
+                    return vec![];
+                };
+                document.text = change.text;
+                (uri, Some(params.text_document.version))
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) = params::<DidCloseTextDocumentParams>(notification) else {
+                    return vec![];
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(uri, vec![], None)];
+            }
+            _ => return vec![],
+        };
+
+        let diagnostics = match self.documents.get_mut(&uri) {
+            Some(document) => document.diagnostics(),
+            None => vec![],
+        };
+        vec![publish_diagnostics(uri, diagnostics, version)]
+    }
+}
+
+fn params<P: DeserializeOwned>(message: impl Into<Message>) -> Result<P, String> {
+    let params = match message.into() {
+        Message::Request(request) => request.params,
+        Message::Notification(notification) => notification.params,
+        Message::Response(_) => Value::Null,
+    };
+    serde_json::from_value(params).map_err(|error| error.to_string())
+}
+
+fn publish_diagnostics(
+    uri: Url,
+    diagnostics: Vec<lsp_types::Diagnostic>,
+    version: Option<i32>,
+) -> Notification {
+    Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version,
+        },
+    )
+}
+
+/// Answers the editor's messages until it shuts the server down.
+fn serve(connection: Connection) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let mut server = Server::default();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                for outgoing in server.handle_notification(notification) {
+                    connection.sender.send(Message::Notification(outgoing))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    // the connection has to be dropped before joining, otherwise the writer never stops
+    serve(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,326 @@
+use std::io;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use lyng2::math::application::diagnostic::Diagnostic;
+use lyng2::math::application::{Application, Output};
+use lyng2::math::ast::budget::{Budget, CancellationToken, Limits};
+use lyng2::math::ast::parser::parse_statements;
+use lyng2::math::ast::{LocalizedSyntaxNode, Span};
+use lyng2::math::protocol::OutputKind;
+
+use crate::rendering::render_in_file;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// the results and the diagnostics as they are shown in the REPL
+    Text,
+    /// one array with an object per file, for other programs
+    Json,
+    /// a display formula per statement, for documents
+    Latex,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "latex" => Ok(Format::Latex),
+            _ => Err(format!(
+                "unknown format '{format}', expected text, json or latex"
+            )),
+        }
+    }
+}
+
+/// The statements of one file, `-` is the standard input.
+struct Sheet {
+    path: String,
+    source: String,
+    statements: Vec<Evaluated>,
+}
+
+struct Evaluated {
+    span: Span,
+    output: Output,
+    /// the exact value, unlike the rounded one in `output`
+    value: Option<String>,
+    /// `None` if the statement doesn't parse
+    tree: Option<LocalizedSyntaxNode>,
+}
+
+#[derive(Serialize)]
+struct SheetOutput<'a> {
+    file: &'a str,
+    statements: Vec<StatementOutput<'a>>,
+    diagnostics: Vec<&'a Diagnostic>,
+}
+
+#[derive(Serialize)]
+struct StatementOutput<'a> {
+    span: Span,
+    kind: OutputKind,
+    /// like in the `result` message of the websocket protocol
+    text: &'a str,
+    value: Option<&'a str>,
+}
+
+/// Evaluates every file on its own within the limits of the server and prints the results in
+/// `format`. Returns whether any statement failed, or an error if a file can't be read.
+pub fn run(paths: &[String], format: Format) -> Result<bool, String> {
+    let mut sheets = vec![];
+    for path in paths {
+        sheets.push(evaluate(path.clone(), read(path)?, Limits::default()));
+    }
+
+    print(
+        &sheets,
+        format,
+        &mut io::stdout().lock(),
+        &mut io::stderr().lock(),
+    )
+    .map_err(|error| format!("can't print the results: {error}"))?;
+    Ok(failed(&sheets))
+}
+
+fn failed(sheets: &[Sheet]) -> bool {
+    sheets.iter().any(|sheet| {
+        sheet
+            .statements
+            .iter()
+            .any(|statement| matches!(statement.output, Output::Error(_)))
+    })
+}
+
+/// Prints the results to `out` and the diagnostics to `errors`.
+fn print(
+    sheets: &[Sheet],
+    format: Format,
+    out: &mut impl Write,
+    errors: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        Format::Text => sheets
+            .iter()
+            .try_for_each(|sheet| print_text(sheet, out, errors)),
+        Format::Json => print_json(sheets, out),
+        Format::Latex => sheets
+            .iter()
+            .try_for_each(|sheet| print_latex(sheet, out, errors)),
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    let mut source = String::new();
+    let result = if path == "-" {
+        std::io::stdin().read_to_string(&mut source).map(|_| ())
+    } else {
+        std::fs::read_to_string(path).map(|text| source = text)
+    };
+    result.map_err(|error| format!("can't read {path}: {error}"))?;
+    Ok(source)
+}
+
+fn evaluate(path: String, source: String, limits: Limits) -> Sheet {
+    let mut application = Application::create();
+    let budget = Budget::new(limits, CancellationToken::new());
+    let results = application.run_within(source.clone(), &budget);
+    // the statements are split like by the application, one tree per statement result
+    let trees = parse_statements(source.clone());
+    let history = application.history();
+
+    let statements = results
+        .into_iter()
+        .filter(|result| !matches!(result.output, Output::Comment(_)))
+        .enumerate()
+        .map(|(index, result)| Evaluated {
+            span: result.span,
+            output: result.output,
+            value: history
+                .get(index)
+                .and_then(|entry| entry.value.as_ref())
+                .map(ToString::to_string),
+            tree: trees
+                .get(index)
+                .and_then(|statement| statement.result.clone().ok()),
+        })
+        .collect();
+    Sheet {
+        path,
+        source,
+        statements,
+    }
+}
+
+fn print_text(sheet: &Sheet, out: &mut impl Write, errors: &mut impl Write) -> io::Result<()> {
+    for statement in &sheet.statements {
+        match &statement.output {
+            Output::Value(text) => writeln!(
+                out,
+                "{}:{}: {text}",
+                sheet.path,
+                statement.span.start.line() + 1
+            )?,
+            Output::Error(diagnostic) => write!(
+                errors,
+                "{}",
+                render_in_file(diagnostic, &sheet.path, &sheet.source, false)
+            )?,
+            Output::Comment(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn print_json(sheets: &[Sheet], out: &mut impl Write) -> io::Result<()> {
+    let output: Vec<_> = sheets
+        .iter()
+        .map(|sheet| {
+            let mut statements = vec![];
+            let mut diagnostics = vec![];
+            for statement in &sheet.statements {
+                let (kind, text) = match &statement.output {
+                    Output::Value(text) => (OutputKind::Value, text.as_str()),
+                    Output::Comment(text) => (OutputKind::Comment, text.as_str()),
+                    Output::Error(diagnostic) => {
+                        diagnostics.push(diagnostic);
+                        (OutputKind::Error, diagnostic.message.as_str())
+                    }
+                };
+                statements.push(StatementOutput {
+                    span: statement.span,
+                    kind,
+                    text,
+                    value: statement.value.as_deref(),
+                });
+            }
+            SheetOutput {
+                file: &sheet.path,
+                statements,
+                diagnostics,
+            }
+        })
+        .collect();
+    writeln!(out, "{}", serde_json::to_string_pretty(&output).unwrap())
+}
+
+/// Failed statements become LaTeX comments, their diagnostics are printed to `errors`.
+fn print_latex(sheet: &Sheet, out: &mut impl Write, errors: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "% {}", sheet.path)?;
+    for statement in &sheet.statements {
+        match (&statement.output, &statement.tree) {
+            (Output::Error(diagnostic), _) => {
+                writeln!(
+                    out,
+                    "% {}:{}:{}: error[{}]: {}",
+                    sheet.path,
+                    diagnostic.span.start.line() + 1,
+                    diagnostic.span.start.column() + 1,
+                    diagnostic.code,
+                    diagnostic.message
+                )?;
+                write!(
+                    errors,
+                    "{}",
+                    render_in_file(diagnostic, &sheet.path, &sheet.source, false)
+                )?;
+            }
+            (_, Some(tree)) => match &statement.value {
+                Some(value) => writeln!(out, "\\[ {} = {value} \\]", tree.to_latex())?,
+                None => writeln!(out, "\\[ {} \\]", tree.to_latex())?,
+            },
+            (_, None) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use lyng2::math::ast::budget::Limits;
+
+    use super::{evaluate, failed, print, run, Format, Sheet};
+
+    fn sheet(source: &str) -> Sheet {
+        evaluate("a.lyng".to_string(), source.to_string(), Limits::default())
+    }
+
+    /// What is printed to the standard output and to the standard error.
+    fn printed(sheets: &[Sheet], format: Format) -> (String, String) {
+        let (mut out, mut errors) = (vec![], vec![]);
+        print(sheets, format, &mut out, &mut errors).unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[test]
+    fn files_with_errors_fail() {
+        assert!(!failed(&[sheet("1 + 2; 3")]));
+        assert!(failed(&[sheet("1 + 2"), sheet("1 +")]));
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let error = run(&["does/not/exist.lyng".to_string()], Format::Text).unwrap_err();
+
+        assert!(
+            error.starts_with("can't read does/not/exist.lyng: "),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn evaluations_stop_at_the_limits() {
+        let limits = Limits {
+            timeout: Duration::ZERO,
+            ..Limits::default()
+        };
+        let sheets = [evaluate("a.lyng".to_string(), "2^3".to_string(), limits)];
+
+        let (_, errors) = printed(&sheets, Format::Text);
+
+        assert!(failed(&sheets));
+        assert!(errors.starts_with("error[E0015]"), "{errors}");
+    }
+
+    #[test]
+    fn text_names_the_file_and_line() {
+        let (out, errors) = printed(&[sheet("1 + 2;\n(3")], Format::Text);
+
+        assert_eq!("a.lyng:1: (1 + 2) = 3\n", out);
+        assert!(errors.contains(" --> a.lyng:2:3\n"), "{errors}");
+    }
+
+    #[test]
+    fn json_lists_statements_and_diagnostics() {
+        let (out, errors) = printed(&[sheet("1 + 2; (3")], Format::Json);
+
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+        let file = &json[0];
+        assert_eq!("a.lyng", file["file"]);
+        assert_eq!("3", file["statements"][0]["value"]);
+        assert_eq!("E0008", file["diagnostics"][0]["code"]);
+        assert_eq!(2, file["statements"].as_array().unwrap().len());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn latex_has_a_formula_per_statement() {
+        let (out, errors) = printed(&[sheet("1/2 + 1; (3")], Format::Latex);
+
+        assert_eq!(
+            "% a.lyng\n\\[ \\frac{1}{2} + 1 = 1.5 \\]\n% a.lyng:1:12: error[E0008]: ",
+            &out[..out.find("E0008").unwrap() + 8]
+        );
+        assert!(errors.contains(" --> a.lyng:1:12\n"), "{errors}");
+    }
+}
@@ -0,0 +1,69 @@
+use std::process::ExitCode;
+
+use crate::batch::Format;
+
+mod batch;
+mod rendering;
+mod repl;
+
+const USAGE: &str = "\
+usage: lyng2-repl [--format text|json|latex] [FILE...]
+
+Without files, statements are read interactively.
+With files, every file is evaluated on its own, within the limits of the server, and the result
+of every statement is printed.
+The exit status is 1 if any statement failed, 2 if a file can't be read.";
+
+fn main() -> ExitCode {
+    let mut format = None;
+    let mut paths = vec![];
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let value = match argument.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            "--format" => arguments.next(),
+            _ => argument.strip_prefix("--format=").map(str::to_string),
+        };
+        match value {
+            Some(value) => match value.parse::<Format>() {
+                Ok(parsed) => format = Some(parsed),
+                Err(error) => return usage_error(&error),
+            },
+            None if argument == "--format" => return usage_error("--format needs a value"),
+            None if argument.starts_with('-') && argument != "-" => {
+                return usage_error(&format!("unknown option '{argument}'"))
+            }
+            None => paths.push(argument),
+        }
+    }
+
+    if paths.is_empty() {
+        if format.is_some() {
+            return usage_error("--format needs at least one file");
+        }
+        return match repl::run() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("error: {error}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    match batch::run(&paths, format.unwrap_or(Format::Text)) {
+        Ok(false) => ExitCode::SUCCESS,
+        Ok(true) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {message}\n\n{USAGE}");
+    ExitCode::from(2)
+}
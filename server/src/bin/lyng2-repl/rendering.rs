@@ -0,0 +1,164 @@
+//! Diagnostics for the terminal, in the style of rustc.
+
+use lyng2::math::application::diagnostic::Diagnostic;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Formats `diagnostic` for a terminal, with the line of `source` it starts in
+/// and its span underlined up to the end of that line. `colored` adds ANSI escape codes.
+///
+/// ```text
+/// error[E0008]: missing closing parenthesis
+///  --> 1:7
+///   |
+/// 1 | (1 + 2
+///   |       ^
+///   = help: ...
+/// ```
+pub fn render(diagnostic: &Diagnostic, source: &str, colored: bool) -> String {
+    render_at(diagnostic, "", source, colored)
+}
+
+/// Like [render], the location is prefixed by `path`, like ` --> sheet.lyng:1:7`.
+pub fn render_in_file(diagnostic: &Diagnostic, path: &str, source: &str, colored: bool) -> String {
+    render_at(diagnostic, &format!("{path}:"), source, colored)
+}
+
+fn render_at(diagnostic: &Diagnostic, prefix: &str, source: &str, colored: bool) -> String {
+    let paint = |style: &str, text: &str| {
+        if colored {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+
+    let start = diagnostic.span.start;
+    let line = source
+        .split('\n')
+        .nth(start.line())
+        .unwrap_or_default()
+        .trim_end_matches('\r');
+    let line_number = (start.line() + 1).to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    // tabs are kept, so that the underline lines up with the text above it
+    let indentation: String = line
+        .chars()
+        .take(start.column())
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+    let end_column = if diagnostic.span.end.line() == start.line() {
+        diagnostic.span.end.column()
+    } else {
+        line.chars().count()
+    };
+    let underline = "^".repeat(end_column.saturating_sub(start.column()).max(1));
+
+    let mut rendered = format!(
+        "{}{}\n",
+        paint(RED, &format!("error[{}]", diagnostic.code)),
+        paint(BOLD, &format!(": {}", diagnostic.message))
+    );
+    rendered.push_str(&format!(
+        "{gutter}{} {prefix}{}:{}\n",
+        paint(BLUE, "-->"),
+        start.line() + 1,
+        start.column() + 1
+    ));
+    rendered.push_str(&format!("{gutter} {}\n", paint(BLUE, "|")));
+    rendered.push_str(&format!(
+        "{} {line}\n",
+        paint(BLUE, &format!("{line_number} |"))
+    ));
+    rendered.push_str(&format!(
+        "{gutter} {} {indentation}{}\n",
+        paint(BLUE, "|"),
+        paint(RED, &underline)
+    ));
+
+    let remarks = diagnostic
+        .help
+        .iter()
+        .map(|help| ("help", help))
+        .chain(diagnostic.notes.iter().map(|note| ("note", note)))
+        .chain(
+            diagnostic
+                .suggestions
+                .iter()
+                .map(|suggestion| ("suggestion", &suggestion.message)),
+        );
+    for (label, remark) in remarks {
+        rendered.push_str(&format!(
+            "{gutter} {} {remark}\n",
+            paint(BOLD, &format!("= {label}:"))
+        ));
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use lyng2::math::application::diagnostic::Diagnostic;
+    use lyng2::math::ast::parser::parse_statements;
+
+    use super::{render, render_in_file};
+
+    fn first_error_of(input: &str) -> Diagnostic {
+        parse_statements(input.to_string())
+            .into_iter()
+            .find_map(|statement| statement.result.err())
+            .map(Diagnostic::from)
+            .unwrap()
+    }
+
+    #[test]
+    fn render_points_at_the_column() {
+        let under_test = first_error_of("1 +\n\t(2 * 3");
+
+        let expected = "\
+error[E0008]: Syntax Error: missing closing parenthesis
+ --> 2:8
+  |
+2 | \t(2 * 3
+  | \t      ^
+  = help: insert ')' to close the parenthesis
+  = suggestion: insert ')'
+";
+        assert_eq!(expected, render(&under_test, "1 +\n\t(2 * 3", false));
+    }
+
+    #[test]
+    fn render_underlines_the_span() {
+        let under_test = first_error_of("1 + 2 3 4");
+
+        let rendered = render(&under_test, "1 + 2 3 4", false);
+
+        assert!(
+            rendered.contains("1 | 1 + 2 3 4\n  |       ^^^\n"),
+            "{rendered}"
+        );
+    }
+
+    #[test]
+    fn render_in_file_names_the_file() {
+        let under_test = first_error_of("1 + 2 3");
+
+        let rendered = render_in_file(&under_test, "sheets/a.lyng", "1 + 2 3", false);
+
+        assert!(rendered.contains(" --> sheets/a.lyng:1:7\n"), "{rendered}");
+    }
+
+    #[test]
+    fn render_with_colors() {
+        let under_test = first_error_of("(1");
+
+        let rendered = render(&under_test, "(1", true);
+
+        assert!(rendered.starts_with("\x1b[1;31merror[E0008]\x1b[0m\x1b[1m: "));
+        assert!(rendered.contains("\x1b[1;34m1 |\x1b[0m (1\n"));
+    }
+}
@@ -0,0 +1,367 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Editor, Helper};
+
+use lyng2::math::application::diagnostic::Diagnostic;
+use lyng2::math::application::{Application, Output};
+use lyng2::math::ast::budget::{Budget, CancellationToken, Limits};
+use lyng2::math::ast::parser::error_kind::ErrorKind;
+use lyng2::math::ast::parser::lexer::{tokenize, TokenKind};
+use lyng2::math::ast::parser::operators::OperatorTable;
+use lyng2::math::ast::parser::parse_statements_with;
+use lyng2::math::ast::{Localization, Span};
+
+use crate::rendering::render;
+
+const HELP: &str = "\
+Enter statements like '(1 + 2) * 3', several statements are separated by ';'.
+A line ending in an operator or with an unclosed '(' continues on the next line.
+Ctrl-C stops a long evaluation, the input is forgotten then.
+Earlier results are available as 'ans', '%n' and 'out[n]', the numbers are shown in brackets.
+
+Commands:
+  :help          show this text
+  :ast <input>   show the syntax tree of the input without evaluating it
+  :reset         forget all statements and operator definitions
+  :quit          leave, like Ctrl-D";
+
+/// Everything entered is part of one document, so that the statements can refer to the earlier
+/// ones and use the operators defined before. Unchanged statements aren't evaluated again.
+struct Repl {
+    application: Application,
+    document: String,
+    colored: bool,
+    limits: Limits,
+    /// cancels the running evaluation, replaced for every input
+    interruption: Arc<Mutex<CancellationToken>>,
+}
+
+impl Repl {
+    fn new(colored: bool) -> Self {
+        Repl {
+            application: Application::create(),
+            document: String::new(),
+            colored,
+            limits: Limits::default(),
+            interruption: Arc::new(Mutex::new(CancellationToken::new())),
+        }
+    }
+
+    /// Returns false if the user wants to leave.
+    fn handle(&mut self, input: &str) -> bool {
+        let (command, argument) = match input.trim().split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input.trim(), ""),
+        };
+        match command {
+            ":quit" | ":q" => return false,
+            ":help" | ":h" => println!("{HELP}"),
+            ":reset" => {
+                self.application.reset();
+                self.document.clear();
+                println!("forgot all statements and operators");
+            }
+            ":ast" if argument.is_empty() => println!("usage: :ast <input>"),
+            ":ast" => self.show_syntax_trees(argument),
+            command if command.starts_with(':') => {
+                println!("unknown command '{command}', enter :help for a list of commands")
+            }
+            _ => {
+                for output in self.evaluate(input) {
+                    match output {
+                        Ok(value) => println!("{value}"),
+                        Err(error) => eprint!("{error}"),
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns the values of the statements of `input` and the rendered errors. An input whose
+    /// evaluation was stopped or that makes the document too long is forgotten again, so that
+    /// it isn't evaluated with every later input.
+    fn evaluate(&mut self, input: &str) -> Vec<Result<String, String>> {
+        // a trailing separator would leave an empty statement before the next input
+        let input =
+            input.trim_end_matches(|character: char| character == ';' || character.is_whitespace());
+        if tokenize(input).iter().all(|token| token.kind.is_trivia()) {
+            return vec![];
+        }
+
+        let previous_length = self.document.len();
+        if !self.document.is_empty() {
+            // the linebreak before the separator ends a line comment of the previous input
+            self.document.push_str("\n;\n");
+        }
+        let start = Localization::at(self.document.matches('\n').count(), 0);
+        let input_span = Span {
+            start,
+            end: start,
+            start_offset: self.document.len(),
+            end_offset: self.document.len(),
+        };
+        self.document.push_str(input);
+
+        let cancellation = CancellationToken::new();
+        *self
+            .interruption
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = cancellation.clone();
+        let statement_results = self
+            .application
+            .run_within(
+                self.document.clone(),
+                &Budget::new(self.limits, cancellation),
+            )
+            .into_iter()
+            .filter(|result| !matches!(result.output, Output::Comment(_)));
+
+        let mut outputs = vec![];
+        let mut forget_input = false;
+        for (index, result) in statement_results.enumerate() {
+            // the statements of earlier inputs
+            if result.span.end_offset < input_span.start_offset {
+                continue;
+            }
+            match result.output {
+                Output::Value(value) => outputs.push(Ok(format!("[{}] {value}", index + 1))),
+                Output::Error(mut diagnostic) => {
+                    forget_input |= matches!(
+                        diagnostic.kind,
+                        ErrorKind::Timeout | ErrorKind::Cancelled | ErrorKind::InputTooLarge
+                    );
+                    // an error about the whole document is shown at the whole input
+                    if diagnostic.span.start_offset < input_span.start_offset {
+                        diagnostic.span = Span {
+                            start: input_span.start,
+                            end: diagnostic.span.end,
+                            start_offset: input_span.start_offset,
+                            end_offset: diagnostic.span.end_offset,
+                        };
+                    }
+                    let diagnostic = diagnostic.moved(input_span, Span::default());
+                    outputs.push(Err(render(&diagnostic, input, self.colored)));
+                }
+                Output::Comment(_) => {}
+            }
+        }
+        if forget_input {
+            self.document.truncate(previous_length);
+        }
+        outputs
+    }
+
+    /// Parses with the operators defined so far.
+    fn show_syntax_trees(&self, input: &str) {
+        let mut operators = OperatorTable::new();
+        for definition in self.application.definitions() {
+            operators.define(definition.clone());
+        }
+        for statement in parse_statements_with(input.to_string(), &mut operators) {
+            match statement.result {
+                Ok(node) => print!("{}", node.outline()),
+                Err(error) => eprint!("{}", render(&Diagnostic::from(error), input, self.colored)),
+            }
+        }
+    }
+}
+
+/// Asks for another line while the input obviously isn't finished yet.
+struct InputValidator;
+
+impl Validator for InputValidator {
+    fn validate(&self, context: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(context.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Completer for InputValidator {
+    type Candidate = String;
+}
+
+impl Hinter for InputValidator {
+    type Hint = String;
+}
+
+impl Highlighter for InputValidator {}
+
+impl Helper for InputValidator {}
+
+/// An input is incomplete if it ends in a binary operator, in an unterminated block comment,
+/// or if its last statement has more opening than closing parentheses.
+fn is_incomplete(input: &str) -> bool {
+    if input.trim_start().starts_with(':') {
+        return false;
+    }
+    let tokens = tokenize(input);
+    let open_parentheses = tokens.iter().fold(0, |open, token| match token.kind {
+        TokenKind::OpeningParenthesis => open + 1,
+        TokenKind::ClosingParenthesis => open - 1,
+        TokenKind::Semicolon => 0,
+        _ => open,
+    });
+    let unterminated_comment = matches!(
+        tokens.last(),
+        Some(token) if token.text.starts_with("/*") && (token.text.len() < 4 || !token.text.ends_with("*/"))
+    );
+    let last = tokens.iter().rev().find(|token| !token.kind.is_trivia());
+    let ends_in_operator = matches!(
+        last.map(|token| token.kind),
+        Some(
+            TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Asterisk
+                | TokenKind::Slash
+                | TokenKind::Percent
+                | TokenKind::Caret
+                | TokenKind::Operator
+        )
+    );
+    open_parentheses > 0 || unterminated_comment || ends_in_operator
+}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".lyng2_history"))
+}
+
+/// See https://no-color.org
+fn colors_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none()
+        && std::env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+/// Reads statements until the user leaves, the history is kept in `~/.lyng2_history`.
+pub fn run() -> rustyline::Result<()> {
+    let mut editor: Editor<InputValidator, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(InputValidator));
+    let history = history_file();
+    if let Some(history) = &history {
+        // there is no history before the first session
+        let _ = editor.load_history(history);
+    }
+
+    println!("lyng2 {}, enter :help for help", env!("CARGO_PKG_VERSION"));
+    let mut repl = Repl::new(colors_enabled());
+    // while reading, the editor receives Ctrl-C as a key and the handler isn't called
+    let interruption = repl.interruption.clone();
+    let interrupt = move || {
+        interruption
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .cancel()
+    };
+    if let Err(error) = ctrlc::set_handler(interrupt) {
+        eprintln!("warning: Ctrl-C can't stop evaluations: {error}");
+    }
+    loop {
+        match editor.readline("> ") {
+            Ok(input) => {
+                editor.add_history_entry(input.as_str())?;
+                if !repl.handle(&input) {
+                    break;
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error),
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use lyng2::math::ast::budget::Limits;
+    use lyng2::math::ast::parser::MAX_INPUT_LENGTH;
+
+    use super::{is_incomplete, Repl};
+
+    fn values(outputs: Vec<Result<String, String>>) -> Vec<String> {
+        outputs.into_iter().map(Result::unwrap).collect()
+    }
+
+    fn error(outputs: Vec<Result<String, String>>) -> String {
+        match &outputs[..] {
+            [Err(error)] => error.clone(),
+            outputs => panic!("expected one error, got {outputs:?}"),
+        }
+    }
+
+    #[test]
+    fn inputs_refer_to_earlier_ones() {
+        let mut repl = Repl::new(false);
+
+        assert_eq!(vec!["[1] (1 + 2) = 3"], values(repl.evaluate("1 + 2;")));
+        assert_eq!(
+            vec!["[2] (ans * 2) = 6", "[3] %1 = 3"],
+            values(repl.evaluate("ans * 2; %1"))
+        );
+        assert!(repl.evaluate("  ").is_empty());
+    }
+
+    #[test]
+    fn errors_point_into_the_input() {
+        let mut repl = Repl::new(false);
+        repl.evaluate("1");
+
+        let error = error(repl.evaluate("(2"));
+
+        assert!(error.contains("E0008"), "{error}");
+        assert!(error.contains("1:3"), "{error}");
+    }
+
+    #[test]
+    fn stopped_inputs_are_forgotten() {
+        let mut repl = Repl::new(false);
+        repl.evaluate("1");
+        repl.limits = Limits {
+            timeout: Duration::ZERO,
+            ..Limits::default()
+        };
+
+        let error = error(repl.evaluate("2^3"));
+        repl.limits = Limits::default();
+
+        assert!(error.contains("E0015"), "{error}");
+        assert_eq!(vec!["[2] (ans + 1) = 2"], values(repl.evaluate("ans + 1")));
+    }
+
+    #[test]
+    fn inputs_beyond_the_maximal_length_are_reported_and_forgotten() {
+        let mut repl = Repl::new(false);
+        repl.evaluate("1");
+
+        let error = error(repl.evaluate(&"2 + ".repeat(MAX_INPUT_LENGTH / 4).replace("+ ", "+ 2")));
+
+        assert!(error.contains("E0019"), "{error}");
+        assert_eq!(vec!["[2] ans = 1"], values(repl.evaluate("ans")));
+    }
+
+    #[test]
+    fn unfinished_inputs_are_incomplete() {
+        for input in ["1 +", "(1 + 2", "1; (2", "/* comment", "1 <+>"] {
+            assert!(is_incomplete(input), "{input}");
+        }
+        for input in ["(1 + 2)", "1 + 2", "(1; 2", ":help ("] {
+            assert!(!is_incomplete(input), "{input}");
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_graphql::async_stream::stream;
+use async_graphql::{Json, SimpleObject, Union};
+use futures_util::Stream;
+use log::{debug, info};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::chat::repository::DocumentRepository;
+use crate::chat::User;
+use crate::math::collaboration::operation::Operation;
+use crate::math::collaboration::SharedDocument;
+use crate::math::evaluation_pool::EvaluationPool;
+use crate::math::protocol::ServerMessage;
+
+/// The documents that are edited together, by their id. The owner of a document opens it,
+/// then other users can join it with its id, until all participants have left.
+#[derive(Default)]
+pub struct Collaborations {
+    documents: Mutex<HashMap<String, Collaboration>>,
+    subscribers: Mutex<HashMap<User, Subscription>>,
+}
+
+struct Collaboration {
+    owner: String,
+    document: SharedDocument,
+    participants: HashMap<String, User>,
+    /// one save of the document at a time, so that an older text can't overwrite a newer one
+    saving: Arc<Mutex<()>>,
+}
+
+/// The latest subscription of a user, to the events of one document.
+struct Subscription {
+    document_id: String,
+    sender: mpsc::UnboundedSender<DocumentEvent>,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct Cursor {
+    user: User,
+    /// the character offset in the document
+    position: usize,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct SharedDocumentState {
+    id: String,
+    revision: u64,
+    content: String,
+    cursors: Vec<Cursor>,
+}
+
+/// A change of a participant, in the format of ot.js, as it applies to `revision - 1`.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct DocumentChange {
+    document_id: String,
+    revision: u64,
+    user: User,
+    operation: Json<Operation>,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct CursorMove {
+    document_id: String,
+    revision: u64,
+    cursor: Cursor,
+}
+
+/// The `result` and `diagnostic` messages of the math websocket protocol, with the revision as id.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct DocumentEvaluation {
+    document_id: String,
+    revision: u64,
+    messages: Json<Vec<ServerMessage>>,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct ParticipantLeft {
+    document_id: String,
+    user: User,
+}
+
+#[derive(Clone, Debug, Union)]
+pub enum DocumentEvent {
+    Change(DocumentChange),
+    CursorMove(CursorMove),
+    Evaluation(DocumentEvaluation),
+    ParticipantLeft(ParticipantLeft),
+}
+
+impl DocumentEvent {
+    fn document_id(&self) -> &str {
+        match self {
+            DocumentEvent::Change(DocumentChange { document_id, .. })
+            | DocumentEvent::CursorMove(CursorMove { document_id, .. })
+            | DocumentEvent::Evaluation(DocumentEvaluation { document_id, .. })
+            | DocumentEvent::ParticipantLeft(ParticipantLeft { document_id, .. }) => document_id,
+        }
+    }
+}
+
+impl Collaboration {
+    fn state(&self, id: &str) -> SharedDocumentState {
+        SharedDocumentState {
+            id: id.to_string(),
+            revision: self.document.revision(),
+            content: self.document.text().to_string(),
+            cursors: self
+                .document
+                .cursors()
+                .iter()
+                .filter_map(|(participant, &position)| {
+                    let user = self.participants.get(participant)?.clone();
+                    Some(Cursor { user, position })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Collaborations {
+    /// `None` if the document isn't open and `user` doesn't own a document with this id.
+    pub async fn join<Repository: DocumentRepository>(
+        &self,
+        repository: &Repository,
+        user: &User,
+        id: &str,
+    ) -> Option<SharedDocumentState> {
+        let mut documents = self.documents.lock().await;
+        if !documents.contains_key(id) {
+            let stored = repository.get_document(&user.id, id).await?;
+            info!("opening document {id} for collaboration");
+            let collaboration = Collaboration {
+                owner: user.id.clone(),
+                document: SharedDocument::new(stored.content),
+                participants: HashMap::new(),
+                saving: Arc::default(),
+            };
+            documents.insert(id.to_string(), collaboration);
+        }
+        let collaboration = documents.get_mut(id).expect("the document is open");
+        collaboration
+            .participants
+            .insert(user.id.clone(), user.clone());
+        let position = collaboration.document.join(&user.id);
+        let state = collaboration.state(id);
+        drop(documents);
+
+        let cursor_move = CursorMove {
+            document_id: id.to_string(),
+            revision: state.revision,
+            cursor: Cursor {
+                user: user.clone(),
+                position,
+            },
+        };
+        self.notify(DocumentEvent::CursorMove(cursor_move), Some(user))
+            .await;
+        Some(state)
+    }
+
+    /// Merges the change of `user`, made against `revision`, into the document and saves it.
+    /// The other participants receive the change, all of them the result of its evaluation.
+    pub async fn edit<Repository: DocumentRepository>(
+        self: &Arc<Self>,
+        repository: &Repository,
+        pool: &EvaluationPool,
+        user: &User,
+        id: &str,
+        revision: u64,
+        operation: Operation,
+    ) -> Result<DocumentChange, String> {
+        let mut documents = self.documents.lock().await;
+        let collaboration = participating(&mut documents, user, id)?;
+        let operation = collaboration.document.apply(revision, operation)?;
+        let (owner, text, saving) = (
+            collaboration.owner.clone(),
+            collaboration.document.text().to_string(),
+            collaboration.saving.clone(),
+        );
+        let change = DocumentChange {
+            document_id: id.to_string(),
+            revision: collaboration.document.revision(),
+            user: user.clone(),
+            operation: Json(operation),
+        };
+        let evaluation = collaboration.document.evaluation();
+        // sent before another change can be applied, so that the participants get them in order
+        self.notify(DocumentEvent::Change(change.clone()), Some(user))
+            .await;
+        drop(documents);
+
+        self.save(repository, &saving, &owner, id, text).await;
+
+        // evaluated once for all participants
+        let collaborations = self.clone();
+        let pool = pool.clone();
+        let (document_id, revision) = (change.document_id.clone(), change.revision);
+        tokio::task::spawn(async move {
+            let messages = pool.run(evaluation).await;
+            // a newer change cancelled the evaluation
+            if messages.is_empty() {
+                return;
+            }
+            let evaluation = DocumentEvaluation {
+                document_id,
+                revision,
+                messages: Json(messages),
+            };
+            collaborations
+                .notify(DocumentEvent::Evaluation(evaluation), None)
+                .await;
+        });
+        Ok(change)
+    }
+
+    /// Saves the latest text of the document, which includes `text` if the document is still
+    /// open, without holding the lock of the documents while the repository is busy.
+    async fn save<Repository: DocumentRepository>(
+        &self,
+        repository: &Repository,
+        saving: &Mutex<()>,
+        owner: &str,
+        id: &str,
+        text: String,
+    ) {
+        let _saving = saving.lock().await;
+        let latest = self
+            .documents
+            .lock()
+            .await
+            .get(id)
+            .map(|collaboration| collaboration.document.text().to_string());
+        repository
+            .save_document(owner, id, latest.unwrap_or(text))
+            .await;
+    }
+
+    /// `position` is a character offset in the text of `revision`.
+    pub async fn move_cursor(
+        &self,
+        user: &User,
+        id: &str,
+        revision: u64,
+        position: usize,
+    ) -> Result<CursorMove, String> {
+        let mut documents = self.documents.lock().await;
+        let collaboration = participating(&mut documents, user, id)?;
+        let position = collaboration
+            .document
+            .move_cursor(&user.id, revision, position)?;
+        let cursor_move = CursorMove {
+            document_id: id.to_string(),
+            revision: collaboration.document.revision(),
+            cursor: Cursor {
+                user: user.clone(),
+                position,
+            },
+        };
+        // in order with the changes
+        self.notify(DocumentEvent::CursorMove(cursor_move.clone()), Some(user))
+            .await;
+        drop(documents);
+        Ok(cursor_move)
+    }
+
+    /// The document is closed when its last participant leaves, its text is saved already.
+    pub async fn leave(&self, user: &User, id: &str) -> bool {
+        let mut documents = self.documents.lock().await;
+        let Ok(collaboration) = participating(&mut documents, user, id) else {
+            return false;
+        };
+        collaboration.participants.remove(&user.id);
+        collaboration.document.leave(&user.id);
+        if collaboration.participants.is_empty() {
+            info!("closing document {id}, all participants have left");
+            collaboration.document.close();
+            documents.remove(id);
+        }
+        drop(documents);
+
+        let left = ParticipantLeft {
+            document_id: id.to_string(),
+            user: user.clone(),
+        };
+        self.notify(DocumentEvent::ParticipantLeft(left), Some(user))
+            .await;
+        true
+    }
+
+    /// The events of the document with id `id`. A user receives the events of the document
+    /// of their latest subscription only. If that subscription ends, e.g. because the connection
+    /// was lost, the user leaves the document.
+    pub async fn subscribe(
+        self: &Arc<Self>,
+        user: User,
+        id: String,
+    ) -> impl Stream<Item = DocumentEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel::<DocumentEvent>();
+        let subscription = Subscription {
+            document_id: id.clone(),
+            sender,
+        };
+        self.subscribers
+            .lock()
+            .await
+            .insert(user.clone(), subscription);
+        let mut departure = Departure {
+            collaborations: self.clone(),
+            user,
+            id,
+            receiver,
+        };
+
+        stream! {
+            while let Some(event) = departure.receiver.recv().await {
+                yield event;
+            }
+        }
+    }
+
+    /// Sends `event` to the subscribers of its document but `acting_user`, and forgets the
+    /// subscriptions whose streams are gone.
+    async fn notify(&self, event: DocumentEvent, acting_user: Option<&User>) {
+        debug!("notifying: {event:?}");
+        let mut subscribers = self.subscribers.lock().await;
+        subscribers.retain(|user, subscription| {
+            Some(user) == acting_user
+                || subscription.document_id != event.document_id()
+                || subscription.sender.send(event.clone()).is_ok()
+        });
+    }
+}
+
+/// Receives the events of a subscription and leaves the document when the subscription is
+/// dropped, unless a newer subscription of the user to the same document replaced it.
+struct Departure {
+    collaborations: Arc<Collaborations>,
+    user: User,
+    id: String,
+    receiver: mpsc::UnboundedReceiver<DocumentEvent>,
+}
+
+impl Drop for Departure {
+    fn drop(&mut self) {
+        // closed before the check below, which tells this subscription from a newer one by it
+        self.receiver.close();
+        let collaborations = self.collaborations.clone();
+        let (user, id) = (self.user.clone(), self.id.clone());
+        tokio::task::spawn(async move {
+            let mut subscribers = collaborations.subscribers.lock().await;
+            match subscribers.get(&user) {
+                Some(current) if current.sender.is_closed() => {
+                    subscribers.remove(&user);
+                }
+                Some(current) if current.document_id == id => return,
+                // a subscription to another document, or removed already when sending failed
+                _ => {}
+            }
+            drop(subscribers);
+            collaborations.leave(&user, &id).await;
+        });
+    }
+}
+
+fn participating<'a>(
+    documents: &'a mut HashMap<String, Collaboration>,
+    user: &User,
+    id: &str,
+) -> Result<&'a mut Collaboration, String> {
+    documents
+        .get_mut(id)
+        .filter(|collaboration| collaboration.participants.contains_key(&user.id))
+        .ok_or_else(|| format!("join the document {id} first"))
+}
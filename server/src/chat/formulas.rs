@@ -0,0 +1,66 @@
+use async_graphql::{SimpleObject, Union};
+
+use crate::math::ast::budget::{Budget, CancellationToken};
+use crate::math::evaluation_pool::EvaluationPool;
+use crate::math::formulas::{segments, Formula, Segment};
+
+#[derive(Clone, Debug, SimpleObject)]
+pub struct TextSegment {
+    text: String,
+}
+
+/// A formula between dollar signs.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct FormulaSegment {
+    /// the text between the dollar signs
+    source: String,
+    /// for LaTeX's math mode, with the values if the formula was evaluated, `null` on errors
+    latex: Option<String>,
+    /// like the statements of the math websocket protocol, `null` if it wasn't evaluated
+    result: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Clone, Debug, Union)]
+pub enum MessageSegment {
+    Text(TextSegment),
+    Formula(FormulaSegment),
+}
+
+/// The text and the formulas of `message`. The formulas are evaluated by `pool` within its limits
+/// for all formulas of the message together, if `evaluate` is set, otherwise they are only parsed.
+/// If that fails, the whole message is one text segment.
+pub async fn message_segments(
+    pool: &EvaluationPool,
+    message: &str,
+    evaluate: bool,
+) -> Vec<MessageSegment> {
+    let text = message.to_string();
+    let result = pool
+        .run_with(move |limits| {
+            let budget = Budget::new(limits, CancellationToken::new());
+            segments(&text, evaluate.then_some(&budget))
+        })
+        .await
+        .unwrap_or_else(|| vec![Segment::Text(message.to_string())]);
+    result.into_iter().map(MessageSegment::from).collect()
+}
+
+impl From<Segment> for MessageSegment {
+    fn from(segment: Segment) -> Self {
+        match segment {
+            Segment::Text(text) => MessageSegment::Text(TextSegment { text }),
+            Segment::Formula(Formula {
+                source,
+                latex,
+                result,
+                error,
+            }) => MessageSegment::Formula(FormulaSegment {
+                source,
+                latex,
+                result,
+                error,
+            }),
+        }
+    }
+}
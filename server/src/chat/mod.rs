@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use async_graphql::async_stream::stream;
 use async_graphql::extensions::Logger;
-use async_graphql::{Context, Object, SimpleObject, Subscription};
+use async_graphql::{Context, Json, Object, SimpleObject, Subscription};
 use chrono::{DateTime, Local};
 use futures_util::Stream;
 use log::{debug, info};
@@ -15,13 +16,24 @@ use tokio::sync::mpsc::UnboundedSender;
 use warp::http::header::SET_COOKIE;
 
 use crate::chat::auth::{create_auth_token, AuthExtensionFactory, AuthUser, AUTH_COOKIE_NAME};
-use crate::chat::repository::ChatRepository;
+use crate::chat::collaboration::{
+    Collaborations, CursorMove, DocumentChange, DocumentEvent, SharedDocumentState,
+};
+use crate::chat::formulas::{message_segments, MessageSegment};
+use crate::chat::repository::{ChatRepository, DocumentRepository};
+use crate::math::collaboration::operation::Operation;
+use crate::math::evaluation_pool::EvaluationPool;
 
 pub mod auth;
+pub mod collaboration;
+pub mod formulas;
 pub mod repository;
 #[cfg(test)]
 mod test;
 
+/// The length of a chat message in bytes, so that its formulas are found quickly.
+pub const MAX_MESSAGE_LENGTH: usize = 10_000;
+
 type Streams<T> = Mutex<HashMap<User, UnboundedSender<T>>>;
 
 pub type Schema<Repository> =
@@ -33,14 +45,30 @@ pub struct User {
     name: String,
 }
 
+/// A math worksheet of a user.
+#[derive(Clone, Debug, SimpleObject, Eq, PartialEq, Deserialize)]
+pub struct Document {
+    id: String,
+    name: String,
+    content: String,
+    /// the id of the user who created the document
+    #[graphql(skip)]
+    owner: String,
+}
+
 #[derive(Clone, Debug, SimpleObject)]
 pub struct Message {
     user: User,
     message: String,
+    /// the text and the formulas of `message`
+    segments: Vec<MessageSegment>,
     date: DateTime<Local>,
 }
 
-pub fn build_schema<Repository: ChatRepository + 'static>() -> Schema<Repository> {
+/// Shared documents and the formulas of messages are evaluated with `pool`.
+pub fn build_schema<Repository: ChatRepository + DocumentRepository + 'static>(
+    pool: EvaluationPool,
+) -> Schema<Repository> {
     Schema::build(
         Query::default(),
         Mutation::default(),
@@ -49,6 +77,8 @@ pub fn build_schema<Repository: ChatRepository + 'static>() -> Schema<Repository
     .data(Repository::default())
     .data(Streams::<User>::default())
     .data(Streams::<Message>::default())
+    .data(Arc::new(Collaborations::default()))
+    .data(pool)
     .extension(Logger)
     .extension(AuthExtensionFactory)
     .finish()
@@ -60,7 +90,7 @@ pub struct Query<Repository> {
 }
 
 #[Object]
-impl<Repository: ChatRepository + 'static> Query<Repository> {
+impl<Repository: ChatRepository + DocumentRepository + 'static> Query<Repository> {
     async fn get_users<'a>(&self, ctx: &Context<'a>) -> Vec<User> {
         info!("calling get_users");
         ctx.data_unchecked::<Repository>().get_users().await
@@ -76,6 +106,22 @@ impl<Repository: ChatRepository + 'static> Query<Repository> {
         info!("found logged in user: {user:?}");
         user
     }
+
+    /// The documents of the logged in user.
+    async fn get_documents(&self, ctx: &Context<'_>) -> Vec<Document> {
+        let owner = &ctx.data_unchecked::<AuthUser>().id;
+        ctx.data_unchecked::<Repository>()
+            .get_documents(owner)
+            .await
+    }
+
+    /// `null` if the logged in user has no document with this id.
+    async fn get_document(&self, ctx: &Context<'_>, id: String) -> Option<Document> {
+        let owner = &ctx.data_unchecked::<AuthUser>().id;
+        ctx.data_unchecked::<Repository>()
+            .get_document(owner, &id)
+            .await
+    }
 }
 
 #[derive(Default)]
@@ -84,7 +130,7 @@ pub struct Mutation<Repository> {
 }
 
 #[Object]
-impl<Repository: ChatRepository + 'static> Mutation<Repository> {
+impl<Repository: ChatRepository + DocumentRepository + 'static> Mutation<Repository> {
     async fn register(&self, ctx: &Context<'_>, name: String) -> User {
         let new_user = ctx
             .data_unchecked::<Repository>()
@@ -92,7 +138,7 @@ impl<Repository: ChatRepository + 'static> Mutation<Repository> {
             .await;
 
         let mut subscribers = ctx.data_unchecked::<Streams<User>>().lock().await;
-        notify_subscribers(new_user.clone(), &new_user, &mut subscribers);
+        notify_subscribers(new_user.clone(), Some(&new_user), &mut subscribers);
         info!("new user registered: {new_user:?}");
 
         let auth_cookie = create_auth_token(&new_user);
@@ -104,8 +150,23 @@ impl<Repository: ChatRepository + 'static> Mutation<Repository> {
         new_user
     }
 
-    async fn send_message(&self, ctx: &Context<'_>, message: String) -> Message {
+    /// Formulas between dollar signs, like `$1 + 2$`, are evaluated unless `evaluate` is false.
+    async fn send_message(
+        &self,
+        ctx: &Context<'_>,
+        message: String,
+        #[graphql(default = true)] evaluate: bool,
+    ) -> async_graphql::Result<Message> {
+        if message.len() > MAX_MESSAGE_LENGTH {
+            return Err(format!(
+                "the message has {} bytes, at most {MAX_MESSAGE_LENGTH} are accepted",
+                message.len()
+            )
+            .into());
+        }
         info!("new message received: {message}");
+        let segments =
+            message_segments(ctx.data_unchecked::<EvaluationPool>(), &message, evaluate).await;
         let mut subscribers = ctx.data_unchecked::<Streams<Message>>().lock().await;
 
         let user = get_user::<Repository>(ctx).await;
@@ -114,10 +175,118 @@ impl<Repository: ChatRepository + 'static> Mutation<Repository> {
         let message = Message {
             user: user.clone(),
             message,
+            segments,
             date: Local::now(),
         };
-        notify_subscribers(message.clone(), &user, &mut subscribers);
-        message
+        notify_subscribers(message.clone(), Some(&user), &mut subscribers);
+        Ok(message)
+    }
+
+    /// Creates an empty document of the logged in user.
+    async fn create_document(&self, ctx: &Context<'_>, name: String) -> Document {
+        let owner = &ctx.data_unchecked::<AuthUser>().id;
+        let document = ctx
+            .data_unchecked::<Repository>()
+            .create_document(owner, name)
+            .await;
+        info!("document created: {document:?}");
+        document
+    }
+
+    /// Like the other operations on documents, `null` if the logged in user has no document with this id.
+    async fn rename_document(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        name: String,
+    ) -> Option<Document> {
+        let owner = &ctx.data_unchecked::<AuthUser>().id;
+        ctx.data_unchecked::<Repository>()
+            .rename_document(owner, &id, name)
+            .await
+    }
+
+    /// Replaces the content of the document.
+    async fn save_document(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        content: String,
+    ) -> Option<Document> {
+        let owner = &ctx.data_unchecked::<AuthUser>().id;
+        ctx.data_unchecked::<Repository>()
+            .save_document(owner, &id, content)
+            .await
+    }
+
+    /// Returns the deleted document.
+    async fn delete_document(&self, ctx: &Context<'_>, id: String) -> Option<Document> {
+        let owner = &ctx.data_unchecked::<AuthUser>().id;
+        let document = ctx
+            .data_unchecked::<Repository>()
+            .delete_document(owner, &id)
+            .await;
+        info!("document deleted: {document:?}");
+        document
+    }
+
+    /// Starts editing a document together with other users: its owner opens it, then other users
+    /// join it with its id. `null` if the document isn't open and the logged in user has no
+    /// document with this id.
+    async fn join_document(&self, ctx: &Context<'_>, id: String) -> Option<SharedDocumentState> {
+        let user = get_user::<Repository>(ctx).await;
+        info!("{user:?} joins document {id}");
+        ctx.data_unchecked::<Arc<Collaborations>>()
+            .join(ctx.data_unchecked::<Repository>(), &user, &id)
+            .await
+    }
+
+    /// Applies a change made against `revision` of a joined document, in the format of ot.js.
+    async fn edit_document(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        revision: u64,
+        operation: Json<Operation>,
+    ) -> async_graphql::Result<DocumentChange> {
+        let user = get_user::<Repository>(ctx).await;
+        let change = ctx
+            .data_unchecked::<Arc<Collaborations>>()
+            .edit(
+                ctx.data_unchecked::<Repository>(),
+                ctx.data_unchecked::<EvaluationPool>(),
+                &user,
+                &id,
+                revision,
+                operation.0,
+            )
+            .await?;
+        Ok(change)
+    }
+
+    /// `position` is a character offset in the text of `revision` of a joined document.
+    async fn move_cursor(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        revision: u64,
+        position: usize,
+    ) -> async_graphql::Result<CursorMove> {
+        let user = get_user::<Repository>(ctx).await;
+        let cursor_move = ctx
+            .data_unchecked::<Arc<Collaborations>>()
+            .move_cursor(&user, &id, revision, position)
+            .await?;
+        Ok(cursor_move)
+    }
+
+    /// False if the logged in user didn't join the document.
+    async fn leave_document(&self, ctx: &Context<'_>, id: String) -> bool {
+        let user = get_user::<Repository>(ctx).await;
+        info!("{user:?} leaves document {id}");
+        ctx.data_unchecked::<Arc<Collaborations>>()
+            .leave(&user, &id)
+            .await
     }
 }
 
@@ -129,14 +298,15 @@ async fn get_user<Repository: ChatRepository + 'static>(ctx: &Context<'_>) -> Us
         .unwrap()
 }
 
+/// Sends `message` to all subscribers but `acting_user`.
 fn notify_subscribers<T: Clone + Debug>(
     message: T,
-    acting_user: &User,
+    acting_user: Option<&User>,
     subscribers: &mut HashMap<User, UnboundedSender<T>>,
 ) {
     let disconnected_users: Vec<_> = subscribers
         .iter()
-        .filter(|(stream_user, _)| *stream_user != acting_user)
+        .filter(|(stream_user, _)| Some(*stream_user) != acting_user)
         .filter_map(|(stream_user, stream)| {
             send_message_and_check_for_disconnect(message.clone(), stream_user, stream)
         })
@@ -183,6 +353,20 @@ impl<Repository: ChatRepository + 'static> Subscription<Repository> {
         }
     }
 
+    /// The changes, cursors and evaluations of a joined document.
+    async fn get_document_events(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> impl Stream<Item = DocumentEvent> {
+        info!("new subscription for document {id}");
+
+        let user = get_user::<Repository>(ctx).await;
+        ctx.data_unchecked::<Arc<Collaborations>>()
+            .subscribe(user, id)
+            .await
+    }
+
     async fn get_new_messages(&self, ctx: &Context<'_>) -> impl Stream<Item = Message> {
         info!("new subscription for messages");
 
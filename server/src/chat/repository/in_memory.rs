@@ -1,11 +1,27 @@
-use crate::chat::repository::ChatRepository;
-use crate::chat::User;
+use crate::chat::repository::{ChatRepository, DocumentRepository};
+use crate::chat::{Document, User};
 use async_trait::async_trait;
 use std::sync::Mutex;
 
 #[derive(Default)]
 pub struct InMemoryRepository {
     users: Mutex<Vec<User>>,
+    documents: Mutex<Documents>,
+}
+
+/// Deleted documents leave gaps, so the ids are counted separately.
+#[derive(Default)]
+struct Documents {
+    created: usize,
+    documents: Vec<Document>,
+}
+
+impl Documents {
+    fn find_mut(&mut self, owner: &str, id: &str) -> Option<&mut Document> {
+        self.documents
+            .iter_mut()
+            .find(|document| document.id == id && document.owner == owner)
+    }
 }
 
 #[async_trait]
@@ -29,3 +45,57 @@ impl ChatRepository for InMemoryRepository {
         new_user
     }
 }
+
+#[async_trait]
+impl DocumentRepository for InMemoryRepository {
+    async fn create_document(&self, owner: &str, name: String) -> Document {
+        let mut documents = self.documents.lock().unwrap();
+        let new_document = Document {
+            id: format!("Document#{id}", id = documents.created),
+            name,
+            content: String::new(),
+            owner: owner.to_string(),
+        };
+        documents.created += 1;
+        documents.documents.push(new_document.clone());
+        new_document
+    }
+
+    async fn get_documents(&self, owner: &str) -> Vec<Document> {
+        let documents = self.documents.lock().unwrap();
+        documents
+            .documents
+            .iter()
+            .filter(|document| document.owner == owner)
+            .cloned()
+            .collect()
+    }
+
+    async fn get_document(&self, owner: &str, id: &str) -> Option<Document> {
+        let mut documents = self.documents.lock().unwrap();
+        documents.find_mut(owner, id).cloned()
+    }
+
+    async fn rename_document(&self, owner: &str, id: &str, name: String) -> Option<Document> {
+        let mut documents = self.documents.lock().unwrap();
+        let document = documents.find_mut(owner, id)?;
+        document.name = name;
+        Some(document.clone())
+    }
+
+    async fn save_document(&self, owner: &str, id: &str, content: String) -> Option<Document> {
+        let mut documents = self.documents.lock().unwrap();
+        let document = documents.find_mut(owner, id)?;
+        document.content = content;
+        Some(document.clone())
+    }
+
+    async fn delete_document(&self, owner: &str, id: &str) -> Option<Document> {
+        let mut documents = self.documents.lock().unwrap();
+        let index = documents
+            .documents
+            .iter()
+            .position(|document| document.id == id && document.owner == owner)?;
+        Some(documents.documents.remove(index))
+    }
+}
@@ -1,5 +1,5 @@
 pub use crate::chat::repository::in_memory::InMemoryRepository;
-use crate::chat::User;
+use crate::chat::{Document, User};
 use async_trait::async_trait;
 
 pub mod in_memory;
@@ -13,3 +13,21 @@ pub trait ChatRepository: Send + Sync + Default {
 
     async fn register_new_user(&self, name: String) -> User;
 }
+
+/// The documents of the users, every document belongs to the user who created it.
+/// Documents of other users are treated like documents that don't exist.
+#[async_trait]
+pub trait DocumentRepository: Send + Sync + Default {
+    async fn create_document(&self, owner: &str, name: String) -> Document;
+
+    async fn get_documents(&self, owner: &str) -> Vec<Document>;
+
+    async fn get_document(&self, owner: &str, id: &str) -> Option<Document>;
+
+    async fn rename_document(&self, owner: &str, id: &str, name: String) -> Option<Document>;
+
+    async fn save_document(&self, owner: &str, id: &str, content: String) -> Option<Document>;
+
+    /// Returns the deleted document.
+    async fn delete_document(&self, owner: &str, id: &str) -> Option<Document>;
+}
@@ -7,8 +7,8 @@ use surrealdb::opt::auth::Root;
 use surrealdb::sql::thing;
 use surrealdb::{Connection, Error, Surreal};
 
-use crate::chat::repository::ChatRepository;
-use crate::chat::User;
+use crate::chat::repository::{ChatRepository, DocumentRepository};
+use crate::chat::{Document, User};
 
 pub struct SurrealDbAdapter(GenericSurrealDbAdapter<Client>);
 
@@ -35,11 +35,43 @@ impl ChatRepository for SurrealDbAdapter {
     }
 }
 
+#[async_trait]
+impl DocumentRepository for SurrealDbAdapter {
+    async fn create_document(&self, owner: &str, name: String) -> Document {
+        self.0.create_document(owner, name).await
+    }
+
+    async fn get_documents(&self, owner: &str) -> Vec<Document> {
+        self.0.get_documents(owner).await
+    }
+
+    async fn get_document(&self, owner: &str, id: &str) -> Option<Document> {
+        self.0.get_document(owner, id).await
+    }
+
+    async fn rename_document(&self, owner: &str, id: &str, name: String) -> Option<Document> {
+        self.0
+            .update_document(owner, id, json!({ "name": name }))
+            .await
+    }
+
+    async fn save_document(&self, owner: &str, id: &str, content: String) -> Option<Document> {
+        self.0
+            .update_document(owner, id, json!({ "content": content }))
+            .await
+    }
+
+    async fn delete_document(&self, owner: &str, id: &str) -> Option<Document> {
+        self.0.delete_document(owner, id).await
+    }
+}
+
 struct GenericSurrealDbAdapter<C: Connection> {
     pub database: Surreal<C>,
 }
 
 const CHAT_USER: &str = "chat_user";
+const DOCUMENT: &str = "document";
 
 impl<C: Connection> GenericSurrealDbAdapter<C> {
     async fn get_users(&self) -> Vec<User> {
@@ -64,6 +96,60 @@ impl<C: Connection> GenericSurrealDbAdapter<C> {
             .await
             .unwrap()
     }
+
+    async fn create_document(&self, owner: &str, name: String) -> Document {
+        info!("new document {name} of {owner}");
+        self.database
+            .create(DOCUMENT)
+            .content(json!({ "name": name, "content": "", "owner": owner }))
+            .await
+            .unwrap()
+    }
+
+    async fn get_documents(&self, owner: &str) -> Vec<Document> {
+        info!("getting all documents of {owner}");
+        self.database
+            .query("SELECT * FROM type::table($table) WHERE owner = $owner")
+            .bind(("table", DOCUMENT))
+            .bind(("owner", owner))
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap()
+    }
+
+    async fn get_document(&self, owner: &str, id: &str) -> Option<Document> {
+        info!("getting document {id} of {owner}");
+        let thing = thing(id).ok()?;
+        if thing.tb != DOCUMENT {
+            return None;
+        }
+        let document: Option<Document> = self.database.select(thing).await.unwrap();
+        document.filter(|document| document.owner == owner)
+    }
+
+    /// Merges `changes` into the document, if it belongs to `owner`.
+    async fn update_document(
+        &self,
+        owner: &str,
+        id: &str,
+        changes: serde_json::Value,
+    ) -> Option<Document> {
+        self.get_document(owner, id).await?;
+        info!("updating document {id}");
+        self.database
+            .update(thing(id).ok()?)
+            .merge(changes)
+            .await
+            .unwrap()
+    }
+
+    async fn delete_document(&self, owner: &str, id: &str) -> Option<Document> {
+        let document = self.get_document(owner, id).await?;
+        info!("deleting document {id}");
+        self.database.delete(thing(id).ok()?).await.unwrap();
+        Some(document)
+    }
 }
 
 async fn get_db() -> Result<Surreal<Client>, Error> {
@@ -85,6 +171,7 @@ async fn get_db() -> Result<Surreal<Client>, Error> {
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
     use surrealdb::engine::any::{connect, Any};
 
     use crate::chat::repository::surreal_db::GenericSurrealDbAdapter;
@@ -112,6 +199,69 @@ mod tests {
         assert_eq!(under_test.get_users().await, vec![user]);
     }
 
+    #[tokio::test]
+    async fn create_document_and_query_it() {
+        let under_test = get_test_db_adapter().await;
+
+        let document = under_test
+            .create_document("chat_user:owner", String::from("sheet"))
+            .await;
+
+        assert_eq!(document.name, "sheet");
+        assert_eq!(document.content, "");
+        assert_eq!(
+            under_test
+                .get_document("chat_user:owner", &document.id)
+                .await,
+            Some(document.clone())
+        );
+        assert_eq!(
+            under_test.get_documents("chat_user:owner").await,
+            vec![document.clone()]
+        );
+        assert_eq!(
+            under_test
+                .get_document("chat_user:other", &document.id)
+                .await,
+            None
+        );
+        assert!(under_test.get_documents("chat_user:other").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_and_delete_document() {
+        let under_test = get_test_db_adapter().await;
+        let document = under_test
+            .create_document("chat_user:owner", String::from("sheet"))
+            .await;
+
+        let other_update = under_test
+            .update_document("chat_user:other", &document.id, json!({ "name": "x" }))
+            .await;
+        let saved = under_test
+            .update_document(
+                "chat_user:owner",
+                &document.id,
+                json!({ "content": "1 + 2" }),
+            )
+            .await
+            .unwrap();
+        let deleted = under_test
+            .delete_document("chat_user:owner", &document.id)
+            .await;
+
+        assert_eq!(other_update, None);
+        assert_eq!(saved.name, "sheet");
+        assert_eq!(saved.content, "1 + 2");
+        assert_eq!(deleted, Some(saved));
+        assert_eq!(
+            under_test
+                .get_document("chat_user:owner", &document.id)
+                .await,
+            None
+        );
+    }
+
     async fn get_test_db_adapter() -> GenericSurrealDbAdapter<Any> {
         let database = connect("memory").await.unwrap();
         database
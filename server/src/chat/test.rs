@@ -9,11 +9,13 @@ use crate::chat::auth::AuthUser;
 use crate::chat::repository::InMemoryRepository;
 use crate::chat::Schema;
 use crate::from_json;
+use crate::math::ast::budget::Limits;
+use crate::math::evaluation_pool::EvaluationPool;
 
 type TestSchema = Schema<InMemoryRepository>;
 
 fn build_schema() -> TestSchema {
-    crate::chat::build_schema::<InMemoryRepository>()
+    crate::chat::build_schema::<InMemoryRepository>(EvaluationPool::new(Limits::default(), 1))
 }
 
 #[tokio::test]
@@ -124,6 +126,7 @@ mod send_messages {
     use chrono::{DateTime, Local};
 
     use super::*;
+    use crate::chat::MAX_MESSAGE_LENGTH;
 
     #[tokio::test]
     async fn send_message_without_authentication_fails() {
@@ -217,6 +220,363 @@ mod send_messages {
         }
         panic!("Did not find expected date in {:?}", sent_message.data);
     }
+
+    const SEGMENTS: &str = "segments { __typename \
+        ... on TextSegment { text } \
+        ... on FormulaSegment { source, latex, result, error } }";
+
+    #[tokio::test]
+    async fn formulas_of_sent_messages_are_evaluated() {
+        let schema = build_schema();
+        register_user(&schema).await;
+
+        let query = format!(
+            "mutation {{ sendMessage(message: \"so $1 + 2$ and $1 / 0$\") {{ {SEGMENTS} }} }}"
+        );
+        let response = execute_as(&schema, "User#0", &query).await;
+
+        let data = response.data.into_json().unwrap();
+        let segments = &data["sendMessage"]["segments"];
+        assert_eq!(
+            serde_json::json!({"__typename": "TextSegment", "text": "so "}),
+            segments[0]
+        );
+        assert_eq!(
+            serde_json::json!({
+                "__typename": "FormulaSegment",
+                "source": "1 + 2",
+                "latex": "1 + 2 = 3",
+                "result": "(1 + 2) = 3",
+                "error": null
+            }),
+            segments[1]
+        );
+        assert_eq!("1 / 0", segments[3]["source"]);
+        assert!(segments[3]["latex"].is_null(), "{segments}");
+        assert!(segments[3]["error"].is_string(), "{segments}");
+    }
+
+    #[tokio::test]
+    async fn long_messages_are_rejected() {
+        let schema = build_schema();
+        register_user(&schema).await;
+
+        let message = "$1".repeat(MAX_MESSAGE_LENGTH / 2 + 1);
+        let query = format!("mutation {{ sendMessage(message: \"{message}\") {{ message }} }}");
+        let request = Request::from(query).data(some_auth_user());
+        let response = schema.execute(request).await;
+
+        assert_eq!(1, response.errors.len());
+        assert_eq!(
+            format!("the message has 10002 bytes, at most {MAX_MESSAGE_LENGTH} are accepted"),
+            response.errors[0].message
+        );
+    }
+
+    #[tokio::test]
+    async fn formulas_are_only_parsed_without_evaluation() {
+        let schema = build_schema();
+        register_user(&schema).await;
+
+        let query = format!(
+            "mutation {{ sendMessage(message: \"$x^2$\", evaluate: false) {{ {SEGMENTS} }} }}"
+        );
+        let response = execute_as(&schema, "User#0", &query).await;
+
+        assert_eq!(
+            from_json!({"sendMessage": {"segments": [{
+                "__typename": "FormulaSegment",
+                "source": "x^2",
+                "latex": "x^{2}",
+                "result": null,
+                "error": null
+            }]}}),
+            response.data
+        );
+    }
+}
+
+mod documents {
+    use super::*;
+
+    #[tokio::test]
+    async fn documents_without_authentication_fail() {
+        let schema = build_schema();
+
+        let response = schema
+            .execute("mutation { createDocument(name: \"sheet\") { id } }")
+            .await;
+
+        assert_missing_authentication(response);
+    }
+
+    #[tokio::test]
+    async fn created_documents_are_listed_for_their_owner_only() {
+        let schema = build_schema();
+        create_document(&schema, "User#0", "first").await;
+        create_document(&schema, "User#1", "other").await;
+        create_document(&schema, "User#0", "second").await;
+
+        let response = execute_as(&schema, "User#0", "{ getDocuments { id, name } }").await;
+
+        assert_eq!(
+            from_json!({"getDocuments": [
+                {"id": "Document#0", "name": "first"},
+                {"id": "Document#2", "name": "second"},
+            ]}),
+            response.data,
+        );
+    }
+
+    #[tokio::test]
+    async fn saved_documents_can_be_loaded() {
+        let schema = build_schema();
+        create_document(&schema, "User#0", "sheet").await;
+
+        execute_as(
+            &schema,
+            "User#0",
+            "mutation { saveDocument(id: \"Document#0\", content: \"1 + 2\") { id } }",
+        )
+        .await;
+        execute_as(
+            &schema,
+            "User#0",
+            "mutation { renameDocument(id: \"Document#0\", name: \"sum\") { id } }",
+        )
+        .await;
+        let response = execute_as(
+            &schema,
+            "User#0",
+            "{ getDocument(id: \"Document#0\") { name, content } }",
+        )
+        .await;
+
+        assert_eq!(
+            from_json!({"getDocument": {"name": "sum", "content": "1 + 2"}}),
+            response.data,
+        );
+    }
+
+    #[tokio::test]
+    async fn documents_of_other_users_are_not_found() {
+        let schema = build_schema();
+        create_document(&schema, "User#0", "sheet").await;
+
+        let response = execute_as(
+            &schema,
+            "User#1",
+            "mutation { saveDocument(id: \"Document#0\", content: \"x\") { id } }",
+        )
+        .await;
+        let loaded = execute_as(
+            &schema,
+            "User#0",
+            "{ getDocument(id: \"Document#0\") { content } }",
+        )
+        .await;
+
+        assert_eq!(from_json!({"saveDocument": null}), response.data);
+        assert_eq!(from_json!({"getDocument": {"content": ""}}), loaded.data);
+    }
+
+    #[tokio::test]
+    async fn deleted_documents_are_gone() {
+        let schema = build_schema();
+        create_document(&schema, "User#0", "sheet").await;
+
+        let response = execute_as(
+            &schema,
+            "User#0",
+            "mutation { deleteDocument(id: \"Document#0\") { name } }",
+        )
+        .await;
+        let created = create_document(&schema, "User#0", "new").await;
+        let listed = execute_as(&schema, "User#0", "{ getDocuments { id } }").await;
+
+        assert_eq!(
+            from_json!({"deleteDocument": {"name": "sheet"}}),
+            response.data
+        );
+        assert_eq!(
+            from_json!({"createDocument": {"id": "Document#1"}}),
+            created.data
+        );
+        assert_eq!(
+            from_json!({"getDocuments": [{"id": "Document#1"}]}),
+            listed.data
+        );
+    }
+}
+
+mod shared_documents {
+    use super::*;
+
+    /// Two registered users, the first one owns `Document#0` with `1 + 2`.
+    async fn schema_with_document() -> TestSchema {
+        let schema = build_schema();
+        register_user(&schema).await;
+        register_user(&schema).await;
+        create_document(&schema, "User#0", "sheet").await;
+        execute_as(
+            &schema,
+            "User#0",
+            "mutation { saveDocument(id: \"Document#0\", content: \"1 + 2\") { id } }",
+        )
+        .await;
+        schema
+    }
+
+    async fn join(schema: &TestSchema, user: &str) -> Response {
+        execute_as(
+            schema,
+            user,
+            "mutation { joinDocument(id: \"Document#0\") { revision, content } }",
+        )
+        .await
+    }
+
+    fn edit(revision: u64, operation: &str) -> String {
+        format!(
+            "mutation {{ editDocument(id: \"Document#0\", revision: {revision}, operation: {operation}) \
+             {{ revision, operation }} }}"
+        )
+    }
+
+    #[tokio::test]
+    async fn participants_start_with_the_stored_content() {
+        let schema = schema_with_document().await;
+
+        let owner = join(&schema, "User#0").await;
+        let other = join(&schema, "User#1").await;
+
+        let expected = from_json!({"joinDocument": {"revision": 0, "content": "1 + 2"}});
+        assert_eq!(expected, owner.data);
+        assert_eq!(expected, other.data);
+    }
+
+    #[tokio::test]
+    async fn only_the_owner_opens_a_document() {
+        let schema = schema_with_document().await;
+
+        let response = join(&schema, "User#1").await;
+
+        assert_eq!(from_json!({ "joinDocument": null }), response.data);
+    }
+
+    #[tokio::test]
+    async fn edits_need_to_join_the_document() {
+        let schema = schema_with_document().await;
+
+        let request = Request::from(edit(0, "[5, \"!\"]")).data(some_auth_user());
+        let response = schema.execute(request).await;
+
+        assert_eq!(1, response.errors.len());
+        assert_eq!(
+            "join the document Document#0 first",
+            response.errors[0].message
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_edits_are_merged_and_saved() {
+        let schema = schema_with_document().await;
+        join(&schema, "User#0").await;
+        join(&schema, "User#1").await;
+
+        execute_as(&schema, "User#0", &edit(0, "[5, \" + 3\"]")).await;
+        let response = execute_as(&schema, "User#1", &edit(0, "[\"(\", 5, \")\"]")).await;
+        let saved = execute_as(
+            &schema,
+            "User#0",
+            "{ getDocument(id: \"Document#0\") { content } }",
+        )
+        .await;
+
+        assert_eq!(
+            from_json!({"editDocument": {"revision": 2, "operation": ["(", 5, ")", 4]}}),
+            response.data
+        );
+        assert_eq!(
+            from_json!({"getDocument": {"content": "(1 + 2) + 3"}}),
+            saved.data
+        );
+    }
+
+    #[tokio::test]
+    async fn other_participants_receive_the_change_and_its_evaluation() {
+        let schema = schema_with_document().await;
+        join(&schema, "User#0").await;
+        join(&schema, "User#1").await;
+        let request = Request::from(
+            "subscription { getDocumentEvents(id: \"Document#0\") { \
+             ... on DocumentChange { revision, user { id } } \
+             ... on DocumentEvaluation { revision, messages } } }",
+        )
+        .data(auth_user("User#1".to_string()));
+        let mut stream = schema.execute_stream(request);
+        poll_once_to_make_stream_perform_work(&mut stream).await;
+
+        execute_as(&schema, "User#0", &edit(0, "[4, -1, \"3\"]")).await;
+        let change = stream.next().await.unwrap();
+        let evaluation = stream.next().await.unwrap().data.into_json().unwrap();
+
+        assert_no_error(&change);
+        assert_eq!(
+            from_json!({"getDocumentEvents": {"revision": 1, "user": {"id": "User#0"}}}),
+            change.data
+        );
+        let messages = &evaluation["getDocumentEvents"]["messages"];
+        assert_eq!("(1 + 3) = 4", messages[0]["statements"][0]["text"]);
+        assert_eq!(1, messages[0]["id"]);
+    }
+
+    #[tokio::test]
+    async fn cursors_are_moved_along_with_edits() {
+        let schema = schema_with_document().await;
+        join(&schema, "User#0").await;
+        join(&schema, "User#1").await;
+
+        execute_as(&schema, "User#0", &edit(0, "[\"10 \", 5]")).await;
+        let response = execute_as(
+            &schema,
+            "User#1",
+            "mutation { moveCursor(id: \"Document#0\", revision: 0, position: 4) \
+             { revision, cursor { position } } }",
+        )
+        .await;
+
+        assert_eq!(
+            from_json!({"moveCursor": {"revision": 1, "cursor": {"position": 7}}}),
+            response.data
+        );
+    }
+
+    #[tokio::test]
+    async fn the_document_closes_when_all_participants_left() {
+        let schema = schema_with_document().await;
+        join(&schema, "User#0").await;
+        join(&schema, "User#1").await;
+
+        let leave = "mutation { leaveDocument(id: \"Document#0\") }";
+        execute_as(&schema, "User#0", leave).await;
+        execute_as(&schema, "User#1", leave).await;
+        let response = join(&schema, "User#1").await;
+
+        assert_eq!(from_json!({ "joinDocument": null }), response.data);
+    }
+}
+
+async fn create_document(schema: &TestSchema, user: &str, name: &str) -> Response {
+    let query = format!("mutation {{ createDocument(name: \"{name}\") {{ id }} }}");
+    execute_as(schema, user, &query).await
+}
+
+async fn execute_as(schema: &TestSchema, user: &str, query: &str) -> Response {
+    let request = Request::from(query).data(auth_user(user.to_string()));
+    let response = schema.execute(request).await;
+    assert_no_error(&response);
+    response
 }
 
 async fn poll_once_to_make_stream_perform_work<
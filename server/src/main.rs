@@ -16,8 +16,10 @@ use warp_reverse_proxy::reverse_proxy_filter;
 
 use lyng2::chat::auth::{with_auth, AuthUser};
 use lyng2::chat::repository::surreal_db::SurrealDbAdapter;
-use lyng2::chat::repository::ChatRepository;
+use lyng2::chat::repository::{ChatRepository, DocumentRepository};
 use lyng2::chat::{build_schema, Schema};
+use lyng2::math::ast::parser::error_kind::ErrorKind;
+use lyng2::math::evaluation_pool::EvaluationPool;
 use lyng2::math::handle_websocket_connection;
 
 #[tokio::main]
@@ -60,9 +62,11 @@ fn address() -> SocketAddr {
 }
 
 fn api_routes() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let schema = build_schema::<SurrealDbAdapter>();
+    let pool = EvaluationPool::from_env();
+    let schema = build_schema::<SurrealDbAdapter>(pool.clone());
 
-    let routes = lyng2_route()
+    let routes = math_error_codes_route()
+        .or(lyng2_route(pool))
         .or(chat_subscription_route(schema.clone()))
         .or(chat_route(schema))
         .or(playground_route())
@@ -71,7 +75,7 @@ fn api_routes() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clo
     warp::path("api").and(routes)
 }
 
-fn chat_subscription_route<R: ChatRepository + 'static>(
+fn chat_subscription_route<R: ChatRepository + DocumentRepository + 'static>(
     schema: Schema<R>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     warp::ws()
@@ -103,13 +107,24 @@ fn data_with(auth_token: Option<AuthUser>) -> Data {
     data
 }
 
-fn lyng2_route() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+fn math_error_codes_route() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("math" / "errors")
+        .and(warp::get())
+        .map(|| warp::reply::json(&ErrorKind::describe_all()))
+}
+
+fn lyng2_route(
+    pool: EvaluationPool,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     warp::path("math")
         .and(warp::ws())
-        .map(|handshake: Ws| handshake.on_upgrade(handle_websocket_connection))
+        .map(move |handshake: Ws| {
+            let pool = pool.clone();
+            handshake.on_upgrade(move |websocket| handle_websocket_connection(websocket, pool))
+        })
 }
 
-fn chat_route<R: ChatRepository + 'static>(
+fn chat_route<R: ChatRepository + DocumentRepository + 'static>(
     schema: Schema<R>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     async_graphql_warp::graphql(schema)
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bigdecimal::BigDecimal;
+
+use crate::math::ast::parser::error::ErrorMessage;
+use crate::math::ast::parser::error_kind::ErrorKind;
+use crate::math::ast::parser::operators::{OperatorDefinition, OperatorTable};
+use crate::math::ast::parser::StatementSource;
+use crate::math::ast::Span;
+
+/// A statement that was parsed and evaluated.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Evaluated {
+    /// the statement as shown to the user
+    pub statement: String,
+    /// `None` for statements with free variables and for operator definitions
+    pub value: Option<BigDecimal>,
+    pub definition: Option<Arc<OperatorDefinition>>,
+}
+
+pub type Outcome = Result<Evaluated, ErrorMessage>;
+
+/// The outcome of a statement and whether it was computed again or taken from the cache.
+#[derive(Debug)]
+pub struct CachedOutcome {
+    pub outcome: Outcome,
+    pub recomputed: bool,
+}
+
+/// A statement yields the same outcome as long as its text, the definitions of the operators
+/// it uses and the values it refers to stay the same, no matter where it is in the document.
+#[derive(Debug, Eq, PartialEq, Hash)]
+struct StatementKey {
+    text: String,
+    /// the keys of the statements that define operators used by this statement
+    dependencies: Vec<Arc<StatementKey>>,
+    /// the exact values of the history references, `None` if a reference can't be resolved
+    references: Vec<Option<String>>,
+}
+
+impl StatementKey {
+    fn new(
+        source: &StatementSource,
+        definitions: &HashMap<String, Arc<StatementKey>>,
+        values: &[Option<BigDecimal>],
+    ) -> Self {
+        let mut dependencies: Vec<Arc<StatementKey>> = vec![];
+        for definition in source
+            .symbols()
+            .filter_map(|symbol| definitions.get(symbol))
+        {
+            if !dependencies.contains(definition) {
+                dependencies.push(definition.clone());
+            }
+        }
+        let references = source
+            .history_references()
+            .into_iter()
+            .map(|reference| reference.resolve(values).ok().map(ToString::to_string))
+            .collect();
+        StatementKey {
+            text: source.text.to_string(),
+            dependencies,
+            references,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// where the text of the statement was when it was evaluated
+    text_span: Span,
+    outcome: Outcome,
+}
+
+/// Remembers the outcomes of the statements of the latest version of a document,
+/// so that only the changed statements and the ones depending on them are evaluated again.
+#[derive(Debug, Default)]
+pub struct StatementCache {
+    entries: HashMap<Arc<StatementKey>, Entry>,
+}
+
+impl StatementCache {
+    pub fn new() -> Self {
+        StatementCache::default()
+    }
+
+    /// Returns the outcome of every statement of a document in order, `evaluate` is called for
+    /// the statements that aren't cached with the values of the statements before them.
+    /// It has to add operator definitions to the table.
+    ///
+    /// Afterwards the cache contains only the statements of this document.
+    pub fn evaluate_all(
+        &mut self,
+        sources: &[StatementSource],
+        mut evaluate: impl FnMut(&StatementSource, &mut OperatorTable, &[Option<BigDecimal>]) -> Outcome,
+    ) -> Vec<CachedOutcome> {
+        let mut operators = OperatorTable::new();
+        let mut definitions = HashMap::new();
+        let mut values = vec![];
+        let mut entries = HashMap::new();
+
+        let mut outcomes = vec![];
+        for source in sources {
+            let key = Arc::new(StatementKey::new(source, &definitions, &values));
+            let cached = entries.get(&key).or_else(|| self.entries.get(&key));
+            let (outcome, recomputed) = match cached {
+                Some(Entry { text_span, outcome }) => {
+                    let outcome = outcome
+                        .clone()
+                        .map_err(|error| error.moved(*text_span, source.text_span));
+                    if let Ok(Evaluated {
+                        definition: Some(definition),
+                        ..
+                    }) = &outcome
+                    {
+                        operators.define(definition.clone());
+                    }
+                    (outcome, false)
+                }
+                None => (evaluate(source, &mut operators, &values), true),
+            };
+            values.push(
+                outcome
+                    .as_ref()
+                    .ok()
+                    .and_then(|evaluated| evaluated.value.clone()),
+            );
+
+            if let Ok(Evaluated {
+                definition: Some(definition),
+                ..
+            }) = &outcome
+            {
+                definitions.insert(definition.symbol.clone(), key.clone());
+            }
+            if is_cacheable(&outcome) {
+                let entry = Entry {
+                    text_span: source.text_span,
+                    outcome: outcome.clone(),
+                };
+                entries.insert(key, entry);
+            }
+            outcomes.push(CachedOutcome {
+                outcome,
+                recomputed,
+            });
+        }
+
+        self.entries = entries;
+        outcomes
+    }
+}
+
+/// Timeouts and cancellations depend on the circumstances of an evaluation, not on the statement.
+fn is_cacheable(outcome: &Outcome) -> bool {
+    !matches!(
+        outcome,
+        Err(ErrorMessage {
+            kind: ErrorKind::Timeout | ErrorKind::Cancelled,
+            ..
+        })
+    )
+}
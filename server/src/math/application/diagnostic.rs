@@ -0,0 +1,82 @@
+use serde::Serialize;
+
+use crate::math::ast::parser::error::{ErrorMessage, Suggestion};
+use crate::math::ast::parser::error_kind::ErrorKind;
+use crate::math::ast::Span;
+
+#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+    Error,
+}
+
+/// An error in the user's input, ready to be shown next to the affected source text.
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// the stable code of `kind`, e.g. `E0008`
+    pub code: &'static str,
+    pub kind: ErrorKind,
+    pub span: Span,
+    pub message: String,
+    pub help: Option<String>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl From<ErrorMessage> for Diagnostic {
+    fn from(error: ErrorMessage) -> Self {
+        let details = error.details.map(|details| *details).unwrap_or_default();
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.kind.code(),
+            kind: error.kind,
+            span: error.span,
+            message: error.message,
+            help: details.help,
+            notes: details.notes,
+            suggestions: details.suggestions,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Moves the diagnostic along with the text it was found in, see [Span::moved].
+    pub fn moved(mut self, from: Span, to: Span) -> Self {
+        self.span = self.span.moved(from, to);
+        for suggestion in &mut self.suggestions {
+            suggestion.span = suggestion.span.moved(from, to);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::application::diagnostic::Diagnostic;
+    use crate::math::ast::parser::parse_statements;
+    use crate::math::ast::{Localization, Span};
+
+    fn first_error_of(input: &str) -> Diagnostic {
+        parse_statements(input.to_string())
+            .into_iter()
+            .find_map(|statement| statement.result.err())
+            .map(Diagnostic::from)
+            .unwrap()
+    }
+
+    #[test]
+    fn moved_diagnostics_point_into_the_input() {
+        let under_test = first_error_of("1;\n2 * (3");
+        let input = Span {
+            start: Localization::at(1, 0),
+            end: Localization::at(1, 7),
+            start_offset: 3,
+            end_offset: 10,
+        };
+
+        let moved = under_test.moved(input, Span::default());
+
+        assert_eq!(Localization::at(0, 6), moved.span.start);
+        assert_eq!(6, moved.suggestions[0].span.start_offset);
+    }
+}
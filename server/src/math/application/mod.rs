@@ -1,55 +1,549 @@
-use serde::Serialize;
+pub mod cache;
+pub mod diagnostic;
 
-use crate::math::ast::parser::parse;
+use std::sync::Arc;
 
-#[derive(Serialize)]
-pub enum CommandResult {
-    Success(String),
-    Error(String),
+use bigdecimal::BigDecimal;
+
+use crate::math::application::cache::{CachedOutcome, Evaluated, Outcome, StatementCache};
+use crate::math::application::diagnostic::Diagnostic;
+use crate::math::ast::budget::Budget;
+use crate::math::ast::evaluation::evaluate_within;
+use crate::math::ast::parser::operators::{OperatorDefinition, OperatorTable};
+use crate::math::ast::parser::{parse_comments, split_statements, StatementSource};
+use crate::math::ast::Span;
+
+/// The outcome of one statement or comment, `span` locates it in the source text,
+/// so that clients can show the output next to the line it comes from.
+#[derive(Debug, Eq, PartialEq)]
+pub struct StatementResult {
+    pub span: Span,
+    pub output: Output,
+    /// false if the output of the statement was taken from the previous run
+    pub recomputed: bool,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Output {
+    Value(String),
+    Comment(String),
+    Error(Diagnostic),
 }
 
-pub struct Application;
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Settings {
+    /// the number of significant digits of results, `None` shows exact results
+    pub precision: Option<u64>,
+}
+
+/// A statement of the latest document and its value.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HistoryEntry {
+    /// the source text of the statement
+    pub statement: String,
+    /// `None` if the statement failed or has no value
+    pub value: Option<BigDecimal>,
+}
+
+/// The state of a user's math session: the settings, and the statements and operator definitions
+/// of the latest document. The results of its statements are kept for the next run.
+#[derive(Default)]
+pub struct Application {
+    pub settings: Settings,
+    cache: StatementCache,
+    history: Vec<HistoryEntry>,
+    definitions: Vec<Arc<OperatorDefinition>>,
+}
 
 impl Application {
     pub fn create() -> Application {
-        Application
+        Application::default()
+    }
+
+    /// Forgets everything, as if the application was just created.
+    pub fn reset(&mut self) {
+        *self = Application::create();
+    }
+
+    /// The statements of the latest document in order.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    /// The operators defined by the latest document, a redefinition replaces the earlier one.
+    pub fn definitions(&self) -> &[Arc<OperatorDefinition>] {
+        &self.definitions
+    }
+
+    /// Returns the results ordered by their position in `input`.
+    pub fn run(&mut self, input: String) -> Vec<StatementResult> {
+        self.run_within(input, &Budget::unlimited())
+    }
+
+    /// Like `run`, statements that exceed `budget` result in an error.
+    ///
+    /// Statements that didn't change since the previous run, and whose operators didn't change
+    /// either, aren't parsed and evaluated again.
+    pub fn run_within(&mut self, input: String, budget: &Budget) -> Vec<StatementResult> {
+        self.history.clear();
+        self.definitions.clear();
+
+        let comments = parse_comments(&input)
+            .into_iter()
+            .map(|comment| StatementResult {
+                span: comment.span,
+                output: Output::Comment(comment.text),
+                recomputed: true,
+            });
+        let statements = match split_statements(&input) {
+            Ok(sources) => self.run_statements(&sources, budget),
+            Err(error) => vec![StatementResult {
+                span: error.span,
+                output: Output::Error(Diagnostic::from(error)),
+                recomputed: true,
+            }],
+        };
+
+        let mut results: Vec<_> = statements.into_iter().chain(comments).collect();
+        results.sort_by_key(|result| result.span.start_offset);
+        results
+    }
+
+    fn run_statements(
+        &mut self,
+        sources: &[StatementSource],
+        budget: &Budget,
+    ) -> Vec<StatementResult> {
+        let outcomes = self
+            .cache
+            .evaluate_all(sources, |source, operators, values| {
+                run_statement(source, operators, values, budget)
+            });
+
+        let mut results = vec![];
+        for (
+            source,
+            CachedOutcome {
+                outcome,
+                recomputed,
+            },
+        ) in sources.iter().zip(outcomes)
+        {
+            let evaluated = outcome.as_ref().ok();
+            self.history.push(HistoryEntry {
+                statement: source.statement().to_string(),
+                value: evaluated.and_then(|evaluated| evaluated.value.clone()),
+            });
+            if let Some(definition) = evaluated.and_then(|evaluated| evaluated.definition.as_ref())
+            {
+                self.definitions
+                    .retain(|defined| defined.symbol != definition.symbol);
+                self.definitions.push(definition.clone());
+            }
+
+            results.push(StatementResult {
+                span: source.span,
+                output: self.output(outcome),
+                recomputed,
+            });
+        }
+        results
+    }
+
+    fn output(&self, outcome: Outcome) -> Output {
+        match outcome {
+            Ok(Evaluated {
+                statement,
+                value: Some(value),
+                ..
+            }) => Output::Value(format!("{statement} = {}", self.rounded(value))),
+            Ok(Evaluated { statement, .. }) => Output::Value(statement),
+            Err(error) => Output::Error(Diagnostic::from(error)),
+        }
     }
 
-    pub fn run(&mut self, input: String) -> CommandResult {
-        match parse(input) {
-            Ok(result) => CommandResult::Success(
-                result
-                    .iter()
-                    .map(|result| format!("{result}"))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-            ),
-            Err(error) => CommandResult::Error(error.message),
+    fn rounded(&self, value: BigDecimal) -> BigDecimal {
+        match self.settings.precision {
+            Some(precision) if value.digits() > precision => value.with_prec(precision),
+            _ => value,
         }
     }
 }
 
+fn run_statement(
+    source: &StatementSource,
+    operators: &mut OperatorTable,
+    values: &[Option<BigDecimal>],
+    budget: &Budget,
+) -> Outcome {
+    let statement = source.parse(operators).result?;
+    let resolved = statement.resolve_history(values)?;
+    Ok(Evaluated {
+        value: evaluate_within(&resolved, budget)?,
+        definition: statement.operator_definition().cloned(),
+        statement: statement.to_string(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::math::application::CommandResult;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::math::application::diagnostic::{Diagnostic, Severity};
+    use crate::math::application::{Application, Output, Settings, StatementResult};
+    use crate::math::ast::budget::{Budget, CancellationToken, Limits};
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::{Localization, Span};
+
+    fn span(start: usize, end: usize) -> Span {
+        Span {
+            start: Localization::at(0, start),
+            end: Localization::at(0, end),
+            start_offset: start,
+            end_offset: end,
+        }
+    }
+
+    fn outputs(results: Vec<StatementResult>) -> Vec<Output> {
+        results.into_iter().map(|result| result.output).collect()
+    }
+
+    fn value(text: &str) -> Output {
+        Output::Value(text.to_string())
+    }
+
+    fn recomputed(results: &[StatementResult]) -> Vec<bool> {
+        results.iter().map(|result| result.recomputed).collect()
+    }
 
     #[test]
-    fn deserialize_success() {
-        let result = CommandResult::Success("success message".to_string());
+    fn run_evaluates_statements() {
+        let result = Application::create().run("1 + 2; x * 3!; |-7| % 4".to_string());
 
-        let actual = ron::to_string(&result).unwrap();
+        let expected = vec![
+            StatementResult {
+                span: span(0, 5),
+                output: value("(1 + 2) = 3"),
+                recomputed: true,
+            },
+            StatementResult {
+                span: span(7, 13),
+                output: value("(x * 3!)"),
+                recomputed: true,
+            },
+            StatementResult {
+                span: span(15, 23),
+                output: value("(|-7| mod 4) = 3"),
+                recomputed: true,
+            },
+        ];
+        assert_eq!(expected, result);
+    }
 
-        let expected = "Success(\"success message\")".to_string();
-        assert_eq!(expected, actual);
+    #[test]
+    fn run_reports_evaluation_errors() {
+        let result = Application::create().run("1 + 2; (-3)!; 2 * 3".to_string());
+
+        let expected = vec![
+            value("(1 + 2) = 3"),
+            Output::Error(Diagnostic {
+                severity: Severity::Error,
+                code: "E0012",
+                kind: ErrorKind::InvalidFactorialArgument,
+                span: span(7, 12),
+                message: "factorial is only defined for non-negative integers, got -3".to_string(),
+                help: None,
+                notes: vec!["the gamma function is not supported yet".to_string()],
+                suggestions: vec![],
+            }),
+            value("(2 * 3) = 6"),
+        ];
+        assert_eq!(expected, outputs(result));
+    }
+
+    #[test]
+    fn run_reports_all_syntax_errors() {
+        let result = Application::create().run("1 +; 2 * 3; 4 5;".to_string());
+
+        assert_eq!(3, result.len());
+        assert_eq!(span(0, 3), result[0].span);
+        match &result[0].output {
+            Output::Error(diagnostic) => {
+                assert_eq!(ErrorKind::ExpectedOperand, diagnostic.kind);
+                assert_eq!(
+                    "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''",
+                    diagnostic.message
+                );
+            }
+            output => panic!("expected a diagnostic, got {output:?}"),
+        }
+        assert_eq!(value("(2 * 3) = 6"), result[1].output);
+        assert_eq!(
+            StatementResult {
+                span: span(12, 15),
+                output: Output::Error(Diagnostic {
+                    severity: Severity::Error,
+                    code: "E0011",
+                    kind: ErrorKind::UnexpectedInput,
+                    span: span(14, 15),
+                    message: "expected end of input, '5' was left".to_string(),
+                    help: Some("separate statements with ';'".to_string()),
+                    notes: vec![],
+                    suggestions: vec![],
+                }),
+                recomputed: true,
+            },
+            result[2]
+        );
+    }
+
+    #[test]
+    fn run_keeps_comments_next_to_results() {
+        let input = "# a sum\n1 + 2; // three\nx /* free variable */ * 2 # symbolic\n/* the end */";
+
+        let result = Application::create().run(input.to_string());
+
+        let comment = |text: &str| Output::Comment(text.to_string());
+        let expected = vec![
+            comment("# a sum"),
+            value("(1 + 2) = 3"),
+            comment("// three"),
+            value("(x * 2)"),
+            comment("/* free variable */"),
+            comment("# symbolic"),
+            comment("/* the end */"),
+        ];
+        let lines: Vec<_> = result.iter().map(|result| result.span.start).collect();
+        assert_eq!(expected, outputs(result));
+        assert_eq!(
+            vec![
+                Localization::at(0, 0),
+                Localization::at(1, 0),
+                Localization::at(1, 7),
+                Localization::at(2, 0),
+                Localization::at(2, 2),
+                Localization::at(2, 26),
+                Localization::at(3, 0),
+            ],
+            lines
+        );
     }
 
     #[test]
-    fn deserialize_failure() {
-        let result = CommandResult::Error("error message".to_string());
+    fn run_evaluates_user_defined_operators() {
+        let input =
+            "infixl 6 a <+> b = (a + b) / 2; 1 <+> 3 * 2; infixr 8 a </> b = a / (b - 1); 4 </> 1";
 
-        let actual = ron::to_string(&result).unwrap();
+        let result = outputs(Application::create().run(input.to_string()));
+
+        assert_eq!(value("infixl 6 a <+> b = ((a + b) / 2)"), result[0]);
+        assert_eq!(value("(1 <+> (3 * 2)) = 3.5"), result[1]);
+        match &result[3] {
+            Output::Error(diagnostic) => {
+                assert_eq!(ErrorKind::DivisionByZero, diagnostic.kind);
+                assert_eq!(span(77, 84), diagnostic.span);
+                assert_eq!(
+                    vec!["in the definition of '</>'".to_string()],
+                    diagnostic.notes
+                );
+            }
+            output => panic!("expected a division by zero, got {output:?}"),
+        }
+    }
+
+    #[test]
+    fn run_reuses_unchanged_statements() {
+        let mut application = Application::create();
+        application.run("1 + 2; 3 * 4; 5!".to_string());
+
+        let result = application.run("1 + 2; 3 * 5; 5!".to_string());
+
+        assert_eq!(vec![false, true, false], recomputed(&result));
+        assert_eq!(
+            vec![
+                value("(1 + 2) = 3"),
+                value("(3 * 5) = 15"),
+                value("5! = 120")
+            ],
+            outputs(result)
+        );
+    }
+
+    #[test]
+    fn run_moves_cached_errors_along_with_their_statement() {
+        let mut application = Application::create();
+        application.run("1 / 0".to_string());
+
+        let result = application.run("x;1 / 0".to_string());
+
+        assert_eq!(vec![true, false], recomputed(&result));
+        match &result[1].output {
+            Output::Error(diagnostic) => assert_eq!(span(2, 7), diagnostic.span),
+            output => panic!("expected a division by zero, got {output:?}"),
+        }
+    }
+
+    #[test]
+    fn run_recomputes_statements_using_a_changed_operator() {
+        let mut application = Application::create();
+        application.run("infixl 6 a <+> b = a + b; 1 <+> 2; 3 * 4".to_string());
+
+        let result = application.run("infixl 6 a <+> b = a - b; 1 <+> 2; 3 * 4".to_string());
+
+        assert_eq!(vec![true, true, false], recomputed(&result));
+        assert_eq!(value("(1 <+> 2) = -1"), result[1].output);
+    }
+
+    #[test]
+    fn run_applies_the_precision_to_cached_results() {
+        let mut application = Application::create();
+        application.run("2 / 3".to_string());
+        application.settings = Settings { precision: Some(2) };
+
+        let result = application.run("2 / 3".to_string());
+
+        assert_eq!(vec![false], recomputed(&result));
+        assert_eq!(vec![value("(2 / 3) = 0.67")], outputs(result));
+    }
+
+    #[test]
+    fn run_keeps_the_statements_and_definitions_of_the_latest_document() {
+        let mut application = Application::create();
+        application.run("1 + 1".to_string());
+
+        application.run("infixl 6 a <+> b = a; 2 * 3; infixl 7 a <+> b = b; x".to_string());
+
+        let history: Vec<_> = application
+            .history()
+            .iter()
+            .map(|entry| (entry.statement.as_str(), entry.value.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("infixl 6 a <+> b = a", None),
+                ("2 * 3", Some(BigDecimal::from(6))),
+                ("infixl 7 a <+> b = b", None),
+                ("x", None),
+            ],
+            history
+        );
+        let definitions: Vec<_> = application
+            .definitions()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(vec!["infixl 7 a <+> b = b"], definitions);
+    }
+
+    #[test]
+    fn reset_forgets_everything() {
+        let mut application = Application::create();
+        application.settings = Settings { precision: Some(5) };
+        application.run("1 + 1".to_string());
+
+        application.reset();
+
+        assert_eq!(Settings::default(), application.settings);
+        assert!(application.history().is_empty());
+        let result = application.run("1 + 1".to_string());
+        assert_eq!(vec![true], recomputed(&result));
+    }
+
+    #[test]
+    fn run_resolves_history_references() {
+        let result = Application::create().run("1 + 2; ans * 2; x; %1 + out[2]; ans".to_string());
+
+        assert_eq!(
+            vec![
+                value("(1 + 2) = 3"),
+                value("(ans * 2) = 6"),
+                value("x"),
+                value("(%1 + out[2]) = 9"),
+                value("ans = 9"),
+            ],
+            outputs(result)
+        );
+    }
+
+    #[test]
+    fn run_reports_references_without_value() {
+        let result = Application::create().run("ans; x; %2; %4 + 1".to_string());
+
+        let messages: Vec<_> = outputs(result)
+            .into_iter()
+            .filter_map(|output| match output {
+                Output::Error(diagnostic) => {
+                    assert_eq!(ErrorKind::InvalidHistoryReference, diagnostic.kind);
+                    Some(diagnostic.message)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "no statement before 'ans' has a value",
+                "statement 2 has no value",
+                "'%4' doesn't refer to an earlier statement",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn run_recomputes_references_to_changed_values() {
+        let mut application = Application::create();
+        application.run("1 + 2; ans * 2; 7".to_string());
+
+        let changed = application.run("1 + 3; ans * 2; 7".to_string());
+        let same_value = application.run("3 + 1; ans * 2; 7".to_string());
+
+        assert_eq!(vec![true, true, false], recomputed(&changed));
+        assert_eq!(value("(ans * 2) = 8"), changed[1].output);
+        assert_eq!(vec![true, false, false], recomputed(&same_value));
+    }
+
+    #[test]
+    fn run_rounds_results_to_the_configured_precision() {
+        let mut application = Application::create();
+        application.settings = Settings { precision: Some(5) };
+
+        let result = application.run("1 / 3; 2 ^ 10; 7".to_string());
+
+        let expected = vec![
+            value("(1 / 3) = 0.33333"),
+            value("(2 ^ 10) = 1024"),
+            value("7 = 7"),
+        ];
+        assert_eq!(expected, outputs(result));
+    }
+
+    #[test]
+    fn statements_exceeding_the_budget_report_an_error() {
+        let limits = Limits {
+            timeout: Duration::from_secs(60),
+            max_digits: 100,
+        };
+        let budget = Budget::new(limits, CancellationToken::new());
+
+        let result = Application::create().run_within("2 ^ 1000; 1 + 1".to_string(), &budget);
+
+        match &result[0].output {
+            Output::Error(diagnostic) => {
+                assert_eq!(ErrorKind::ResultTooLarge, diagnostic.kind);
+                assert_eq!("E0016", diagnostic.code);
+            }
+            output => panic!("expected a diagnostic, got {output:?}"),
+        }
+        assert_eq!(value("(1 + 1) = 2"), result[1].output);
+    }
+
+    #[test]
+    fn statements_spanning_multiple_lines_start_at_their_first_line() {
+        let result = Application::create().run("\n1 +\n2;\n\n3".to_string());
 
-        let expected = "Error(\"error message\")".to_string();
-        assert_eq!(expected, actual);
+        let lines: Vec<_> = result.iter().map(|result| result.span.start).collect();
+        assert_eq!(vec![Localization::at(1, 0), Localization::at(4, 0)], lines);
     }
 }
@@ -0,0 +1,262 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bigdecimal::BigDecimal;
+
+use crate::math::ast::parser::error::ErrorMessage;
+use crate::math::ast::{Localization, Span};
+
+/// The resources one evaluation of a document may use.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Limits {
+    /// the wall-clock time for all statements of a document
+    pub timeout: Duration,
+    /// the number of digits of every intermediate result, which bounds the memory of a number
+    pub max_digits: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            timeout: Duration::from_secs(2),
+            max_digits: 100_000,
+        }
+    }
+}
+
+/// Set by another thread to stop an evaluation that is no longer needed.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The limits of one running evaluation.
+///
+/// A blocking computation can't be interrupted from the outside, so the evaluation checks its
+/// budget between its steps and gives up with an error once the budget is exhausted.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    deadline: Option<Instant>,
+    max_digits: Option<u64>,
+    cancellation: CancellationToken,
+}
+
+impl Budget {
+    /// Starts the clock for `limits.timeout` now.
+    pub fn new(limits: Limits, cancellation: CancellationToken) -> Self {
+        Budget {
+            deadline: Some(Instant::now() + limits.timeout),
+            max_digits: Some(limits.max_digits),
+            cancellation,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Budget {
+            deadline: None,
+            max_digits: None,
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    /// Fails if the evaluation was cancelled or ran out of time.
+    pub fn check_time(&self, localization: Localization, span: Span) -> Result<(), ErrorMessage> {
+        if self.cancellation.is_cancelled() {
+            return Err(ErrorMessage::cancelled(localization, span));
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() > deadline => Err(ErrorMessage::timeout(
+                "evaluation took too long".to_string(),
+                localization,
+                span,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a number with `digits` digits exceeds the budget, checked before it is computed.
+    pub fn check_digits(
+        &self,
+        digits: u64,
+        localization: Localization,
+        span: Span,
+    ) -> Result<(), ErrorMessage> {
+        match self.max_digits {
+            Some(max_digits) if digits > max_digits => Err(ErrorMessage::result_too_large(
+                format!("the result would have more than {max_digits} digits"),
+                localization,
+                span,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts the digits of `value` written without exponent, `1e5` has 6 and `1e-5` has 5.
+    pub fn check_size(
+        &self,
+        value: &BigDecimal,
+        localization: Localization,
+        span: Span,
+    ) -> Result<(), ErrorMessage> {
+        let scale = scale_of(value);
+        let digits = if scale < 0 {
+            value.digits().saturating_add(scale.unsigned_abs())
+        } else {
+            value.digits().max(scale.unsigned_abs())
+        };
+        self.check_digits(digits, localization, span)
+    }
+
+    /// Adding, subtracting and the modulo first give both operands the larger scale of the two,
+    /// which can write out a number like `1e99999` with all its digits. Checked before that.
+    pub fn check_aligned_size(
+        &self,
+        left: &BigDecimal,
+        right: &BigDecimal,
+        localization: Localization,
+        span: Span,
+    ) -> Result<(), ErrorMessage> {
+        let (left_scale, right_scale) = (scale_of(left), scale_of(right));
+        let scale = left_scale.max(right_scale);
+        let digits = (left.digits().saturating_add(scale.abs_diff(left_scale)))
+            .max(right.digits().saturating_add(scale.abs_diff(right_scale)));
+        self.check_digits(digits, localization, span)
+    }
+}
+
+/// The number of decimals, negative if the value is a multiple of a power of ten.
+fn scale_of(value: &BigDecimal) -> i64 {
+    value.as_bigint_and_exponent().1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::math::ast::budget::{Budget, CancellationToken, Limits};
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::{Localization, Span};
+
+    fn limits(timeout: Duration, max_digits: u64) -> Limits {
+        Limits {
+            timeout,
+            max_digits,
+        }
+    }
+
+    #[test]
+    fn unlimited_budget_never_fails() {
+        let budget = Budget::unlimited();
+
+        assert!(budget
+            .check_time(Localization::new(), Span::default())
+            .is_ok());
+        assert!(budget
+            .check_digits(u64::MAX, Localization::new(), Span::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn exceeding_the_deadline_is_a_timeout() {
+        let budget = Budget::new(limits(Duration::ZERO, 10), CancellationToken::new());
+        std::thread::sleep(Duration::from_millis(1));
+
+        let error = budget
+            .check_time(Localization::new(), Span::default())
+            .unwrap_err();
+
+        assert_eq!(ErrorKind::Timeout, error.kind);
+    }
+
+    #[test]
+    fn cancellation_is_visible_to_clones() {
+        let cancellation = CancellationToken::new();
+        let budget = Budget::new(limits(Duration::from_secs(60), 10), cancellation.clone());
+
+        cancellation.cancel();
+
+        let error = budget
+            .check_time(Localization::new(), Span::default())
+            .unwrap_err();
+        assert_eq!(ErrorKind::Cancelled, error.kind);
+    }
+
+    #[test]
+    fn digits_are_limited() {
+        let budget = Budget::new(
+            limits(Duration::from_secs(60), 10),
+            CancellationToken::new(),
+        );
+
+        assert!(budget
+            .check_digits(10, Localization::new(), Span::default())
+            .is_ok());
+        let error = budget
+            .check_digits(11, Localization::new(), Span::default())
+            .unwrap_err();
+        assert_eq!(ErrorKind::ResultTooLarge, error.kind);
+        assert_eq!(
+            "the result would have more than 10 digits".to_string(),
+            error.message
+        );
+    }
+
+    #[test]
+    fn sizes_include_the_exponent() {
+        let budget = Budget::new(
+            limits(Duration::from_secs(60), 10),
+            CancellationToken::new(),
+        );
+        let check = |value: &str| {
+            budget
+                .check_size(
+                    &BigDecimal::from_str(value).unwrap(),
+                    Localization::new(),
+                    Span::default(),
+                )
+                .is_ok()
+        };
+
+        assert_eq!(
+            vec![true, true, false, false],
+            ["1e9", "1e-10", "1e10", "1e-11"].map(check)
+        );
+    }
+
+    #[test]
+    fn aligned_sizes_are_checked_before_adding() {
+        let budget = Budget::new(
+            limits(Duration::from_secs(60), 10),
+            CancellationToken::new(),
+        );
+        let check = |left: &str, right: &str| {
+            budget
+                .check_aligned_size(
+                    &BigDecimal::from_str(left).unwrap(),
+                    &BigDecimal::from_str(right).unwrap(),
+                    Localization::new(),
+                    Span::default(),
+                )
+                .is_ok()
+        };
+
+        assert!(check("1e5", "0.0001"));
+        assert!(!check("1e6", "0.00001"));
+    }
+}
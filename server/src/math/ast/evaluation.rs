@@ -0,0 +1,429 @@
+use bigdecimal::{BigDecimal, FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use crate::math::ast::budget::Budget;
+use crate::math::ast::parser::error::ErrorMessage;
+use crate::math::ast::{Localization, LocalizedSyntaxNode, Span, SyntaxTree};
+
+/// `Ok(None)` means that the expression contains free variables and can't be reduced to a number.
+pub type EvaluationResult = Result<Option<BigDecimal>, ErrorMessage>;
+
+pub fn evaluate(node: &LocalizedSyntaxNode) -> EvaluationResult {
+    evaluate_within(node, &Budget::unlimited())
+}
+
+/// Evaluates `node`, failing as soon as `budget` is exhausted.
+pub fn evaluate_within(node: &LocalizedSyntaxNode, budget: &Budget) -> EvaluationResult {
+    let location = node.location;
+    let span = node.span;
+    budget.check_time(location, span)?;
+
+    let result = match &node.tree {
+        // references are resolved before the evaluation, otherwise they are like free variables
+        SyntaxTree::Variable(_) | SyntaxTree::HistoryReference(_) => Ok(None),
+        SyntaxTree::Number(value) => Ok(Some(value.clone())),
+        SyntaxTree::Sum(left, right) => binary(left, right, budget, |left, right| {
+            budget.check_aligned_size(&left, &right, location, span)?;
+            Ok(left + right)
+        }),
+        SyntaxTree::Subtraction(left, right) => binary(left, right, budget, |left, right| {
+            budget.check_aligned_size(&left, &right, location, span)?;
+            Ok(left - right)
+        }),
+        SyntaxTree::Product(left, right) => {
+            binary(left, right, budget, |left, right| Ok(left * right))
+        }
+        SyntaxTree::Division(left, right) => binary(left, right, budget, |left, right| {
+            if right.is_zero() {
+                return Err(ErrorMessage::division_by_zero(
+                    format!("division by zero in {node}"),
+                    location,
+                    span,
+                ));
+            }
+            Ok(left / right)
+        }),
+        SyntaxTree::Modulo(left, right) => binary(left, right, budget, |left, right| {
+            if right.is_zero() {
+                return Err(ErrorMessage::division_by_zero(
+                    format!("modulo by zero in {node}"),
+                    location,
+                    span,
+                ));
+            }
+            budget.check_aligned_size(&left, &right, location, span)?;
+            Ok(floored_modulo(left, right))
+        }),
+        SyntaxTree::Exponent(base, exponent) => binary(base, exponent, budget, |base, exponent| {
+            power(base, exponent, budget, location, span)
+        }),
+        SyntaxTree::Negation(value) => unary(value, budget, |value| Ok(-value)),
+        SyntaxTree::Factorial(value) => unary(value, budget, |value| {
+            factorial(&value, 1, budget, location, span)?.ok_or_else(|| {
+                ErrorMessage::invalid_factorial_argument(
+                    format!("factorial is only defined for non-negative integers, got {value}"),
+                    location,
+                    span,
+                )
+            })
+        }),
+        SyntaxTree::DoubleFactorial(value) => unary(value, budget, |value| {
+            factorial(&value, 2, budget, location, span)?.ok_or_else(|| {
+                ErrorMessage::invalid_factorial_argument(
+                    format!(
+                        "double factorial is only defined for non-negative integers, got {value}"
+                    ),
+                    location,
+                    span,
+                )
+            })
+        }),
+        SyntaxTree::AbsoluteValue(value) => unary(value, budget, |value| Ok(value.abs())),
+        SyntaxTree::DefinedOperation(definition, left, right) => {
+            match (
+                evaluate_within(left, budget)?,
+                evaluate_within(right, budget)?,
+            ) {
+                (Some(left), Some(right)) => {
+                    let [left_name, right_name] = &definition.parameters;
+                    let body = definition
+                        .body
+                        .substitute(&[(left_name, &left), (right_name, &right)]);
+                    // errors in the body are reported at the operator that was applied
+                    evaluate_within(&body, budget).map_err(|mut error| {
+                        error.localization = location;
+                        error.span = span;
+                        error.with_note(&format!("in the definition of '{}'", definition.symbol))
+                    })
+                }
+                _ => Ok(None),
+            }
+        }
+        SyntaxTree::OperatorDefinition(_) => Ok(None),
+    }?;
+
+    if let Some(value) = &result {
+        budget.check_size(value, location, span)?;
+    }
+    Ok(result)
+}
+
+fn unary(
+    value: &LocalizedSyntaxNode,
+    budget: &Budget,
+    operation: impl FnOnce(BigDecimal) -> Result<BigDecimal, ErrorMessage>,
+) -> EvaluationResult {
+    match evaluate_within(value, budget)? {
+        Some(value) => operation(value).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn binary(
+    left: &LocalizedSyntaxNode,
+    right: &LocalizedSyntaxNode,
+    budget: &Budget,
+    operation: impl FnOnce(BigDecimal, BigDecimal) -> Result<BigDecimal, ErrorMessage>,
+) -> EvaluationResult {
+    match (
+        evaluate_within(left, budget)?,
+        evaluate_within(right, budget)?,
+    ) {
+        (Some(left), Some(right)) => operation(left, right).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The result has the sign of the divisor, e.g. `-7 mod 3 = 2`.
+fn floored_modulo(dividend: BigDecimal, divisor: BigDecimal) -> BigDecimal {
+    let remainder = &dividend % &divisor;
+    if !remainder.is_zero() && remainder.is_negative() != divisor.is_negative() {
+        remainder + divisor
+    } else {
+        remainder
+    }
+}
+
+/// Multiplies `n * (n - step) * (n - 2 * step) * ...` down to 1,
+/// `None` if the factorial of `value` is not defined.
+fn factorial(
+    value: &BigDecimal,
+    step: u64,
+    budget: &Budget,
+    location: Localization,
+    span: Span,
+) -> Result<Option<BigDecimal>, ErrorMessage> {
+    if !value.is_integer() || value.is_negative() {
+        return Ok(None);
+    }
+    let Some(mut factor) = value.to_u64() else {
+        return Ok(None);
+    };
+
+    let mut result = BigDecimal::one();
+    while factor > 1 {
+        budget.check_time(location, span)?;
+        result *= BigDecimal::from(factor);
+        budget.check_size(&result, location, span)?;
+        factor = factor.saturating_sub(step);
+    }
+    Ok(Some(result))
+}
+
+fn power(
+    base: BigDecimal,
+    exponent: BigDecimal,
+    budget: &Budget,
+    location: Localization,
+    span: Span,
+) -> Result<BigDecimal, ErrorMessage> {
+    if exponent.is_integer() {
+        let integer_exponent = exponent.to_i64().ok_or_else(|| {
+            ErrorMessage::undefined_power(
+                format!("exponent {exponent} is too large"),
+                location,
+                span,
+            )
+        })?;
+        let result = integer_power(
+            &base,
+            integer_exponent.unsigned_abs(),
+            budget,
+            location,
+            span,
+        )?;
+        if integer_exponent >= 0 {
+            return Ok(result);
+        }
+        if result.is_zero() {
+            return Err(ErrorMessage::division_by_zero(
+                format!("division by zero in ({base} ^ {exponent})"),
+                location,
+                span,
+            ));
+        }
+        return Ok(BigDecimal::one() / result);
+    }
+
+    if base.is_negative() {
+        return Err(ErrorMessage::undefined_power(
+            format!("({base} ^ {exponent}) is not a real number"),
+            location,
+            span,
+        ));
+    }
+
+    base.to_f64()
+        .zip(exponent.to_f64())
+        .and_then(|(base, exponent)| BigDecimal::from_f64(base.powf(exponent)))
+        .ok_or_else(|| {
+            ErrorMessage::undefined_power(
+                format!("({base} ^ {exponent}) is out of range"),
+                location,
+                span,
+            )
+        })
+}
+
+/// Exponentiation by squaring, the size of every product is checked before it is computed.
+fn integer_power(
+    base: &BigDecimal,
+    mut exponent: u64,
+    budget: &Budget,
+    location: Localization,
+    span: Span,
+) -> Result<BigDecimal, ErrorMessage> {
+    let mut result = BigDecimal::one();
+    let mut square = base.clone();
+    while exponent > 0 {
+        budget.check_time(location, span)?;
+        if exponent % 2 == 1 {
+            budget.check_digits(result.digits() + square.digits(), location, span)?;
+            result *= square.clone();
+        }
+        exponent /= 2;
+        if exponent > 0 {
+            budget.check_digits(2 * square.digits(), location, span)?;
+            square = square.square();
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+
+    use crate::math::ast::budget::{Budget, CancellationToken, Limits};
+    use crate::math::ast::evaluation::{evaluate, evaluate_within};
+    use crate::math::ast::parser::error::ErrorMessage;
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::parser::parse;
+    use crate::math::ast::Localization;
+
+    fn evaluate_single(input: &str) -> Option<BigDecimal> {
+        let statements = parse(input.to_string()).unwrap();
+        assert_eq!(1, statements.len());
+        evaluate(&statements[0]).unwrap()
+    }
+
+    fn evaluate_with_digits(input: &str, max_digits: u64) -> ErrorMessage {
+        let statements = parse(input.to_string()).unwrap();
+        let limits = Limits {
+            timeout: Duration::from_secs(60),
+            max_digits,
+        };
+        evaluate_within(
+            &statements[0],
+            &Budget::new(limits, CancellationToken::new()),
+        )
+        .unwrap_err()
+    }
+
+    fn decimal(value: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(value).unwrap())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(decimal("7"), evaluate_single("1 + 2 * 3"));
+        assert_eq!(decimal("-1.5"), evaluate_single("(1 - 4) / 2"));
+        assert_eq!(decimal("8"), evaluate_single("2 ^ 3"));
+        assert_eq!(decimal("0.25"), evaluate_single("2 ^ -2"));
+    }
+
+    #[test]
+    fn factorial() {
+        assert_eq!(decimal("1"), evaluate_single("0!"));
+        assert_eq!(decimal("120"), evaluate_single("5!"));
+        assert_eq!(decimal("720"), evaluate_single("(3!)!"));
+        assert_eq!(decimal("-6"), evaluate_single("-3!"));
+    }
+
+    #[test]
+    fn double_factorial() {
+        assert_eq!(decimal("48"), evaluate_single("6!!"));
+        assert_eq!(decimal("15"), evaluate_single("5!!"));
+        assert_eq!(decimal("1"), evaluate_single("0!!"));
+    }
+
+    #[test]
+    fn factorial_of_negative_number_fails() {
+        let statements = parse("(-3)!".to_string()).unwrap();
+
+        let message = evaluate(&statements[0]).unwrap_err();
+
+        assert_eq!(
+            "factorial is only defined for non-negative integers, got -3".to_string(),
+            message.message
+        );
+        assert_eq!(Localization::at(0, 4), message.localization);
+    }
+
+    #[test]
+    fn factorial_of_fraction_fails() {
+        let statements = parse("1.5!!".to_string()).unwrap();
+
+        let message = evaluate(&statements[0]).unwrap_err();
+
+        assert_eq!(
+            "double factorial is only defined for non-negative integers, got 1.5".to_string(),
+            message.message
+        );
+    }
+
+    #[test]
+    fn absolute_value() {
+        assert_eq!(decimal("3"), evaluate_single("|1 - 4|"));
+        assert_eq!(decimal("2"), evaluate_single("||-1| - 3|"));
+        assert_eq!(decimal("6"), evaluate_single("|-2| * |3|"));
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(decimal("1"), evaluate_single("7 % 3"));
+        assert_eq!(decimal("2"), evaluate_single("-7 mod 3"));
+        assert_eq!(decimal("-2"), evaluate_single("7 mod -3"));
+        assert_eq!(decimal("0.5"), evaluate_single("5.5 % 1"));
+    }
+
+    #[test]
+    fn modulo_by_zero_fails() {
+        let statements = parse("7 % 0".to_string()).unwrap();
+
+        let message = evaluate(&statements[0]).unwrap_err();
+
+        assert_eq!("modulo by zero in (7 mod 0)".to_string(), message.message);
+    }
+
+    #[test]
+    fn division_by_zero_fails() {
+        let statements = parse("1 / (2 - 2)".to_string()).unwrap();
+
+        let message = evaluate(&statements[0]).unwrap_err();
+
+        assert_eq!(
+            "division by zero in (1 / (2 - 2))".to_string(),
+            message.message
+        );
+    }
+
+    #[test]
+    fn expressions_with_variables_are_not_evaluated() {
+        assert_eq!(None, evaluate_single("x! + 1"));
+    }
+
+    #[test]
+    fn huge_powers_exceed_the_digit_budget() {
+        let message = evaluate_with_digits("9^9^9^9", 1000);
+
+        assert_eq!(ErrorKind::ResultTooLarge, message.kind);
+        assert_eq!(
+            "the result would have more than 1000 digits".to_string(),
+            message.message
+        );
+    }
+
+    #[test]
+    fn huge_factorials_exceed_the_digit_budget() {
+        let message = evaluate_with_digits("1 + 100000!", 1000);
+
+        assert_eq!(ErrorKind::ResultTooLarge, message.kind);
+        assert_eq!(
+            "the result would have more than 1000 digits".to_string(),
+            message.message
+        );
+    }
+
+    #[test]
+    fn large_exponents_exceed_the_digit_budget() {
+        for input in [
+            "1e5000",
+            "1e-5000",
+            "1e600 * 1e600",
+            "1e900 + 1e-900",
+            "1e-900 mod 1e900",
+        ] {
+            let message = evaluate_with_digits(input, 1000);
+
+            assert_eq!(ErrorKind::ResultTooLarge, message.kind, "{input}");
+        }
+    }
+
+    #[test]
+    fn cancelled_evaluations_stop() {
+        let statements = parse("1 + 2".to_string()).unwrap();
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+
+        let message = evaluate_within(
+            &statements[0],
+            &Budget::new(Limits::default(), cancellation),
+        )
+        .unwrap_err();
+
+        assert_eq!(ErrorKind::Cancelled, message.kind);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::math::ast::parser::operators::Associativity;
+use crate::math::ast::{HistoryReference, LocalizedSyntaxNode, SyntaxTree};
+
+/// Negations bind tighter than all binary operators, see [OperatorTable](super::parser::operators::OperatorTable).
+const NEGATION: u8 = 10;
+const POSTFIX: u8 = 11;
+/// numbers, variables and everything that is delimited, like fractions and absolute values
+const ATOM: u8 = 12;
+
+impl LocalizedSyntaxNode {
+    /// The node as a formula for LaTeX's math mode, with only the parentheses that are needed.
+    pub fn to_latex(&self) -> String {
+        match &self.tree {
+            SyntaxTree::Variable(name) if name.chars().count() > 1 => format!("\\mathrm{{{name}}}"),
+            SyntaxTree::Variable(name) => name.clone(),
+            SyntaxTree::Number(value) => value.to_string(),
+            SyntaxTree::Sum(left, right) => self.binary(left, "+", right),
+            SyntaxTree::Subtraction(left, right) => self.binary(left, "-", right),
+            SyntaxTree::Product(left, right) => self.binary(left, "\\cdot", right),
+            SyntaxTree::Modulo(left, right) => self.binary(left, "\\bmod", right),
+            SyntaxTree::Division(left, right) => {
+                format!("\\frac{{{}}}{{{}}}", left.to_latex(), right.to_latex())
+            }
+            // a fraction as base would look like a fraction with an exponent in its denominator
+            SyntaxTree::Exponent(base, exponent) => {
+                let needs_parentheses =
+                    base.precedence() < POSTFIX || matches!(base.tree, SyntaxTree::Division(..));
+                format!(
+                    "{}^{{{}}}",
+                    base.operand_latex(needs_parentheses),
+                    exponent.to_latex()
+                )
+            }
+            SyntaxTree::Negation(value) => {
+                format!("-{}", value.operand_latex(value.precedence() <= NEGATION))
+            }
+            SyntaxTree::Factorial(value) => {
+                format!("{}!", value.operand_latex(value.precedence() < ATOM))
+            }
+            SyntaxTree::DoubleFactorial(value) => {
+                format!("{}!!", value.operand_latex(value.precedence() < ATOM))
+            }
+            SyntaxTree::AbsoluteValue(value) => format!("\\left|{}\\right|", value.to_latex()),
+            SyntaxTree::DefinedOperation(definition, left, right) => {
+                self.binary(left, &symbol_latex(&definition.symbol), right)
+            }
+            SyntaxTree::OperatorDefinition(definition) => {
+                let [left, right] = &definition.parameters;
+                format!(
+                    "{left} {} {right} := {}",
+                    symbol_latex(&definition.symbol),
+                    definition.body.to_latex()
+                )
+            }
+            SyntaxTree::HistoryReference(reference) => match reference {
+                HistoryReference::Answer => "\\mathrm{ans}".to_string(),
+                HistoryReference::Percent(number) => format!("\\%{number}"),
+                HistoryReference::Out(number) => format!("\\mathrm{{out}}[{number}]"),
+            },
+        }
+    }
+
+    /// Operands of the same precedence are parenthesized on the side the operator doesn't
+    /// associate to, so that `1 - (2 - 3)` keeps its parentheses.
+    fn binary(&self, left: &Self, operator: &str, right: &Self) -> String {
+        let precedence = self.precedence();
+        let (left_needs_parentheses, right_needs_parentheses) = match self.associativity() {
+            Associativity::Left => (
+                left.precedence() < precedence,
+                right.precedence() <= precedence,
+            ),
+            Associativity::Right => (
+                left.precedence() <= precedence,
+                right.precedence() < precedence,
+            ),
+        };
+        format!(
+            "{} {operator} {}",
+            left.operand_latex(left_needs_parentheses),
+            right.operand_latex(right_needs_parentheses)
+        )
+    }
+
+    fn operand_latex(&self, needs_parentheses: bool) -> String {
+        if needs_parentheses {
+            format!("\\left({}\\right)", self.to_latex())
+        } else {
+            self.to_latex()
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match &self.tree {
+            SyntaxTree::Sum(..) | SyntaxTree::Subtraction(..) => 6,
+            SyntaxTree::Product(..) | SyntaxTree::Modulo(..) => 7,
+            SyntaxTree::Exponent(..) => 8,
+            SyntaxTree::DefinedOperation(definition, ..) => definition.precedence,
+            // a definition is never an operand, it binds weaker than everything else
+            SyntaxTree::OperatorDefinition(_) => 0,
+            SyntaxTree::Negation(_) => NEGATION,
+            SyntaxTree::Factorial(_) | SyntaxTree::DoubleFactorial(_) => POSTFIX,
+            SyntaxTree::Variable(_)
+            | SyntaxTree::Number(_)
+            | SyntaxTree::Division(..)
+            | SyntaxTree::AbsoluteValue(_)
+            | SyntaxTree::HistoryReference(_) => ATOM,
+        }
+    }
+
+    fn associativity(&self) -> Associativity {
+        match &self.tree {
+            SyntaxTree::Exponent(..) => Associativity::Right,
+            SyntaxTree::DefinedOperation(definition, ..) => definition.associativity,
+            _ => Associativity::Left,
+        }
+    }
+}
+
+/// User defined symbols are set in typewriter type, escaping the characters LaTeX treats specially.
+fn symbol_latex(symbol: &str) -> String {
+    let escaped: String = symbol
+        .chars()
+        .map(|character| match character {
+            '%' => "\\%".to_string(),
+            '$' => "\\$".to_string(),
+            '&' => "\\&".to_string(),
+            '~' => "\\textasciitilde{}".to_string(),
+            '^' => "\\textasciicircum{}".to_string(),
+            '<' => "\\textless{}".to_string(),
+            '>' => "\\textgreater{}".to_string(),
+            character => character.to_string(),
+        })
+        .collect();
+    format!("\\mathbin{{\\texttt{{{escaped}}}}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ast::parser::parse;
+
+    fn latex_of(input: &str) -> Vec<String> {
+        parse(input.to_string())
+            .unwrap()
+            .iter()
+            .map(|statement| statement.to_latex())
+            .collect()
+    }
+
+    #[test]
+    fn only_needed_parentheses_are_kept() {
+        let result = latex_of("(1 + 2) * 3 - (4 - x); 1 - 2 - 3; 2 ^ (3 ^ 4); (2 ^ 3) ^ 4");
+
+        let expected = vec![
+            "\\left(1 + 2\\right) \\cdot 3 - \\left(4 - x\\right)",
+            "1 - 2 - 3",
+            "2^{3^{4}}",
+            "\\left(2^{3}\\right)^{4}",
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn delimited_and_unary_operators() {
+        let result = latex_of("-(1 + 2)! / |-3|; -2 ^ 2; (1 / 2) ^ 2; 5 mod 3!!; speed * ans * %1");
+
+        let expected = vec![
+            "\\frac{-\\left(1 + 2\\right)!}{\\left|-3\\right|}",
+            "\\left(-2\\right)^{2}",
+            "\\left(\\frac{1}{2}\\right)^{2}",
+            "5 \\bmod 3!!",
+            "\\mathrm{speed} \\cdot \\mathrm{ans} \\cdot \\%1",
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn user_defined_operators() {
+        let result = latex_of("infixr 5 a <^> b = a + b; 1 <^> 2 <^> 3; (1 <^> 2) * 3");
+
+        let expected = vec![
+            "a \\mathbin{\\texttt{\\textless{}\\textasciicircum{}\\textgreater{}}} b := a + b",
+            "1 \\mathbin{\\texttt{\\textless{}\\textasciicircum{}\\textgreater{}}} 2 \
+             \\mathbin{\\texttt{\\textless{}\\textasciicircum{}\\textgreater{}}} 3",
+            "\\left(1 \\mathbin{\\texttt{\\textless{}\\textasciicircum{}\\textgreater{}}} 2\\right) \\cdot 3",
+        ];
+        assert_eq!(expected, result);
+    }
+}
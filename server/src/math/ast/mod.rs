@@ -1,10 +1,19 @@
+use std::convert::Infallible;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
 use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
 
+use crate::math::ast::parser::error::ErrorMessage;
+use crate::math::ast::parser::operators::OperatorDefinition;
+
+pub mod budget;
+pub mod evaluation;
+mod latex;
 pub mod parser;
 
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Localization {
     line: usize,
     column: usize,
@@ -18,6 +27,101 @@ impl Localization {
     pub fn at(line: usize, column: usize) -> Localization {
         Localization { line, column }
     }
+
+    /// counted from 0
+    pub fn line(self) -> usize {
+        self.line
+    }
+
+    /// the number of characters before `self` in its line
+    pub fn column(self) -> usize {
+        self.column
+    }
+
+    /// `self` lies in a text starting at `from`, the result lies at the same place of a copy of that
+    /// text starting at `to`. Only the first line of the text is indented by its start.
+    fn moved(self, from: Localization, to: Localization) -> Localization {
+        let column = if self.line == from.line {
+            (self.column + to.column).saturating_sub(from.column)
+        } else {
+            self.column
+        };
+        Localization {
+            line: (self.line + to.line).saturating_sub(from.line),
+            column,
+        }
+    }
+}
+
+/// A range of the source text from `start` (inclusive) to `end` (exclusive).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Span {
+    pub start: Localization,
+    pub end: Localization,
+    /// byte offset of `start` in the source text
+    pub start_offset: usize,
+    /// byte offset of `end` in the source text
+    pub end_offset: usize,
+}
+
+impl Span {
+    fn covering(first: Span, last: Span) -> Span {
+        Span {
+            start: first.start,
+            end: last.end,
+            start_offset: first.start_offset,
+            end_offset: last.end_offset,
+        }
+    }
+
+    fn empty_start(self) -> Span {
+        Span {
+            start: self.start,
+            end: self.start,
+            start_offset: self.start_offset,
+            end_offset: self.start_offset,
+        }
+    }
+
+    /// The empty span at the end of `self`, where text can be inserted after it.
+    pub fn empty_end(self) -> Span {
+        Span {
+            start: self.end,
+            end: self.end,
+            start_offset: self.end_offset,
+            end_offset: self.end_offset,
+        }
+    }
+
+    /// `self` lies in a text starting at `from`, the result lies at the same place of a copy of that
+    /// text starting at `to`.
+    pub fn moved(self, from: Span, to: Span) -> Span {
+        Span {
+            start: self.start.moved(from.start, to.start),
+            end: self.end.moved(from.start, to.start),
+            start_offset: (self.start_offset + to.start_offset).saturating_sub(from.start_offset),
+            end_offset: (self.end_offset + to.start_offset).saturating_sub(from.start_offset),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Comment {
+    pub span: Span,
+    pub text: String,
+}
+
+impl Comment {
+    fn new(span: Span, text: String) -> Self {
+        Self { span, text }
+    }
+}
+
+/// `span` covers the source text of the statement without surrounding whitespace, comments and the separating `;`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Statement {
+    pub span: Span,
+    pub result: Result<LocalizedSyntaxNode, ErrorMessage>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -30,7 +134,62 @@ pub enum SyntaxTree {
 
     Subtraction(Box<LocalizedSyntaxNode>, Box<LocalizedSyntaxNode>),
     Division(Box<LocalizedSyntaxNode>, Box<LocalizedSyntaxNode>),
+    Modulo(Box<LocalizedSyntaxNode>, Box<LocalizedSyntaxNode>),
     Negation(Box<LocalizedSyntaxNode>),
+    Factorial(Box<LocalizedSyntaxNode>),
+    DoubleFactorial(Box<LocalizedSyntaxNode>),
+    AbsoluteValue(Box<LocalizedSyntaxNode>),
+    /// an application of a user defined operator
+    DefinedOperation(
+        Arc<OperatorDefinition>,
+        Box<LocalizedSyntaxNode>,
+        Box<LocalizedSyntaxNode>,
+    ),
+    /// a statement like `infixl 6 a <+> b = (a + b) / 2`, it has no value
+    OperatorDefinition(Arc<OperatorDefinition>),
+    HistoryReference(HistoryReference),
+}
+
+/// A reference to the value of an earlier statement of the document.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum HistoryReference {
+    /// `ans`, the value of the latest statement that has a value
+    Answer,
+    /// `%n`, the value of the n-th statement, counted from 1
+    Percent(usize),
+    /// `out[n]`, the same as `%n`
+    Out(usize),
+}
+
+impl HistoryReference {
+    /// The value referred to, `values` are the values of the statements before the reference.
+    pub fn resolve(self, values: &[Option<BigDecimal>]) -> Result<&BigDecimal, String> {
+        match self {
+            HistoryReference::Answer => values
+                .iter()
+                .rev()
+                .flatten()
+                .next()
+                .ok_or_else(|| "no statement before 'ans' has a value".to_string()),
+            HistoryReference::Percent(number) | HistoryReference::Out(number) => {
+                match number.checked_sub(1).and_then(|index| values.get(index)) {
+                    Some(Some(value)) => Ok(value),
+                    Some(None) => Err(format!("statement {number} has no value")),
+                    None => Err(format!("'{self}' doesn't refer to an earlier statement")),
+                }
+            }
+        }
+    }
+}
+
+impl Display for HistoryReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryReference::Answer => write!(f, "ans"),
+            HistoryReference::Percent(number) => write!(f, "%{number}"),
+            HistoryReference::Out(number) => write!(f, "out[{number}]"),
+        }
+    }
 }
 
 // TODO check whether necessary
@@ -44,29 +203,202 @@ pub enum SyntaxTree {
 //     MulInv(Box<Values>),
 // }
 
+/// `location` points to the operator of the node, `span` covers the whole expression.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct LocalizedSyntaxNode {
     location: Localization,
+    span: Span,
+    /// the number of nodes on the longest path down to a leaf, which bounds the recursion depth of walking the tree
+    height: usize,
     tree: SyntaxTree,
 }
 
 impl LocalizedSyntaxNode {
     fn new(location: Localization, tree: SyntaxTree) -> Self {
-        Self { location, tree }
+        Self {
+            location,
+            span: tree.span_of_operands(),
+            height: tree.height_of_operands() + 1,
+            tree,
+        }
     }
 
     fn number<N: Into<BigDecimal>>(location: Localization, number: N) -> Self {
-        Self {
-            location,
-            tree: SyntaxTree::Number(number.into()),
+        Self::new(location, SyntaxTree::Number(number.into()))
+    }
+
+    fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The definition if this node is an operator definition statement.
+    pub fn operator_definition(&self) -> Option<&Arc<OperatorDefinition>> {
+        match &self.tree {
+            SyntaxTree::OperatorDefinition(definition) => Some(definition),
+            _ => None,
         }
     }
 
+    /// The tree with one node per line, indented by its depth and followed by its location
+    /// counted from 1, like `Sum at 1:3`. The body of an operator definition is its child.
+    pub fn outline(&self) -> String {
+        let mut outline = String::new();
+        self.write_outline(&mut outline, 0);
+        outline
+    }
+
+    fn write_outline(&self, outline: &mut String, depth: usize) {
+        let label = match &self.tree {
+            SyntaxTree::Variable(name) => format!("Variable {name}"),
+            SyntaxTree::Number(value) => format!("Number {value}"),
+            SyntaxTree::Sum(..) => "Sum".to_string(),
+            SyntaxTree::Product(..) => "Product".to_string(),
+            SyntaxTree::Exponent(..) => "Exponent".to_string(),
+            SyntaxTree::Subtraction(..) => "Subtraction".to_string(),
+            SyntaxTree::Division(..) => "Division".to_string(),
+            SyntaxTree::Modulo(..) => "Modulo".to_string(),
+            SyntaxTree::Negation(_) => "Negation".to_string(),
+            SyntaxTree::Factorial(_) => "Factorial".to_string(),
+            SyntaxTree::DoubleFactorial(_) => "DoubleFactorial".to_string(),
+            SyntaxTree::AbsoluteValue(_) => "AbsoluteValue".to_string(),
+            SyntaxTree::DefinedOperation(definition, ..) => {
+                format!("DefinedOperation {}", definition.symbol)
+            }
+            SyntaxTree::OperatorDefinition(definition) => {
+                let [left, right] = &definition.parameters;
+                format!(
+                    "OperatorDefinition {} {} {left} {} {right}",
+                    definition.associativity, definition.precedence, definition.symbol
+                )
+            }
+            SyntaxTree::HistoryReference(reference) => format!("HistoryReference {reference}"),
+        };
+        // leaves are located where their text starts, numbers are located before it
+        let location = match self.tree {
+            SyntaxTree::Variable(_) | SyntaxTree::Number(_) | SyntaxTree::HistoryReference(_) => {
+                self.span.start
+            }
+            _ => self.location,
+        };
+        outline.push_str(&format!(
+            "{}{label} at {}:{}\n",
+            "  ".repeat(depth),
+            location.line + 1,
+            location.column + 1
+        ));
+        for child in self.tree.children() {
+            child.write_outline(outline, depth + 1);
+        }
+    }
+
+    /// Replaces the variables named like the first element of a pair by the number of that pair.
+    fn substitute(&self, values: &[(&str, &BigDecimal)]) -> Self {
+        let result = self.try_map_leaves(&mut |node| match &node.tree {
+            SyntaxTree::Variable(name) => Ok::<_, Infallible>(
+                values
+                    .iter()
+                    .find(|(variable, _)| variable == name)
+                    .map(|(_, value)| SyntaxTree::Number((*value).clone())),
+            ),
+            _ => Ok(None),
+        });
+        match result {
+            Ok(node) => node,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Replaces the history references by the values they refer to,
+    /// `values` are the values of the statements before this one.
+    pub fn resolve_history(&self, values: &[Option<BigDecimal>]) -> Result<Self, ErrorMessage> {
+        self.try_map_leaves(&mut |node| match &node.tree {
+            SyntaxTree::HistoryReference(reference) => reference
+                .resolve(values)
+                .map(|value| Some(SyntaxTree::Number(value.clone())))
+                .map_err(|message| ErrorMessage::invalid_history_reference(message, node.span)),
+            _ => Ok(None),
+        })
+    }
+
+    /// Copies the tree, replacing the leaves for which `replacement` returns a tree.
+    /// The bodies of operators are left as they are.
+    fn try_map_leaves<E>(
+        &self,
+        replacement: &mut impl FnMut(&Self) -> Result<Option<SyntaxTree>, E>,
+    ) -> Result<Self, E> {
+        let mut map = |node: &Self| node.try_map_leaves(replacement).map(Box::new);
+        let tree = match &self.tree {
+            SyntaxTree::Variable(_)
+            | SyntaxTree::Number(_)
+            | SyntaxTree::HistoryReference(_)
+            | SyntaxTree::OperatorDefinition(_) => {
+                replacement(self)?.unwrap_or_else(|| self.tree.clone())
+            }
+            SyntaxTree::Sum(left, right) => SyntaxTree::Sum(map(left)?, map(right)?),
+            SyntaxTree::Product(left, right) => SyntaxTree::Product(map(left)?, map(right)?),
+            SyntaxTree::Exponent(left, right) => SyntaxTree::Exponent(map(left)?, map(right)?),
+            SyntaxTree::Subtraction(left, right) => {
+                SyntaxTree::Subtraction(map(left)?, map(right)?)
+            }
+            SyntaxTree::Division(left, right) => SyntaxTree::Division(map(left)?, map(right)?),
+            SyntaxTree::Modulo(left, right) => SyntaxTree::Modulo(map(left)?, map(right)?),
+            SyntaxTree::Negation(value) => SyntaxTree::Negation(map(value)?),
+            SyntaxTree::Factorial(value) => SyntaxTree::Factorial(map(value)?),
+            SyntaxTree::DoubleFactorial(value) => SyntaxTree::DoubleFactorial(map(value)?),
+            SyntaxTree::AbsoluteValue(value) => SyntaxTree::AbsoluteValue(map(value)?),
+            SyntaxTree::DefinedOperation(definition, left, right) => {
+                SyntaxTree::DefinedOperation(definition.clone(), map(left)?, map(right)?)
+            }
+        };
+        Ok(Self {
+            location: self.location,
+            span: self.span,
+            height: self.height,
+            tree,
+        })
+    }
+
     #[cfg(test)]
     fn variable(location: Localization, name: String) -> Self {
         Self::new(location, SyntaxTree::Variable(name))
     }
 
+    #[cfg(test)]
+    fn without_spans(self) -> Self {
+        let strip = |node: Box<Self>| Box::new(node.without_spans());
+        let tree = match self.tree {
+            SyntaxTree::Variable(_) | SyntaxTree::Number(_) | SyntaxTree::HistoryReference(_) => {
+                self.tree
+            }
+            SyntaxTree::Sum(left, right) => SyntaxTree::Sum(strip(left), strip(right)),
+            SyntaxTree::Product(left, right) => SyntaxTree::Product(strip(left), strip(right)),
+            SyntaxTree::Exponent(left, right) => SyntaxTree::Exponent(strip(left), strip(right)),
+            SyntaxTree::Subtraction(left, right) => {
+                SyntaxTree::Subtraction(strip(left), strip(right))
+            }
+            SyntaxTree::Division(left, right) => SyntaxTree::Division(strip(left), strip(right)),
+            SyntaxTree::Modulo(left, right) => SyntaxTree::Modulo(strip(left), strip(right)),
+            SyntaxTree::Negation(value) => SyntaxTree::Negation(strip(value)),
+            SyntaxTree::Factorial(value) => SyntaxTree::Factorial(strip(value)),
+            SyntaxTree::DoubleFactorial(value) => SyntaxTree::DoubleFactorial(strip(value)),
+            SyntaxTree::AbsoluteValue(value) => SyntaxTree::AbsoluteValue(strip(value)),
+            SyntaxTree::DefinedOperation(definition, left, right) => {
+                SyntaxTree::DefinedOperation(definition, strip(left), strip(right))
+            }
+            SyntaxTree::OperatorDefinition(definition) => {
+                let mut definition = (*definition).clone();
+                definition.body = definition.body.without_spans();
+                SyntaxTree::OperatorDefinition(Arc::new(definition))
+            }
+        };
+        Self::new(self.location, tree)
+    }
+
     fn add(location: Localization, left: Self, right: Self) -> Self {
         Self::new(location, SyntaxTree::Sum(Box::new(left), Box::new(right)))
     }
@@ -99,9 +431,92 @@ impl LocalizedSyntaxNode {
         )
     }
 
+    fn modulo(location: Localization, left: Self, right: Self) -> Self {
+        Self::new(
+            location,
+            SyntaxTree::Modulo(Box::new(left), Box::new(right)),
+        )
+    }
+
     fn neg(location: Localization, value: Self) -> Self {
         Self::new(location, SyntaxTree::Negation(Box::new(value)))
     }
+
+    fn factorial(location: Localization, value: Self) -> Self {
+        Self::new(location, SyntaxTree::Factorial(Box::new(value)))
+    }
+
+    fn double_factorial(location: Localization, value: Self) -> Self {
+        Self::new(location, SyntaxTree::DoubleFactorial(Box::new(value)))
+    }
+
+    fn abs(location: Localization, value: Self) -> Self {
+        Self::new(location, SyntaxTree::AbsoluteValue(Box::new(value)))
+    }
+}
+
+impl SyntaxTree {
+    /// The operands in order, for an operator definition its body.
+    fn children(&self) -> Vec<&LocalizedSyntaxNode> {
+        match self {
+            SyntaxTree::Variable(_) | SyntaxTree::Number(_) | SyntaxTree::HistoryReference(_) => {
+                vec![]
+            }
+            SyntaxTree::Sum(left, right)
+            | SyntaxTree::Product(left, right)
+            | SyntaxTree::Exponent(left, right)
+            | SyntaxTree::Subtraction(left, right)
+            | SyntaxTree::Division(left, right)
+            | SyntaxTree::Modulo(left, right)
+            | SyntaxTree::DefinedOperation(_, left, right) => vec![left, right],
+            SyntaxTree::Negation(value)
+            | SyntaxTree::Factorial(value)
+            | SyntaxTree::DoubleFactorial(value)
+            | SyntaxTree::AbsoluteValue(value) => vec![value],
+            SyntaxTree::OperatorDefinition(definition) => vec![&definition.body],
+        }
+    }
+
+    fn height_of_operands(&self) -> usize {
+        match self {
+            SyntaxTree::Variable(_) | SyntaxTree::Number(_) | SyntaxTree::HistoryReference(_) => 0,
+            SyntaxTree::Sum(left, right)
+            | SyntaxTree::Product(left, right)
+            | SyntaxTree::Exponent(left, right)
+            | SyntaxTree::Subtraction(left, right)
+            | SyntaxTree::Division(left, right)
+            | SyntaxTree::Modulo(left, right) => left.height.max(right.height),
+            SyntaxTree::Negation(value)
+            | SyntaxTree::Factorial(value)
+            | SyntaxTree::DoubleFactorial(value)
+            | SyntaxTree::AbsoluteValue(value) => value.height,
+            // evaluating an operation walks the body of the operator as well
+            SyntaxTree::DefinedOperation(definition, left, right) => {
+                left.height.max(right.height).max(definition.body.height)
+            }
+            SyntaxTree::OperatorDefinition(definition) => definition.body.height,
+        }
+    }
+
+    fn span_of_operands(&self) -> Span {
+        match self {
+            SyntaxTree::Variable(_) | SyntaxTree::Number(_) | SyntaxTree::HistoryReference(_) => {
+                Span::default()
+            }
+            SyntaxTree::Sum(left, right)
+            | SyntaxTree::Product(left, right)
+            | SyntaxTree::Exponent(left, right)
+            | SyntaxTree::Subtraction(left, right)
+            | SyntaxTree::Division(left, right)
+            | SyntaxTree::Modulo(left, right) => Span::covering(left.span, right.span),
+            SyntaxTree::Negation(value)
+            | SyntaxTree::Factorial(value)
+            | SyntaxTree::DoubleFactorial(value)
+            | SyntaxTree::AbsoluteValue(value) => value.span,
+            SyntaxTree::DefinedOperation(_, left, right) => Span::covering(left.span, right.span),
+            SyntaxTree::OperatorDefinition(definition) => definition.body.span,
+        }
+    }
 }
 
 impl Display for LocalizedSyntaxNode {
@@ -120,7 +535,28 @@ impl Display for SyntaxTree {
             SyntaxTree::Exponent(left, right) => write!(f, "({left} ^ {right})"),
             SyntaxTree::Subtraction(left, right) => write!(f, "({left} - {right})"),
             SyntaxTree::Division(left, right) => write!(f, "({left} / {right})"),
+            SyntaxTree::Modulo(left, right) => write!(f, "({left} mod {right})"),
             SyntaxTree::Negation(value) => write!(f, "-{value}"),
+            SyntaxTree::Factorial(value) => write!(f, "{}!", PostfixOperand(value)),
+            SyntaxTree::DoubleFactorial(value) => write!(f, "{}!!", PostfixOperand(value)),
+            SyntaxTree::AbsoluteValue(value) => write!(f, "|{value}|"),
+            SyntaxTree::DefinedOperation(definition, left, right) => {
+                write!(f, "({left} {} {right})", definition.symbol)
+            }
+            SyntaxTree::OperatorDefinition(definition) => write!(f, "{definition}"),
+            SyntaxTree::HistoryReference(reference) => write!(f, "{reference}"),
+        }
+    }
+}
+
+/// Wraps negations in parentheses, so that `(-3)!` is not displayed as `-3!`.
+struct PostfixOperand<'a>(&'a LocalizedSyntaxNode);
+
+impl Display for PostfixOperand<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0.tree {
+            SyntaxTree::Negation(_) => write!(f, "({})", self.0),
+            _ => write!(f, "{}", self.0),
         }
     }
 }
@@ -160,4 +596,46 @@ mod tests {
 
         assert_eq!(expected, format!("{}", under_test));
     }
+
+    #[test]
+    fn display_postfix_and_delimited_operators() {
+        let under_test = LocalizedSyntaxNode::modulo(
+            Localization::new(),
+            LocalizedSyntaxNode::factorial(
+                Localization::new(),
+                LocalizedSyntaxNode::neg(
+                    Localization::new(),
+                    LocalizedSyntaxNode::number(Localization::new(), 3),
+                ),
+            ),
+            LocalizedSyntaxNode::abs(
+                Localization::new(),
+                LocalizedSyntaxNode::double_factorial(
+                    Localization::new(),
+                    LocalizedSyntaxNode::variable(Localization::new(), "x".to_string()),
+                ),
+            ),
+        );
+
+        let expected = "((-3)! mod |x!!|)".to_string();
+
+        assert_eq!(expected, format!("{}", under_test));
+    }
+
+    #[test]
+    fn outline() {
+        let under_test = parser::parse("(1 + x) * |2|!".to_string()).unwrap();
+
+        let expected = "\
+Product at 1:9
+  Sum at 1:4
+    Number 1 at 1:2
+    Variable x at 1:6
+  Factorial at 1:14
+    AbsoluteValue at 1:11
+      Number 2 at 1:12
+";
+
+        assert_eq!(expected, under_test[0].outline());
+    }
 }
@@ -1,188 +1,235 @@
-use crate::math::ast::Localization;
-use std::cmp::Ordering;
+use crate::math::ast::parser::error_kind::ErrorKind;
+use crate::math::ast::{Localization, Span};
+use serde::Serialize;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::str::Chars;
 
+/// `localization` points to where the error was detected, `span` covers the affected source text.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ErrorMessage {
     pub message: String,
     pub localization: Localization,
-    priority: Priority,
+    pub span: Span,
+    /// boxed, most errors have none and errors are returned in every `Result` of the parser
+    pub details: Option<Box<Details>>,
+    pub kind: ErrorKind,
 }
 
-type Priority = u8;
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Details {
+    /// a hint how to fix the error
+    pub help: Option<String>,
+    /// additional context that doesn't fit into the message
+    pub notes: Vec<String>,
+    /// fixes that can be applied without further input from the user
+    pub suggestions: Vec<Suggestion>,
+}
 
-impl ErrorMessage {
-    pub fn new(message: String, priority: Priority, localization: Localization) -> Self {
-        ErrorMessage {
-            message,
-            priority,
-            localization,
+/// A machine-applicable fix: replacing the text in `span` by `replacement` resolves the error.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Suggestion {
+    pub fn insert(text: &str, span: Span) -> Self {
+        Suggestion {
+            message: format!("insert '{text}'"),
+            span: span.empty_end(),
+            replacement: text.to_string(),
         }
     }
 
-    pub fn forgot_comma(message: String) -> Self {
-        ErrorMessage::new(message, 121, Localization::new())
+    pub fn replace(text: &str, span: Span, replacement: &str) -> Self {
+        Suggestion {
+            message: format!("did you mean '{replacement}' instead of '{text}'?"),
+            span,
+            replacement: replacement.to_string(),
+        }
     }
 
-    pub fn sign_failed(message: String, localization: Localization) -> Self {
-        ErrorMessage::new(message, 70, localization)
+    pub fn remove(text: &str, span: Span) -> Self {
+        Suggestion {
+            message: format!("remove '{text}'"),
+            span,
+            replacement: String::new(),
+        }
     }
+}
 
-    pub fn missing_opening_parenthesis(message: String) -> Self {
-        ErrorMessage::new(message, 40, Localization::new())
+impl ErrorMessage {
+    pub fn new(message: String, kind: ErrorKind, span: Span) -> Self {
+        ErrorMessage {
+            message,
+            kind,
+            localization: span.start,
+            span,
+            details: None,
+        }
     }
 
-    pub fn missing_closing_parenthesis(message: String) -> Self {
-        ErrorMessage::new(message, 120, Localization::new())
+    pub fn expected_operand(message: String, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::ExpectedOperand, span)
     }
 
-    pub fn empty_expression(message: String) -> Self {
-        ErrorMessage::new(message, 99, Localization::new())
+    pub fn missing_closing_parenthesis(message: String, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::MissingClosingParenthesis, span)
+            .with_help("insert ')' to close the parenthesis")
     }
 
-    pub fn atom_failed(message: String) -> Self {
-        ErrorMessage::new(message, 10, Localization::new())
+    pub fn missing_closing_absolute_value_bar(message: String, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::MissingClosingAbsoluteValueBar, span)
+            .with_help("insert '|' to close the absolute value")
     }
 
-    pub fn term_failed(message: String) -> Self {
-        ErrorMessage::new(message, 15, Localization::new())
+    pub fn empty_expression(message: String, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::EmptyExpression, span)
     }
 
-    pub fn expression_failed(message: String) -> Self {
-        ErrorMessage::new(message, 20, Localization::new())
+    pub fn invalid_factorial_argument(
+        message: String,
+        localization: Localization,
+        span: Span,
+    ) -> Self {
+        ErrorMessage::new(message, ErrorKind::InvalidFactorialArgument, span)
+            .at(localization)
+            .with_note("the gamma function is not supported yet")
     }
 
-    pub fn exponentiation_failed(message: String) -> Self {
-        ErrorMessage::new(message, 25, Localization::new())
+    pub fn division_by_zero(message: String, localization: Localization, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::DivisionByZero, span).at(localization)
     }
 
-    pub fn expected_end_of_input(message: String) -> Self {
-        ErrorMessage::new(message, 110, Localization::new())
+    pub fn undefined_power(message: String, localization: Localization, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::UndefinedPower, span).at(localization)
     }
 
-    pub fn assignment_error(message: String) -> Self {
-        ErrorMessage::new(message, 120, Localization::new())
+    pub fn timeout(message: String, localization: Localization, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::Timeout, span).at(localization)
     }
 
-    pub fn no_assignment_found(message: String) -> Self {
-        ErrorMessage::new(message, 0, Localization::new())
+    pub fn result_too_large(message: String, localization: Localization, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::ResultTooLarge, span).at(localization)
     }
 
-    pub fn missing_tuple_comma() -> Self {
-        ErrorMessage::new("expected ',' for tuple".to_string(), 0, Localization::new())
+    pub fn cancelled(localization: Localization, span: Span) -> Self {
+        ErrorMessage::new(
+            "evaluation was cancelled".to_string(),
+            ErrorKind::Cancelled,
+            span,
+        )
+        .at(localization)
     }
 
-    pub fn empty_function_arguments(input: Chars) -> Self {
+    pub fn nesting_too_deep(max_depth: usize, span: Span) -> Self {
         ErrorMessage::new(
-            format!("expected function arguments, got {}", input.as_str()),
-            80,
-            Localization::new(),
+            format!("expression is nested deeper than {max_depth} levels"),
+            ErrorKind::NestingTooDeep,
+            span,
         )
     }
 
-    pub fn leftover(leftover: String, localization: Localization) -> Self {
-        ErrorMessage {
-            message: format!("expected end of input, '{leftover}' was left"),
-            localization,
-            priority: 0,
-        }
+    pub fn input_too_large(length: usize, max_length: usize, span: Span) -> Self {
+        ErrorMessage::new(
+            format!("the document has {length} bytes, at most {max_length} are accepted"),
+            ErrorKind::InputTooLarge,
+            span,
+        )
+        .with_help("split the document into smaller ones")
     }
 
-    pub fn map_message(mut self, mapper: fn(String) -> String) -> Self {
-        self.message = mapper(self.message);
-        self
+    pub fn invalid_operator_definition(message: String, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::InvalidOperatorDefinition, span)
+            .with_help("define operators like 'infixl 6 a <+> b = (a + b) / 2'")
     }
-}
-
-#[macro_export]
-macro_rules! most_important_of {
-    ($($error_message:expr),+ $(,)?) => {
-        {
-            let mut messages = vec![$($error_message),+];
-            messages.reverse();
-            messages.sort();
-            messages.last().unwrap().clone()
-        }
-    };
-}
 
-impl PartialOrd for ErrorMessage {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    pub fn invalid_history_reference(message: String, span: Span) -> Self {
+        ErrorMessage::new(message, ErrorKind::InvalidHistoryReference, span)
     }
-}
 
-impl Ord for ErrorMessage {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.priority.cmp(&other.priority)
+    pub fn number_out_of_range(number: &str, span: Span) -> Self {
+        ErrorMessage::new(
+            format!("the number {number} is out of range"),
+            ErrorKind::NumberOutOfRange,
+            span,
+        )
     }
-}
 
-impl Display for ErrorMessage {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
+    pub fn leftover(leftover: String, span: Span) -> Self {
+        ErrorMessage::new(
+            format!("expected end of input, '{leftover}' was left"),
+            ErrorKind::UnexpectedInput,
+            span,
+        )
+        .with_help("separate statements with ';'")
     }
-}
 
-impl From<ErrorMessage> for String {
-    fn from(error: ErrorMessage) -> Self {
-        error.message
+    fn at(mut self, localization: Localization) -> Self {
+        self.localization = localization;
+        self
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn most_important_of_one() {
-        let error_message = ErrorMessage::missing_tuple_comma();
 
-        let result = most_important_of!(error_message.clone());
-
-        assert_eq!(error_message, result)
+    pub fn help(&self) -> Option<&str> {
+        self.details.as_ref()?.help.as_deref()
     }
 
-    #[test]
-    fn first_is_more_important() {
-        let more_important = ErrorMessage::new("message1".to_string(), 100, Localization::new());
-        let less_important = ErrorMessage::new("message2".to_string(), 1, Localization::new());
-
-        let result = most_important_of!(more_important.clone(), less_important);
-
-        assert_eq!(more_important, result)
+    pub fn notes(&self) -> &[String] {
+        self.details.as_ref().map_or(&[], |details| &details.notes)
     }
 
-    #[test]
-    fn second_is_more_important() {
-        let more_important = ErrorMessage::new("message1".to_string(), 100, Localization::new());
-        let less_important = ErrorMessage::new("message2".to_string(), 1, Localization::new());
+    pub fn suggestions(&self) -> &[Suggestion] {
+        self.details
+            .as_ref()
+            .map_or(&[], |details| &details.suggestions)
+    }
 
-        let result = most_important_of!(less_important, more_important.clone());
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.details_mut().help = Some(help.to_string());
+        self
+    }
 
-        assert_eq!(more_important, result)
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.details_mut().notes.push(note.to_string());
+        self
     }
 
-    #[test]
-    fn returns_first_if_equally_important() {
-        let first = ErrorMessage::new("message1".to_string(), 5, Localization::new());
-        let second = ErrorMessage::new("message2".to_string(), 5, Localization::new());
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.details_mut().suggestions.push(suggestion);
+        self
+    }
 
-        let result = most_important_of!(first.clone(), second);
+    fn details_mut(&mut self) -> &mut Details {
+        self.details.get_or_insert_with(Default::default)
+    }
 
-        assert_eq!(first, result)
+    /// Moves the error along with the text it was found in, see [Span::moved].
+    pub fn moved(mut self, from: Span, to: Span) -> Self {
+        self.localization = self.localization.moved(from.start, to.start);
+        self.span = self.span.moved(from, to);
+        if let Some(details) = &mut self.details {
+            for suggestion in &mut details.suggestions {
+                suggestion.span = suggestion.span.moved(from, to);
+            }
+        }
+        self
     }
 
-    #[test]
-    fn most_important_of_three() {
-        let most_important = ErrorMessage::new("message1".to_string(), 100, Localization::new());
-        let less_important = ErrorMessage::new("message2".to_string(), 5, Localization::new());
-        let least_important = ErrorMessage::new("message3".to_string(), 1, Localization::new());
+    pub fn map_message(mut self, mapper: fn(String) -> String) -> Self {
+        self.message = mapper(self.message);
+        self
+    }
+}
 
-        let result = most_important_of!(less_important, most_important.clone(), least_important);
+impl Display for ErrorMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
-        assert_eq!(most_important, result)
+impl From<ErrorMessage> for String {
+    fn from(error: ErrorMessage) -> Self {
+        error.message
     }
 }
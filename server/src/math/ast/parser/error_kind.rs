@@ -0,0 +1,171 @@
+use serde::Serialize;
+
+/// What went wrong, independent of the wording of the message.
+///
+/// The codes are part of the protocol: never reuse or renumber them, only append new kinds.
+/// E0001 to E0005 and E0010 belonged to errors of an earlier parser and stay reserved.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize)]
+pub enum ErrorKind {
+    ExpectedOperand,
+    EmptyExpression,
+    MissingClosingParenthesis,
+    MissingClosingAbsoluteValueBar,
+    UnexpectedInput,
+    InvalidFactorialArgument,
+    DivisionByZero,
+    UndefinedPower,
+    Timeout,
+    ResultTooLarge,
+    Cancelled,
+    NestingTooDeep,
+    InputTooLarge,
+    InvalidOperatorDefinition,
+    InvalidHistoryReference,
+    NumberOutOfRange,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct ErrorCodeDescription {
+    pub code: &'static str,
+    pub kind: ErrorKind,
+    pub explanation: &'static str,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 16] = [
+        ErrorKind::ExpectedOperand,
+        ErrorKind::EmptyExpression,
+        ErrorKind::MissingClosingParenthesis,
+        ErrorKind::MissingClosingAbsoluteValueBar,
+        ErrorKind::UnexpectedInput,
+        ErrorKind::InvalidFactorialArgument,
+        ErrorKind::DivisionByZero,
+        ErrorKind::UndefinedPower,
+        ErrorKind::Timeout,
+        ErrorKind::ResultTooLarge,
+        ErrorKind::Cancelled,
+        ErrorKind::NestingTooDeep,
+        ErrorKind::InputTooLarge,
+        ErrorKind::InvalidOperatorDefinition,
+        ErrorKind::InvalidHistoryReference,
+        ErrorKind::NumberOutOfRange,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::ExpectedOperand => "E0006",
+            ErrorKind::EmptyExpression => "E0007",
+            ErrorKind::MissingClosingParenthesis => "E0008",
+            ErrorKind::MissingClosingAbsoluteValueBar => "E0009",
+            ErrorKind::UnexpectedInput => "E0011",
+            ErrorKind::InvalidFactorialArgument => "E0012",
+            ErrorKind::DivisionByZero => "E0013",
+            ErrorKind::UndefinedPower => "E0014",
+            ErrorKind::Timeout => "E0015",
+            ErrorKind::ResultTooLarge => "E0016",
+            ErrorKind::Cancelled => "E0017",
+            ErrorKind::NestingTooDeep => "E0018",
+            ErrorKind::InputTooLarge => "E0019",
+            ErrorKind::InvalidOperatorDefinition => "E0020",
+            ErrorKind::InvalidHistoryReference => "E0021",
+            ErrorKind::NumberOutOfRange => "E0022",
+        }
+    }
+
+    pub fn explanation(self) -> &'static str {
+        match self {
+            ErrorKind::ExpectedOperand => {
+                "An operand was expected: a number, a variable, a reference like ans, %3 or out[3], '-', '(' or '|'."
+            }
+            ErrorKind::EmptyExpression => "A statement or a parenthesis doesn't contain anything.",
+            ErrorKind::MissingClosingParenthesis => "A '(' was not closed by a matching ')'.",
+            ErrorKind::MissingClosingAbsoluteValueBar => {
+                "An absolute value |x| was not closed by a second '|'."
+            }
+            ErrorKind::UnexpectedInput => {
+                "A statement is complete, but more input follows before the next ';'."
+            }
+            ErrorKind::InvalidFactorialArgument => {
+                "The factorial n! and the double factorial n!! are only defined for non-negative integers."
+            }
+            ErrorKind::DivisionByZero => "The divisor of a division or of a modulo is zero.",
+            ErrorKind::UndefinedPower => {
+                "A power has no real result, e.g. a negative base with a fractional exponent, or it is too large."
+            }
+            ErrorKind::Timeout => "The evaluation took longer than the server allows.",
+            ErrorKind::ResultTooLarge => {
+                "A result has more digits than the server allows, e.g. a large power or factorial."
+            }
+            ErrorKind::Cancelled => {
+                "The evaluation was cancelled because a newer version of the document arrived."
+            }
+            ErrorKind::NestingTooDeep => {
+                "An expression nests too many parentheses, absolute values, signs, powers or operators."
+            }
+            ErrorKind::InputTooLarge => "The document is longer than the server accepts.",
+            ErrorKind::InvalidOperatorDefinition => {
+                "An operator definition must look like 'infixl 6 a <+> b = (a + b) / 2' and can't redefine a built-in operator."
+            }
+            ErrorKind::InvalidHistoryReference => {
+                "A reference like ans, %3 or out[3] must refer to an earlier statement that has a value."
+            }
+            ErrorKind::NumberOutOfRange => {
+                "The exponent of a number like 1e-5 is larger than 100000 or smaller than -100000."
+            }
+        }
+    }
+
+    pub fn describe_all() -> Vec<ErrorCodeDescription> {
+        ErrorKind::ALL
+            .iter()
+            .map(|&kind| ErrorCodeDescription {
+                code: kind.code(),
+                kind,
+                explanation: kind.explanation(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn codes_are_numbered_consecutively_except_the_reserved_ones() {
+        let reserved = [1, 2, 3, 4, 5, 10];
+        let codes: Vec<_> = ErrorKind::ALL.iter().map(|kind| kind.code()).collect();
+
+        let expected: Vec<_> = (1..=ErrorKind::ALL.len() + reserved.len())
+            .filter(|number| !reserved.contains(number))
+            .map(|number| format!("E{number:04}"))
+            .collect();
+        assert_eq!(expected, codes);
+    }
+
+    #[test]
+    fn all_contains_every_kind_once() {
+        let kinds: HashSet<_> = ErrorKind::ALL.iter().collect();
+
+        assert_eq!(ErrorKind::ALL.len(), kinds.len());
+    }
+
+    #[test]
+    fn describe_all_lists_codes_with_explanations() {
+        let descriptions = ErrorKind::describe_all();
+
+        assert_eq!(
+            ErrorCodeDescription {
+                code: "E0008",
+                kind: ErrorKind::MissingClosingParenthesis,
+                explanation: "A '(' was not closed by a matching ')'.",
+            },
+            descriptions[2]
+        );
+        assert!(descriptions
+            .iter()
+            .all(|description| !description.explanation.is_empty()));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::math::ast::parser::lexer::{tokenize, Token, TokenKind};
+use crate::math::ast::parser::operators::OperatorTable;
+use crate::math::ast::parser::split_statements;
+use crate::math::ast::Span;
+
+/// What a token is for highlighting, a coarser view of [TokenKind].
+#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenClass {
+    Number,
+    Identifier,
+    /// the built-in and user defined operators, including named ones like `mod`, `=` and `;`
+    Operator,
+    /// parentheses, brackets and the bars of absolute values
+    Paren,
+    Comment,
+    /// a character that can't start any token
+    Error,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ClassifiedToken {
+    pub class: TokenClass,
+    pub span: Span,
+}
+
+/// The tokens of a document without whitespace, classified like the parser sees them:
+/// a name is an operator where an operator definition before it, or the definition it is
+/// the symbol of, makes it one.
+pub fn classify_tokens(input: &str) -> Vec<ClassifiedToken> {
+    let named_operators = named_operators(input);
+    tokenize(input)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Whitespace)
+        .map(|token| ClassifiedToken {
+            class: class_of(&token, &named_operators),
+            span: token.span,
+        })
+        .collect()
+}
+
+/// The offsets of the names that are used or defined as operators.
+/// Documents that are too long to parse only have `mod`.
+fn named_operators(input: &str) -> HashSet<usize> {
+    let Ok(sources) = split_statements(input) else {
+        return HashSet::new();
+    };
+
+    let mut operators = OperatorTable::new();
+    let mut offsets = HashSet::new();
+    for source in &sources {
+        let is_operator = |token: &&Token| {
+            token.kind == TokenKind::Identifier && operators.get(token.text).is_some()
+        };
+        offsets.extend(
+            source
+                .tokens()
+                .iter()
+                .filter(is_operator)
+                .map(|token| token.span.start_offset),
+        );
+
+        let statement = source.parse(&mut operators);
+        let defines_operator =
+            matches!(&statement.result, Ok(node) if node.operator_definition().is_some());
+        if defines_operator {
+            // `infixl 7 a div b = ...`
+            if let Some(symbol) = source.tokens().get(3) {
+                offsets.insert(symbol.span.start_offset);
+            }
+        }
+    }
+    offsets
+}
+
+fn class_of(token: &Token, named_operators: &HashSet<usize>) -> TokenClass {
+    match token.kind {
+        TokenKind::Number => TokenClass::Number,
+        TokenKind::Identifier if token.text == "mod" => TokenClass::Operator,
+        TokenKind::Identifier if named_operators.contains(&token.span.start_offset) => {
+            TokenClass::Operator
+        }
+        TokenKind::Identifier => TokenClass::Identifier,
+        TokenKind::Plus
+        | TokenKind::Minus
+        | TokenKind::Asterisk
+        | TokenKind::Slash
+        | TokenKind::Percent
+        | TokenKind::Caret
+        | TokenKind::Factorial
+        | TokenKind::DoubleFactorial
+        | TokenKind::Operator
+        | TokenKind::Semicolon => TokenClass::Operator,
+        TokenKind::OpeningParenthesis
+        | TokenKind::ClosingParenthesis
+        | TokenKind::OpeningBracket
+        | TokenKind::ClosingBracket
+        | TokenKind::Bar => TokenClass::Paren,
+        TokenKind::Comment => TokenClass::Comment,
+        TokenKind::Unknown => TokenClass::Error,
+        TokenKind::Whitespace => unreachable!("whitespace is skipped"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ast::parser::highlighting::{classify_tokens, TokenClass};
+
+    /// The text and the class of every token.
+    fn classes(input: &str) -> Vec<(&str, TokenClass)> {
+        classify_tokens(input)
+            .into_iter()
+            .map(|token| {
+                let span = token.span;
+                (&input[span.start_offset..span.end_offset], token.class)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokens_are_classified() {
+        use TokenClass::*;
+
+        let result = classes("-(1.5 + x)! mod |2| # two\n; out[1] ?");
+
+        let expected = vec![
+            ("-", Operator),
+            ("(", Paren),
+            ("1.5", Number),
+            ("+", Operator),
+            ("x", Identifier),
+            (")", Paren),
+            ("!", Operator),
+            ("mod", Operator),
+            ("|", Paren),
+            ("2", Number),
+            ("|", Paren),
+            ("# two", Comment),
+            (";", Operator),
+            ("out", Identifier),
+            ("[", Paren),
+            ("1", Number),
+            ("]", Paren),
+            ("?", Operator),
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn unknown_characters_are_errors() {
+        assert_eq!(
+            vec![("1", TokenClass::Number), ("§", TokenClass::Error)],
+            classes("1 §")
+        );
+    }
+
+    #[test]
+    fn named_operators_after_their_definition() {
+        let result = classes("7 div 2; infixl 7 a div b = a / b; 7 div 2");
+
+        let div_classes: Vec<_> = result
+            .into_iter()
+            .filter(|(text, _)| *text == "div")
+            .map(|(_, class)| class)
+            .collect();
+        assert_eq!(
+            vec![
+                TokenClass::Identifier,
+                TokenClass::Operator,
+                TokenClass::Operator
+            ],
+            div_classes
+        );
+    }
+
+    #[test]
+    fn malformed_definitions_define_nothing() {
+        let result = classes("infixl 12 a div b = a; 7 div 2");
+
+        assert!(result
+            .iter()
+            .filter(|(text, _)| *text == "div")
+            .all(|(_, class)| *class == TokenClass::Identifier));
+    }
+}
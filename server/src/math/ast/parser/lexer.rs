@@ -0,0 +1,332 @@
+use crate::math::ast::{Localization, Span};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum TokenKind {
+    /// an integer like `123` or a decimal like `1.5`, `2e10` and `3e-4`
+    Number,
+    /// a name made of letters, `mod` is an identifier as well
+    Identifier,
+    Plus,
+    Minus,
+    Asterisk,
+    Slash,
+    Percent,
+    Caret,
+    /// `!`
+    Factorial,
+    /// `!!`, only if both exclamation marks are adjacent
+    DoubleFactorial,
+    /// the symbol of a user defined operator like `<+>`, or `=`
+    Operator,
+    OpeningParenthesis,
+    ClosingParenthesis,
+    /// `[`, only used by history references like `out[3]`
+    OpeningBracket,
+    ClosingBracket,
+    Bar,
+    Semicolon,
+    Whitespace,
+    /// `# ...` and `// ...` up to the end of the line, or `/* ... */`
+    Comment,
+    /// a character that can't start any token
+    Unknown,
+}
+
+impl TokenKind {
+    /// Whitespace and comments don't show up in the syntax tree.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// Splits `input` into tokens without gaps, including whitespace and comments.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    Lexer {
+        input,
+        position: Localization::new(),
+        offset: 0,
+    }
+    .collect()
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    position: Localization,
+    /// byte offset of `position`
+    offset: usize,
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.input[self.offset..];
+        let (kind, length) = scan(rest)?;
+        let text = &rest[..length];
+
+        let start = self.position;
+        let start_offset = self.offset;
+        for character in text.chars() {
+            if character == '\n' {
+                self.position.line += 1;
+                self.position.column = 0;
+            } else {
+                self.position.column += 1;
+            }
+        }
+        self.offset += length;
+
+        Some(Token {
+            kind,
+            text,
+            span: Span {
+                start,
+                end: self.position,
+                start_offset,
+                end_offset: self.offset,
+            },
+        })
+    }
+}
+
+/// The kind and the length in bytes of the token at the beginning of `text`.
+fn scan(text: &str) -> Option<(TokenKind, usize)> {
+    let first = text.chars().next()?;
+    let token = match first {
+        '#' => (TokenKind::Comment, line_length(text)),
+        '/' if text.starts_with("//") => (TokenKind::Comment, line_length(text)),
+        // an unterminated block comment extends to the end of the input
+        '/' if text.starts_with("/*") => (
+            TokenKind::Comment,
+            text[2..].find("*/").map_or(text.len(), |end| end + 4),
+        ),
+        first if SYMBOL_CHARACTERS.contains(first) && is_operator_symbol(text) => {
+            (TokenKind::Operator, symbol_length(text))
+        }
+        '!' if text.starts_with("!!") => (TokenKind::DoubleFactorial, 2),
+        first if first.is_whitespace() => (
+            TokenKind::Whitespace,
+            length_while(text, char::is_whitespace),
+        ),
+        first if first.is_ascii_digit() => (TokenKind::Number, number_length(text)),
+        first if first.is_alphabetic() => (
+            TokenKind::Identifier,
+            length_while(text, char::is_alphabetic),
+        ),
+        first => {
+            let kind = match first {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Asterisk,
+                '/' => TokenKind::Slash,
+                '%' => TokenKind::Percent,
+                '^' => TokenKind::Caret,
+                '!' => TokenKind::Factorial,
+                '(' => TokenKind::OpeningParenthesis,
+                ')' => TokenKind::ClosingParenthesis,
+                '[' => TokenKind::OpeningBracket,
+                ']' => TokenKind::ClosingBracket,
+                '|' => TokenKind::Bar,
+                ';' => TokenKind::Semicolon,
+                _ => TokenKind::Unknown,
+            };
+            (kind, first.len_utf8())
+        }
+    };
+    Some(token)
+}
+
+/// The characters that operator symbols are made of.
+const SYMBOL_CHARACTERS: &str = "+-*/%^!<>=&~@$?:";
+
+/// The built-in operators consist of a single character, or `!!`.
+const BUILT_IN_SYMBOL_CHARACTERS: &str = "+-*/%^!";
+
+/// The symbol at the beginning of `text` is as long as possible and ends before a comment.
+fn symbol_length(text: &str) -> usize {
+    let mut length = 0;
+    for character in text.chars() {
+        let rest = &text[length..];
+        if !SYMBOL_CHARACTERS.contains(character)
+            || rest.starts_with("//")
+            || rest.starts_with("/*")
+        {
+            break;
+        }
+        length += character.len_utf8();
+    }
+    length
+}
+
+/// A sequence of built-in operators like `*-` in `2*-3` is split into its operators,
+/// all other symbols are operators on their own.
+fn is_operator_symbol(text: &str) -> bool {
+    text[..symbol_length(text)]
+        .chars()
+        .any(|character| !BUILT_IN_SYMBOL_CHARACTERS.contains(character))
+}
+
+/// Line comments end before the next linebreak.
+fn line_length(text: &str) -> usize {
+    text.find('\n').unwrap_or(text.len())
+}
+
+fn length_while(text: &str, predicate: fn(char) -> bool) -> usize {
+    text.find(|character| !predicate(character))
+        .unwrap_or(text.len())
+}
+
+/// Digits, optionally followed by one of the separators `.`, `e-` or `e` and more digits.
+fn number_length(text: &str) -> usize {
+    let is_digit = |character: char| character.is_ascii_digit();
+    let integer = length_while(text, is_digit);
+
+    for separator in [".", "e-", "e"] {
+        if let Some(fraction) = text[integer..].strip_prefix(separator) {
+            let fraction = length_while(fraction, is_digit);
+            if fraction > 0 {
+                return integer + separator.len() + fraction;
+            }
+        }
+    }
+    integer
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ast::parser::lexer::{tokenize, TokenKind};
+    use crate::math::ast::{Localization, Span};
+
+    fn kinds_and_texts(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .into_iter()
+            .filter(|token| !token.kind.is_trivia())
+            .map(|token| (token.kind, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn operators_and_delimiters() {
+        let result = kinds_and_texts("(1 + x) * -|y|! mod 2 ^ 3!! - out[4];");
+
+        let expected = vec![
+            (TokenKind::OpeningParenthesis, "("),
+            (TokenKind::Number, "1"),
+            (TokenKind::Plus, "+"),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::ClosingParenthesis, ")"),
+            (TokenKind::Asterisk, "*"),
+            (TokenKind::Minus, "-"),
+            (TokenKind::Bar, "|"),
+            (TokenKind::Identifier, "y"),
+            (TokenKind::Bar, "|"),
+            (TokenKind::Factorial, "!"),
+            (TokenKind::Identifier, "mod"),
+            (TokenKind::Number, "2"),
+            (TokenKind::Caret, "^"),
+            (TokenKind::Number, "3"),
+            (TokenKind::DoubleFactorial, "!!"),
+            (TokenKind::Minus, "-"),
+            (TokenKind::Identifier, "out"),
+            (TokenKind::OpeningBracket, "["),
+            (TokenKind::Number, "4"),
+            (TokenKind::ClosingBracket, "]"),
+            (TokenKind::Semicolon, ";"),
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn numbers() {
+        let result = kinds_and_texts("12 3.45 6e7 8e-9 1.2.3 4e x");
+
+        let expected = vec![
+            (TokenKind::Number, "12"),
+            (TokenKind::Number, "3.45"),
+            (TokenKind::Number, "6e7"),
+            (TokenKind::Number, "8e-9"),
+            (TokenKind::Number, "1.2"),
+            (TokenKind::Unknown, "."),
+            (TokenKind::Number, "3"),
+            (TokenKind::Number, "4"),
+            (TokenKind::Identifier, "e"),
+            (TokenKind::Identifier, "x"),
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn operator_symbols() {
+        let result = kinds_and_texts("a <+> b = a*-b <=>// c\n!!!");
+
+        let expected = vec![
+            (TokenKind::Identifier, "a"),
+            (TokenKind::Operator, "<+>"),
+            (TokenKind::Identifier, "b"),
+            (TokenKind::Operator, "="),
+            (TokenKind::Identifier, "a"),
+            (TokenKind::Asterisk, "*"),
+            (TokenKind::Minus, "-"),
+            (TokenKind::Identifier, "b"),
+            (TokenKind::Operator, "<=>"),
+            (TokenKind::DoubleFactorial, "!!"),
+            (TokenKind::Factorial, "!"),
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn comments_and_whitespace_are_tokens() {
+        let result: Vec<_> = tokenize("1 # one\n/* two */2")
+            .into_iter()
+            .map(|token| (token.kind, token.text))
+            .collect();
+
+        let expected = vec![
+            (TokenKind::Number, "1"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Comment, "# one"),
+            (TokenKind::Whitespace, "\n"),
+            (TokenKind::Comment, "/* two */"),
+            (TokenKind::Number, "2"),
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn spans_count_characters_and_bytes() {
+        let result = tokenize("ä +\n b");
+
+        let spans: Vec<_> = result.iter().map(|token| token.span).collect();
+        let span = |start: (usize, usize), end: (usize, usize), offsets: (usize, usize)| Span {
+            start: Localization::at(start.0, start.1),
+            end: Localization::at(end.0, end.1),
+            start_offset: offsets.0,
+            end_offset: offsets.1,
+        };
+        assert_eq!(
+            vec![
+                span((0, 0), (0, 1), (0, 2)),
+                span((0, 1), (0, 2), (2, 3)),
+                span((0, 2), (0, 3), (3, 4)),
+                span((0, 3), (1, 1), (4, 6)),
+                span((1, 1), (1, 2), (6, 7)),
+            ],
+            spans
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_extends_to_the_end() {
+        let result = kinds_and_texts("1 /* 2 + 3");
+
+        assert_eq!(vec![(TokenKind::Number, "1")], result);
+    }
+}
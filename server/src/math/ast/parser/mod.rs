@@ -1,390 +1,747 @@
-use core::convert::identity;
-use core::str::Chars;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use bigdecimal::BigDecimal;
 use log::debug;
-use parser_combinator::either::Either;
-use parser_combinator::either::Either3;
-use parser_combinator::pair::Pair;
-use parser_combinator::parser::{match_anything, match_character, match_literal, Parser};
-use parser_combinator::repeated::RepeatedParser;
-use parser_combinator::triple::Triple;
-use parser_combinator::*;
 
-use error::ErrorMessage;
+use error::{ErrorMessage, Suggestion};
+use lexer::{tokenize, Token, TokenKind};
+use operators::{Associativity, InfixOperator, OperatorDefinition, OperatorTable, MAX_PRECEDENCE};
 
+use crate::math::ast::Comment;
+use crate::math::ast::HistoryReference;
 use crate::math::ast::Localization;
 use crate::math::ast::LocalizedSyntaxNode;
+use crate::math::ast::Span;
+use crate::math::ast::Statement;
 use crate::math::ast::SyntaxTree;
-use crate::most_important_of;
 
-mod error;
+pub mod error;
+pub mod error_kind;
+pub mod highlighting;
+pub mod lexer;
+pub mod operators;
+mod suggestions;
 #[cfg(test)]
 mod tests;
 
-type ParseResult<'a> =
-    parser_combinator::ParseResult<'a, CharWrapper<'a>, LocalizedSyntaxNode, ErrorMessage>;
+/// Deeper nesting would overflow the stack, while parsing or while walking the syntax tree.
+pub const MAX_NESTING_DEPTH: usize = 100;
 
-#[derive(Clone, Debug)]
-struct CharWrapper<'a> {
-    chars: Chars<'a>,
-    start: Localization,
-    end: Localization,
-}
+/// Longer documents are rejected before parsing, in bytes.
+pub const MAX_INPUT_LENGTH: usize = 100_000;
 
-impl<'a> CharWrapper<'a> {
-    fn new(chars: Chars<'a>) -> CharWrapper<'a> {
-        Self {
-            chars,
-            start: Localization::new(),
-            end: Localization::new(),
-        }
-    }
-}
+/// Numbers with larger exponents are rejected, written out they would have more digits.
+pub const MAX_EXPONENT: u64 = 100_000;
 
-impl<'a> Iterator for CharWrapper<'a> {
-    type Item = char;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.start = self.end;
-        self.end.column += 1;
-        let next = self.chars.next();
+/// Error messages show at most this many characters of the remaining input.
+const PREVIEW_LENGTH: usize = 20;
 
-        if next == Some('\n') {
-            self.end.line += 1;
-            self.end.column = 0;
+pub fn parse(input: String) -> Result<Vec<LocalizedSyntaxNode>, ErrorMessage> {
+    debug!("parsing {input}");
+    check_input_length(&input)?;
+
+    let tokens = significant_tokens(&input);
+    let end = end_of(&input);
+    let mut operators = OperatorTable::new();
+    let mut remaining = &tokens[..];
+    let mut start = Span::default();
+
+    let mut result = vec![];
+    loop {
+        let mut parser = Parser::new(&input, remaining, start, end, &operators);
+        let statement = parser.parse_statement().map_err(syntax_error)?;
+        let separated = parser.expect(TokenKind::Semicolon).is_some();
+        let next = parser.peek().map(|token| token.span);
+        (remaining, start) = (&remaining[parser.position..], parser.consumed);
+
+        if let Some(definition) = statement.operator_definition() {
+            operators.define(definition.clone());
+        }
+        result.push(statement);
+
+        match next {
+            None => return Ok(result),
+            Some(_) if separated => {}
+            Some(next) => {
+                return Err(ErrorMessage::leftover(
+                    input[next.start_offset..].to_string(),
+                    Span::covering(next, end),
+                ))
+            }
         }
-        next
     }
 }
 
-trait Operator {
-    fn parser<'a>() -> Parser<'a, CharWrapper<'a>, CharWrapper<'a>, ErrorMessage>;
+/// Parses every statement of a document on its own, so that a syntax error only affects the statement it occurs in.
+/// After a failing statement, parsing continues after the next `;`.
+pub fn parse_document(input: String) -> Vec<Result<LocalizedSyntaxNode, ErrorMessage>> {
+    parse_statements(input)
+        .into_iter()
+        .map(|statement| statement.result)
+        .collect()
+}
 
-    fn combine(
-        operator: CharWrapper,
-        lhs: LocalizedSyntaxNode,
-        rhs: LocalizedSyntaxNode,
-    ) -> LocalizedSyntaxNode;
+/// Like [parse_document], but keeps the span of every statement, also of the ones that failed to parse.
+pub fn parse_statements(input: String) -> Vec<Statement> {
+    parse_statements_with(input, &mut OperatorTable::new())
 }
 
-struct PlusAndMinus;
+/// Like [parse_statements], starting with the operators of `operators`.
+/// The operators defined by the document are added to it.
+pub fn parse_statements_with(input: String, operators: &mut OperatorTable) -> Vec<Statement> {
+    debug!("parsing document {input}");
 
-impl Operator for PlusAndMinus {
-    fn parser<'a>() -> Parser<'a, CharWrapper<'a>, CharWrapper<'a>, ErrorMessage> {
-        match_literal(CharWrapper::new("+".chars()))
-            .or_else(match_literal(CharWrapper::new("-".chars())))
-            .with_error(|_, input: CharWrapper| {
-                ErrorMessage::term_failed(format!(
-                    "expected operator + or -, got {}",
-                    input.chars.collect::<String>()
-                ))
-            })
-            .peek_and_transform(|mut x, y| {
-                x.end = y.start;
-                x
-            })
+    match split_statements(&input) {
+        Ok(sources) => sources
+            .iter()
+            .map(|source| source.parse(operators))
+            .collect(),
+        Err(error) => vec![Statement {
+            span: error.span,
+            result: Err(error),
+        }],
     }
+}
+
+/// The source text of one statement, from the previous `;` or the beginning of the document
+/// up to the next `;` or the end of the document.
+#[derive(Debug, Clone)]
+pub struct StatementSource<'a> {
+    /// covers the significant tokens of the statement, like [Statement::span]
+    pub span: Span,
+    /// the whole source text of the statement, including whitespace and comments
+    pub text: &'a str,
+    /// covers `text`
+    pub text_span: Span,
+    document: &'a str,
+    tokens: Vec<Token<'a>>,
+}
 
-    fn combine(
-        operator: CharWrapper,
-        lhs: LocalizedSyntaxNode,
-        rhs: LocalizedSyntaxNode,
-    ) -> LocalizedSyntaxNode {
-        let op = operator.chars.collect::<String>();
-        match &*op {
-            "+" => LocalizedSyntaxNode::add(operator.end, lhs, rhs),
-            "-" => LocalizedSyntaxNode::sub(operator.end, lhs, rhs),
-            _ => unreachable!("{op}"),
+/// Splits a document into its statements without parsing them.
+/// The text of a statement is followed by a `;`, except for the last one, which isn't empty.
+pub fn split_statements(input: &str) -> Result<Vec<StatementSource<'_>>, ErrorMessage> {
+    check_input_length(input)?;
+
+    let mut sources = vec![];
+    let mut tokens = vec![];
+    let mut start = Span::default();
+    for token in tokenize(input) {
+        match token.kind {
+            TokenKind::Semicolon => {
+                let end = token.span.empty_start();
+                sources.push(StatementSource::new(input, tokens, start, end));
+                tokens = vec![];
+                start = token.span.empty_end();
+            }
+            kind if kind.is_trivia() => {}
+            _ => tokens.push(token),
         }
     }
+    if !tokens.is_empty() {
+        sources.push(StatementSource::new(input, tokens, start, end_of(input)));
+    }
+    Ok(sources)
 }
 
-struct MulAndDiv;
+impl<'a> StatementSource<'a> {
+    fn new(document: &'a str, tokens: Vec<Token<'a>>, start: Span, end: Span) -> Self {
+        let span = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Span::covering(first.span, last.span),
+            _ => end,
+        };
+        let text_span = Span::covering(start, end);
+        StatementSource {
+            span,
+            text: &document[text_span.start_offset..text_span.end_offset],
+            text_span,
+            document,
+            tokens,
+        }
+    }
 
-impl Operator for MulAndDiv {
-    fn parser<'a>() -> Parser<'a, CharWrapper<'a>, CharWrapper<'a>, ErrorMessage> {
-        match_literal(CharWrapper::new("*".chars()))
-            .or_else(match_literal(CharWrapper::new("/".chars())))
-            .with_error(|_, input: CharWrapper| {
-                ErrorMessage::term_failed(format!(
-                    "expected operator + or -, got {}",
-                    input.chars.collect::<String>()
-                ))
-            })
-            .peek_and_transform(|mut x, y| {
-                x.end = y.start;
-                x
-            })
+    /// The source text of the statement without surrounding whitespace and comments.
+    pub fn statement(&self) -> &'a str {
+        &self.document[self.span.start_offset..self.span.end_offset]
     }
 
-    fn combine(
-        operator: CharWrapper,
-        lhs: LocalizedSyntaxNode,
-        rhs: LocalizedSyntaxNode,
-    ) -> LocalizedSyntaxNode {
-        let op = operator.chars.collect::<String>();
-        match &*op {
-            "*" => LocalizedSyntaxNode::mul(operator.end, lhs, rhs),
-            "/" => LocalizedSyntaxNode::div(operator.end, lhs, rhs),
-            _ => unreachable!("{op}",),
-        }
+    /// The tokens of the statement without whitespace and comments.
+    pub fn tokens(&self) -> &[Token<'a>] {
+        &self.tokens
     }
-}
 
-pub fn parse(input: String) -> Result<Vec<LocalizedSyntaxNode>, ErrorMessage> {
-    debug!("parsing {input}");
+    /// The names and symbols in the statement that may refer to user defined operators.
+    pub fn symbols(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.tokens
+            .iter()
+            .filter(|token| matches!(token.kind, TokenKind::Operator | TokenKind::Identifier))
+            .map(|token| token.text)
+    }
 
-    let (result, leftover) = parse_expression
-        .separated_by(match_character(';'))
-        .with_error(|err, _| {
-            err.fold(identity, |err2| {
-                err2.fold(ErrorMessage::forgot_comma, identity)
+    /// The references to earlier statements in the statement. It may contain more references
+    /// than the syntax tree, e.g. `%3` in `5 %3` is a modulo.
+    pub fn history_references(&self) -> Vec<HistoryReference> {
+        let following = |index: usize| self.tokens.get(index);
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(index, token)| match (token.kind, token.text) {
+                (TokenKind::Identifier, "ans") => Some(HistoryReference::Answer),
+                (TokenKind::Percent, _) => following(index + 1)
+                    .and_then(statement_number)
+                    .map(HistoryReference::Percent),
+                (TokenKind::Identifier, "out")
+                    if following(index + 1).map(|token| token.kind)
+                        == Some(TokenKind::OpeningBracket) =>
+                {
+                    following(index + 2)
+                        .and_then(statement_number)
+                        .map(HistoryReference::Out)
+                }
+                _ => None,
             })
-        })
-        .with_error(|error, _| error.map_message(|message| format!("Syntax Error: {message}",)))
-        .transform(move |(first, rest)| {
-            let mut result = vec![first];
-            for (_, fragment) in rest {
-                result.push(fragment);
-            }
-            result
-        })
-        .skip(whitespace)
-        .skip(semicolon)
-        .parse(CharWrapper::new(input.chars()))?;
+            .collect()
+    }
 
-    let leftover_string: String = leftover.chars.collect();
-    if !leftover_string.is_empty() {
-        return Err(ErrorMessage::leftover(leftover_string, leftover.end));
+    /// Parses the statement with the operators of `operators`.
+    /// An operator definition is added to `operators`.
+    pub fn parse(&self, operators: &mut OperatorTable) -> Statement {
+        let (start, end) = (self.text_span.empty_start(), self.text_span.empty_end());
+        let result = parse_complete_statement(self.document, &self.tokens, start, end, operators)
+            .map_err(|error| suggestions::add_suggestions(error, &self.tokens));
+
+        if let Some(definition) = result
+            .as_ref()
+            .ok()
+            .and_then(|statement| statement.operator_definition())
+        {
+            operators.define(definition.clone());
+        }
+        Statement {
+            span: self.span,
+            result,
+        }
     }
+}
 
-    Ok(result)
+/// Collects all comments of a document, so that they can be shown next to the results.
+pub fn parse_comments(input: &str) -> Vec<Comment> {
+    tokenize(input)
+        .into_iter()
+        .filter(|token| token.kind == TokenKind::Comment)
+        .map(|token| Comment::new(token.span, token.text.to_string()))
+        .collect()
 }
 
-fn parse_expression(input: CharWrapper) -> ParseResult {
-    if !input.clone().any(|char| !char.is_whitespace()) {
-        return Err(ErrorMessage::empty_expression(format!(
-            "expected expression, got '{}'",
-            input.chars.collect::<String>()
-        )));
+fn check_input_length(input: &str) -> Result<(), ErrorMessage> {
+    if input.len() <= MAX_INPUT_LENGTH {
+        return Ok(());
     }
+    let span = Span::covering(Span::default(), end_of(input));
+    Err(ErrorMessage::input_too_large(
+        input.len(),
+        MAX_INPUT_LENGTH,
+        span,
+    ))
+}
 
-    let summand_and_operator_parser =
-        RepeatedParser::zero_or_more(Pair::new(parse_term, PlusAndMinus::parser()));
-
-    Pair::new(summand_and_operator_parser, parse_term)
-        .with_error(|error, _| error.fold(|inner_error| inner_error.reduce(), identity))
-        .transform(|(summands, result)| combine_to_tree::<PlusAndMinus>(summands, result))
-        .parse(input)
+fn significant_tokens(input: &str) -> Vec<Token<'_>> {
+    tokenize(input)
+        .into_iter()
+        .filter(|token| !token.kind.is_trivia())
+        .collect()
 }
 
-fn combine_to_tree<T: Operator>(
-    mut summands_and_operators: Vec<(LocalizedSyntaxNode, CharWrapper)>,
-    last_summand: LocalizedSyntaxNode,
-) -> LocalizedSyntaxNode {
-    if summands_and_operators.is_empty() {
-        return last_summand;
+/// The empty span at the end of `input`.
+fn end_of(input: &str) -> Span {
+    let last_line = input.rsplit('\n').next().unwrap_or_default();
+    let end = Localization::at(input.matches('\n').count(), last_line.chars().count());
+    Span {
+        start: end,
+        end,
+        start_offset: input.len(),
+        end_offset: input.len(),
     }
+}
 
-    let (second_last_summand, operator) = summands_and_operators.pop().unwrap();
+fn parse_complete_statement(
+    source: &str,
+    tokens: &[Token],
+    start: Span,
+    end: Span,
+    operators: &OperatorTable,
+) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+    let mut parser = Parser::new(source, tokens, start, end, operators);
+    let statement = parser.parse_statement().map_err(syntax_error)?;
+
+    if let Some(token) = parser.peek() {
+        let leftover = source[token.span.start_offset..end.start_offset].trim_end();
+        return Err(ErrorMessage::leftover(
+            leftover.to_string(),
+            Span::covering(token.span, end),
+        ));
+    }
 
-    let lhs = combine_to_tree::<T>(summands_and_operators, second_last_summand);
+    Ok(statement)
+}
 
-    T::combine(operator, lhs, last_summand)
+/// `None` if the exponent exceeds [MAX_EXPONENT]. bigdecimal parses it as an `i64` and negates it,
+/// which fails for longer exponents and overflows for the smallest one.
+fn parse_number(text: &str) -> Option<BigDecimal> {
+    if let Some((_, exponent)) = text.split_once('e') {
+        match exponent.parse::<i64>() {
+            Ok(exponent) if exponent.unsigned_abs() <= MAX_EXPONENT => {}
+            _ => return None,
+        }
+    }
+    BigDecimal::from_str(text).ok()
 }
 
-fn parse_term(input: CharWrapper) -> ParseResult {
-    let summand_and_operator_parser =
-        RepeatedParser::zero_or_more(Pair::new(parse_exponent, MulAndDiv::parser()));
+fn syntax_error(error: ErrorMessage) -> ErrorMessage {
+    error.map_message(|message| format!("Syntax Error: {message}"))
+}
 
-    Pair::new(summand_and_operator_parser, parse_exponent)
-        .with_error(|error, _| error.fold(|inner_error| inner_error.reduce(), identity))
-        .transform(|(summands, result)| combine_to_tree::<MulAndDiv>(summands, result))
-        .parse(input)
+/// Long chains of operators build deep trees without nesting, see [MAX_NESTING_DEPTH].
+fn limit_height(node: LocalizedSyntaxNode) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+    if node.height > MAX_NESTING_DEPTH {
+        return Err(ErrorMessage::nesting_too_deep(MAX_NESTING_DEPTH, node.span));
+    }
+    Ok(node)
 }
 
-fn parse_exponent(input: CharWrapper) -> ParseResult {
-    let exponentiation_operator_parser = match_literal(CharWrapper::new("^".chars()))
-        .with_error(|_, input: CharWrapper| {
-            ErrorMessage::exponentiation_failed(format!(
-                "expected operator ^, got {}",
-                input.chars.collect::<String>()
-            ))
-        })
-        .peek_and_transform(|mut x, y| {
-            x.end = y.start;
-            x
-        });
-
-    let exponent_parser =
-        RepeatedParser::zero_or_more(Pair::new(exponentiation_operator_parser, parse_exponent))
-            .with_error(|error, _| error.reduce());
-
-    Pair::new(parse_sign, exponent_parser)
-        .transform(move |(x, y)| {
-            y.into_iter().fold(x, |left, (op, right)| {
-                LocalizedSyntaxNode::exp(op.end, left, right)
-            })
-        })
-        .with_error(|error, _| error.reduce())
-        .parse(input)
+/// A precedence climbing parser over the significant tokens of a statement,
+/// or of a whole document for [parse], where `;` ends an expression.
+struct Parser<'a> {
+    source: &'a str,
+    tokens: &'a [Token<'a>],
+    /// the index of the next token
+    position: usize,
+    /// the empty span right after the last consumed token
+    consumed: Span,
+    /// the empty span where the input of the parser ends
+    end: Span,
+    /// the number of enclosing parentheses, absolute values, signs and exponents
+    depth: usize,
+    operators: &'a OperatorTable,
+    /// the body of an operator definition can't refer to the history
+    in_definition: bool,
 }
 
-fn parse_sign(input: CharWrapper) -> ParseResult {
-    let error_mapper = |(sign_error, expression_in_brackets_error), input: CharWrapper| {
-        let message = format!(
-            "expected '-' or {expression_in_brackets_error}, got '{}'",
-            input.chars.collect::<String>(),
-        );
+impl<'a> Parser<'a> {
+    fn new(
+        source: &'a str,
+        tokens: &'a [Token<'a>],
+        start: Span,
+        end: Span,
+        operators: &'a OperatorTable,
+    ) -> Self {
+        Parser {
+            source,
+            tokens,
+            position: 0,
+            consumed: start,
+            end,
+            depth: 0,
+            operators,
+            in_definition: false,
+        }
+    }
 
-        let new_message = match sign_error {
-            Either::Left(_) => ErrorMessage::sign_failed(message, input.end),
-            Either::Right(message) => message,
-        };
+    fn peek(&self) -> Option<&'a Token<'a>> {
+        self.tokens.get(self.position)
+    }
 
-        most_important_of!(new_message, expression_in_brackets_error)
-    };
+    fn peek_kind(&self) -> Option<TokenKind> {
+        self.peek().map(|token| token.kind)
+    }
 
-    let prefix_parser = match_literal(CharWrapper::new("-".chars()))
-        .pair(parse_sign)
-        .transform(move |(op, x)| LocalizedSyntaxNode::neg(op.end, x));
+    fn advance(&mut self) -> Token<'a> {
+        let token = self.tokens[self.position];
+        self.position += 1;
+        self.consumed = token.span.empty_end();
+        token
+    }
 
-    prefix_parser
-        .or_else(parse_expression_in_brackets)
-        .with_error(error_mapper)
-        .skip(whitespace)
-        .parse(input)
-}
+    fn expect(&mut self, kind: TokenKind) -> Option<Token<'a>> {
+        if self.peek_kind() != Some(kind) {
+            return None;
+        }
+        Some(self.advance())
+    }
 
-fn parse_expression_in_brackets(input: CharWrapper) -> ParseResult {
-    let error_mapper =
-        |(expression_in_brackets_error, atom_error), _| match expression_in_brackets_error {
-            Either3::Left(_) => most_important_of!(
-                ErrorMessage::missing_opening_parenthesis(format!("'(' or {atom_error}")),
-                atom_error
-            ),
-            Either3::Middle(message) => message,
-            Either3::Right(_) => {
-                ErrorMessage::missing_closing_parenthesis("missing closing parenthesis".to_string())
+    /// Errors about the next token point to its first character.
+    fn next_character_span(&self) -> Span {
+        match self.peek() {
+            Some(token) => first_character_span(token),
+            None => self.end,
+        }
+    }
+
+    /// The beginning of the remaining input, for error messages.
+    fn preview(&self) -> String {
+        let start = self
+            .peek()
+            .map_or(self.end.start_offset, |token| token.span.start_offset);
+        let text = &self.source[start..self.end.start_offset];
+        match text.char_indices().nth(PREVIEW_LENGTH) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.to_string(),
+        }
+    }
+
+    /// Enters one more level of nesting, see [MAX_NESTING_DEPTH]. `opening` is reported if that's one level too many.
+    fn nested(&mut self, opening: Span) -> Result<(), ErrorMessage> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(ErrorMessage::nesting_too_deep(MAX_NESTING_DEPTH, opening));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// An expression, or an operator definition if the statement starts with `infixl` or `infixr`.
+    fn parse_statement(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        match self.peek() {
+            Some(token)
+                if token.kind == TokenKind::Identifier
+                    && Associativity::from_keyword(token.text).is_some() =>
+            {
+                self.parse_operator_definition()
             }
+            _ => self.parse_expression(),
+        }
+    }
+
+    /// `infixl 6 a <+> b = (a + b) / 2` defines `<+>` for the statements after it.
+    fn parse_operator_definition(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let keyword = self.advance();
+        let associativity =
+            Associativity::from_keyword(keyword.text).expect("only called for keywords");
+
+        let precedence = self.expect_token(
+            |token| token.kind == TokenKind::Number && parse_precedence(token.text).is_some(),
+            &format!("a precedence from 0 to {MAX_PRECEDENCE}"),
+        )?;
+        let precedence = parse_precedence(precedence.text).expect("checked by expect_token");
+        let left = self.expect_token(
+            |token| token.kind == TokenKind::Identifier,
+            "the name of the left operand",
+        )?;
+        let symbol = self.expect_token(
+            |token| match token.kind {
+                TokenKind::Operator => token.text != "=",
+                TokenKind::Identifier => true,
+                kind => OperatorTable::is_built_in(token.text) && kind != TokenKind::Number,
+            },
+            "an operator symbol",
+        )?;
+        if OperatorTable::is_built_in(symbol.text) {
+            return Err(ErrorMessage::invalid_operator_definition(
+                format!(
+                    "'{}' is a built-in operator and can't be redefined",
+                    symbol.text
+                ),
+                symbol.span,
+            ));
+        }
+        let right = self.expect_token(
+            |token| token.kind == TokenKind::Identifier,
+            "the name of the right operand",
+        )?;
+        if right.text == left.text {
+            return Err(ErrorMessage::invalid_operator_definition(
+                format!("both operands are named '{}'", right.text),
+                right.span,
+            ));
+        }
+        self.expect_token(
+            |token| token.kind == TokenKind::Operator && token.text == "=",
+            "'=' and the definition of the operator",
+        )?;
+        self.in_definition = true;
+        let body = self.parse_expression()?;
+
+        let span = Span::covering(keyword.span, body.span);
+        let definition = OperatorDefinition {
+            symbol: symbol.text.to_string(),
+            precedence,
+            associativity,
+            parameters: [left.text.to_string(), right.text.to_string()],
+            body,
         };
+        let node = SyntaxTree::OperatorDefinition(Arc::new(definition));
+        limit_height(LocalizedSyntaxNode::new(keyword.span.start, node).with_span(span))
+    }
 
-    Triple::new(
-        match_literal(CharWrapper::new("(".chars())),
-        parse_expression,
-        match_literal(CharWrapper::new(")".chars())),
-    )
-    .second()
-    .or_else(parse_atom)
-    .with_error(error_mapper)
-    .parse(input)
-}
+    /// Consumes the next token if it is `accepted`, otherwise reports that `expected` was expected.
+    fn expect_token(
+        &mut self,
+        accepted: impl Fn(&Token) -> bool,
+        expected: &str,
+    ) -> Result<Token<'a>, ErrorMessage> {
+        match self.peek() {
+            Some(token) if accepted(token) => Ok(self.advance()),
+            _ => Err(ErrorMessage::invalid_operator_definition(
+                format!("expected {expected}, got '{}'", self.preview()),
+                self.next_character_span(),
+            )),
+        }
+    }
 
-fn parse_atom(input: CharWrapper) -> ParseResult {
-    let integer_parser = parse_natural_numbers.transform(move |numeric_string| {
-        LocalizedSyntaxNode::number(input.start, BigDecimal::from_str(&numeric_string).unwrap())
-    });
+    fn parse_expression(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        if self.peek().is_none() {
+            return Err(ErrorMessage::empty_expression(
+                format!("expected expression, got '{}'", self.preview()),
+                Span::covering(self.consumed, self.end),
+            ));
+        }
 
-    let float_parser = parse_float.transform(move |x| LocalizedSyntaxNode::number(input.start, x));
+        self.parse_binary_operation(0)
+    }
 
-    float_parser
-        .or_else(integer_parser)
-        .with_error(|_, _| ErrorMessage::atom_failed("a number".to_string()))
-        .or_else(parse_identifier)
-        .with_error(|(err, _), _| err)
-        .skip(whitespace)
-        .parse(input)
-}
+    /// Parses operands joined by operators with at least `min_precedence`.
+    fn parse_binary_operation(
+        &mut self,
+        min_precedence: u8,
+    ) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let mut result = self.parse_negation()?;
+
+        while let Some(token) = self.peek() {
+            let operator = match self.infix_operator(token) {
+                Some(operator) if operator.precedence >= min_precedence => operator,
+                _ => break,
+            };
+            self.advance();
+
+            let right = match operator.associativity {
+                Associativity::Left => self.parse_binary_operation(operator.precedence + 1)?,
+                // every right associative operator of a chain nests one level deeper
+                Associativity::Right => {
+                    self.nested(first_character_span(token))?;
+                    let right = self.parse_binary_operation(operator.precedence)?;
+                    self.depth -= 1;
+                    right
+                }
+            };
+            result = limit_height(operator.apply(last_character(token), result, right))?;
+        }
+
+        Ok(result)
+    }
+
+    fn infix_operator(&self, token: &Token) -> Option<&'a InfixOperator> {
+        match token.kind {
+            TokenKind::Number | TokenKind::Unknown => None,
+            _ => self.operators.get(token.text),
+        }
+    }
+
+    fn parse_negation(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let sign = match self.expect(TokenKind::Minus) {
+            Some(sign) => sign,
+            None => return self.parse_factorial(),
+        };
+
+        self.nested(sign.span)?;
+        let value = self.parse_negation()?;
+        self.depth -= 1;
+
+        let span = Span::covering(sign.span, value.span);
+        Ok(LocalizedSyntaxNode::neg(sign.span.start, value).with_span(span))
+    }
+
+    fn parse_factorial(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let mut result = self.parse_operand()?;
+
+        loop {
+            let build: fn(Localization, LocalizedSyntaxNode) -> LocalizedSyntaxNode =
+                match self.peek_kind() {
+                    Some(TokenKind::Factorial) => LocalizedSyntaxNode::factorial,
+                    Some(TokenKind::DoubleFactorial) => LocalizedSyntaxNode::double_factorial,
+                    _ => return Ok(result),
+                };
+            let operator = self.advance();
+            let span = Span::covering(result.span, operator.span);
+            result = limit_height(build(operator.span.start, result).with_span(span))?;
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let token = match self.peek() {
+            Some(token) => *token,
+            None => return Err(self.expected_operand()),
+        };
+
+        match token.kind {
+            TokenKind::OpeningParenthesis => self.parse_expression_in_brackets(),
+            TokenKind::Bar => self.parse_absolute_value(),
+            TokenKind::Number => {
+                self.advance();
+                let value = parse_number(token.text)
+                    .ok_or_else(|| ErrorMessage::number_out_of_range(token.text, token.span))?;
+                let location = self.preceding_character(token.span);
+                Ok(LocalizedSyntaxNode::number(location, value).with_span(token.span))
+            }
+            TokenKind::Identifier if token.text == "ans" => {
+                self.advance();
+                self.history_reference(HistoryReference::Answer, token.span)
+            }
+            TokenKind::Identifier
+                if token.text == "out"
+                    && self.tokens.get(self.position + 1).map(|token| token.kind)
+                        == Some(TokenKind::OpeningBracket) =>
+            {
+                self.parse_statement_reference()
+            }
+            TokenKind::Percent => self.parse_statement_reference(),
+            TokenKind::Identifier => {
+                self.advance();
+                let variable = SyntaxTree::Variable(token.text.to_string());
+                Ok(LocalizedSyntaxNode::new(token.span.start, variable).with_span(token.span))
+            }
+            _ => Err(self.expected_operand()),
+        }
+    }
+
+    /// `%3` and `out[3]` refer to the value of the third statement.
+    fn parse_statement_reference(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let first = self.advance();
+        let is_out = first.kind == TokenKind::Identifier;
+        if is_out {
+            self.advance();
+        }
 
-fn parse_float(
-    input: CharWrapper,
-) -> parser_combinator::ParseResult<CharWrapper, BigDecimal, String> {
-    let separator_parser = match_literal(CharWrapper::new(".".chars()))
-        .or_else(match_literal(CharWrapper::new("e-".chars())))
-        .or_else(match_literal(CharWrapper::new("e".chars())))
-        .with_error(|_, input| {
+        let number = match self.peek().and_then(statement_number) {
+            Some(number) => number,
+            None => {
+                return Err(ErrorMessage::invalid_history_reference(
+                    format!(
+                        "expected the number of a statement, got '{}'",
+                        self.preview()
+                    ),
+                    self.next_character_span(),
+                ))
+            }
+        };
+        let mut last = self.advance();
+
+        let reference = if is_out {
+            last = self.expect(TokenKind::ClosingBracket).ok_or_else(|| {
+                ErrorMessage::invalid_history_reference(
+                    format!("expected ']', got '{}'", self.preview()),
+                    self.next_character_span(),
+                )
+            })?;
+            HistoryReference::Out(number)
+        } else {
+            HistoryReference::Percent(number)
+        };
+        self.history_reference(reference, Span::covering(first.span, last.span))
+    }
+
+    fn history_reference(
+        &self,
+        reference: HistoryReference,
+        span: Span,
+    ) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        if self.in_definition {
+            return Err(ErrorMessage::invalid_history_reference(
+                format!("'{reference}' can't be used in the definition of an operator"),
+                span,
+            ));
+        }
+        let node = SyntaxTree::HistoryReference(reference);
+        Ok(LocalizedSyntaxNode::new(span.start, node).with_span(span))
+    }
+
+    fn expected_operand(&self) -> ErrorMessage {
+        ErrorMessage::expected_operand(
             format!(
-                "expected decimal separator '.' or 'e', got {} ",
-                input.chars.as_str(),
+                "expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '{}'",
+                self.preview()
+            ),
+            self.next_character_span(),
+        )
+    }
+
+    fn parse_expression_in_brackets(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let opening = self.advance();
+        self.nested(opening.span)?;
+        let value = self.parse_expression()?;
+
+        let closing = self.expect(TokenKind::ClosingParenthesis).ok_or_else(|| {
+            ErrorMessage::missing_closing_parenthesis(
+                "missing closing parenthesis".to_string(),
+                self.next_character_span(),
             )
-        })
-        .transform(|y| y.chars);
-
-    let to_float = |(leading, separator, fractional): (String, Chars, String)| {
-        let num = format!("{leading}{}{}", separator.as_str(), fractional.as_str());
-        BigDecimal::from_str(&num).unwrap()
-    };
-
-    Triple::new(
-        parse_natural_numbers,
-        separator_parser,
-        parse_natural_numbers,
-    )
-    .transform(to_float)
-    .with_error(|error, _| error.fold(identity, identity, identity))
-    .parse(input)
-}
+            .with_suggestion(Suggestion::insert(")", value.span))
+        })?;
+        self.depth -= 1;
 
-fn parse_natural_numbers(
-    input: CharWrapper,
-) -> parser_combinator::ParseResult<CharWrapper, String, String> {
-    let to_integer = |characters: Vec<char>| {
-        characters
-            .iter()
-            .fold(String::new(), |mut result, character| {
-                result.push(*character);
-                result
-            })
-    };
+        let span = Span::covering(opening.span, closing.span);
+        Ok(value.with_span(span))
+    }
 
-    match_anything()
-        .validate(
-            |character: &char| character.is_numeric(),
-            "numeric character".to_string(),
-        )
-        .one_or_more()
-        .transform(to_integer)
-        .parse(input)
+    fn parse_absolute_value(&mut self) -> Result<LocalizedSyntaxNode, ErrorMessage> {
+        let opening = self.advance();
+        self.nested(opening.span)?;
+        let value = self.parse_expression()?;
+
+        let closing = self.expect(TokenKind::Bar).ok_or_else(|| {
+            ErrorMessage::missing_closing_absolute_value_bar(
+                "missing closing '|' of absolute value".to_string(),
+                self.next_character_span(),
+            )
+            .with_suggestion(Suggestion::insert("|", value.span))
+        })?;
+        self.depth -= 1;
+
+        let span = Span::covering(opening.span, closing.span);
+        Ok(LocalizedSyntaxNode::abs(opening.span.start, value).with_span(span))
+    }
+
+    /// Numbers are located at the character in front of them, at the start of the input if there is none.
+    fn preceding_character(&self, span: Span) -> Localization {
+        let Localization { line, column } = span.start;
+        if column > 0 {
+            return Localization::at(line, column - 1);
+        }
+        if line == 0 {
+            return Localization::new();
+        }
+        // the linebreak at the end of the previous line
+        let previous_line = self.source[..span.start_offset - 1]
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default();
+        Localization::at(line - 1, previous_line.chars().count())
+    }
 }
 
-fn parse_identifier(input: CharWrapper) -> ParseResult {
-    let transformer = move |letters: Vec<char>| LocalizedSyntaxNode {
-        tree: SyntaxTree::Variable(letters.into_iter().collect()),
-        location: input.end,
-    };
+fn first_character_span(token: &Token) -> Span {
+    let length = token.text.chars().next().map_or(0, char::len_utf8);
+    Span {
+        start: token.span.start,
+        end: Localization::at(token.span.start.line, token.span.start.column + 1),
+        start_offset: token.span.start_offset,
+        end_offset: token.span.start_offset + length,
+    }
+}
 
-    match_anything()
-        .validate(
-            |character: &char| character.is_alphabetic(),
-            "alphabetic character".to_string(),
-        )
-        .one_or_more()
-        .transform(transformer)
-        .with_error(|err, input: CharWrapper| ErrorMessage::new(err, 0, input.end))
-        .parse(input)
+/// Statements are counted from 1.
+fn statement_number(token: &Token) -> Option<usize> {
+    if token.kind != TokenKind::Number {
+        return None;
+    }
+    token.text.parse().ok().filter(|number| *number > 0)
 }
 
-fn whitespace(
-    input: CharWrapper,
-) -> parser_combinator::ParseResult<CharWrapper, char, ErrorMessage> {
-    match_anything()
-        .validate(
-            |character: &char| character == &' ' || character == &'\n',
-            "alphabetic character".to_string(),
-        )
-        .with_error(|err, input: CharWrapper| ErrorMessage::new(err, 0, input.end))
-        .parse(input)
+fn parse_precedence(text: &str) -> Option<u8> {
+    text.parse()
+        .ok()
+        .filter(|precedence| *precedence <= MAX_PRECEDENCE)
 }
 
-fn semicolon(
-    input: CharWrapper,
-) -> parser_combinator::ParseResult<CharWrapper, char, ErrorMessage> {
-    match_anything()
-        .validate(
-            |character| character == &';',
-            "expected semicolon".to_string(),
-        )
-        .with_error(|err, input: CharWrapper| ErrorMessage::new(err, 0, input.end))
-        .parse(input)
+/// Operators are located at their last character, operators never span several lines.
+fn last_character(token: &Token) -> Localization {
+    Localization::at(token.span.end.line, token.span.end.column - 1)
 }
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use crate::math::ast::{Localization, LocalizedSyntaxNode, SyntaxTree};
+
+/// User defined operators have a precedence from 0 to this, like in Haskell.
+pub const MAX_PRECEDENCE: u8 = 9;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+impl Associativity {
+    /// The keyword of an operator definition, `infixl` or `infixr`.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "infixl" => Some(Associativity::Left),
+            "infixr" => Some(Associativity::Right),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Associativity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Associativity::Left => write!(f, "infixl"),
+            Associativity::Right => write!(f, "infixr"),
+        }
+    }
+}
+
+/// An operator introduced by a statement like `infixl 6 a <+> b = (a + b) / 2`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OperatorDefinition {
+    pub symbol: String,
+    pub precedence: u8,
+    pub associativity: Associativity,
+    /// the names of the left and the right operand in `body`
+    pub parameters: [String; 2],
+    pub body: LocalizedSyntaxNode,
+}
+
+impl Display for OperatorDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [left, right] = &self.parameters;
+        write!(
+            f,
+            "{} {} {left} {} {right} = {}",
+            self.associativity, self.precedence, self.symbol, self.body
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct InfixOperator {
+    /// operators with a higher precedence bind tighter
+    pub precedence: u8,
+    pub associativity: Associativity,
+    action: Action,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    BuiltIn(fn(Localization, LocalizedSyntaxNode, LocalizedSyntaxNode) -> LocalizedSyntaxNode),
+    Defined(Arc<OperatorDefinition>),
+}
+
+impl InfixOperator {
+    pub fn apply(
+        &self,
+        location: Localization,
+        left: LocalizedSyntaxNode,
+        right: LocalizedSyntaxNode,
+    ) -> LocalizedSyntaxNode {
+        match &self.action {
+            Action::BuiltIn(build) => build(location, left, right),
+            Action::Defined(definition) => LocalizedSyntaxNode::new(
+                location,
+                SyntaxTree::DefinedOperation(definition.clone(), Box::new(left), Box::new(right)),
+            ),
+        }
+    }
+}
+
+/// The binary operators by their symbol. Negations and factorials bind tighter than all of them,
+/// so `-2 ^ 2` is `(-2) ^ 2`.
+///
+/// Documents extend the table with operator definitions, which apply to the statements after them.
+#[derive(Debug, Clone)]
+pub struct OperatorTable {
+    operators: HashMap<String, InfixOperator>,
+}
+
+type BuiltInOperator = (
+    &'static str,
+    u8,
+    Associativity,
+    fn(Localization, LocalizedSyntaxNode, LocalizedSyntaxNode) -> LocalizedSyntaxNode,
+);
+
+const BUILT_IN_OPERATORS: [BuiltInOperator; 7] = [
+    ("+", 6, Associativity::Left, LocalizedSyntaxNode::add),
+    ("-", 6, Associativity::Left, LocalizedSyntaxNode::sub),
+    ("*", 7, Associativity::Left, LocalizedSyntaxNode::mul),
+    ("/", 7, Associativity::Left, LocalizedSyntaxNode::div),
+    ("%", 7, Associativity::Left, LocalizedSyntaxNode::modulo),
+    ("mod", 7, Associativity::Left, LocalizedSyntaxNode::modulo),
+    ("^", 8, Associativity::Right, LocalizedSyntaxNode::exp),
+];
+
+impl Default for OperatorTable {
+    fn default() -> Self {
+        let operators = BUILT_IN_OPERATORS
+            .iter()
+            .map(|&(symbol, precedence, associativity, build)| {
+                let operator = InfixOperator {
+                    precedence,
+                    associativity,
+                    action: Action::BuiltIn(build),
+                };
+                (symbol.to_string(), operator)
+            })
+            .collect();
+        OperatorTable { operators }
+    }
+}
+
+impl OperatorTable {
+    /// Contains only the built-in operators.
+    pub fn new() -> Self {
+        OperatorTable::default()
+    }
+
+    pub fn is_built_in(symbol: &str) -> bool {
+        BUILT_IN_OPERATORS
+            .iter()
+            .any(|(built_in, ..)| *built_in == symbol)
+    }
+
+    /// Adds a user defined operator, or replaces an earlier definition of its symbol.
+    ///
+    /// # Panics
+    /// If the symbol is a built-in operator, the parser rejects such definitions.
+    pub fn define(&mut self, definition: Arc<OperatorDefinition>) {
+        assert!(
+            !OperatorTable::is_built_in(&definition.symbol),
+            "{} is a built-in operator",
+            definition.symbol
+        );
+        let operator = InfixOperator {
+            precedence: definition.precedence,
+            associativity: definition.associativity,
+            action: Action::Defined(definition.clone()),
+        };
+        self.operators.insert(definition.symbol.clone(), operator);
+    }
+
+    pub(super) fn get(&self, symbol: &str) -> Option<&InfixOperator> {
+        self.operators.get(symbol)
+    }
+}
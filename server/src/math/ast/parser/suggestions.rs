@@ -0,0 +1,118 @@
+use crate::math::ast::parser::error::{ErrorMessage, Suggestion};
+use crate::math::ast::parser::error_kind::ErrorKind;
+use crate::math::ast::parser::lexer::{Token, TokenKind};
+use crate::math::ast::Span;
+
+/// Names that the parser knows, misspelled identifiers are compared against them.
+const KNOWN_NAMES: [&str; 1] = ["mod"];
+
+const BINARY_OPERATORS: [TokenKind; 7] = [
+    TokenKind::Plus,
+    TokenKind::Minus,
+    TokenKind::Asterisk,
+    TokenKind::Slash,
+    TokenKind::Percent,
+    TokenKind::Caret,
+    TokenKind::Operator,
+];
+
+/// Looks for common mistakes in a statement that failed to parse and attaches fixes for them.
+/// `tokens` are the significant tokens of the statement.
+pub(super) fn add_suggestions(mut error: ErrorMessage, tokens: &[Token]) -> ErrorMessage {
+    for pair in tokens.windows(2) {
+        let (first, second) = (&pair[0], &pair[1]);
+        if (first.kind, second.kind) == (TokenKind::Asterisk, TokenKind::Asterisk)
+            && second.span.start_offset == first.span.end_offset
+        {
+            let span = Span::covering(first.span, second.span);
+            error = error.with_suggestion(Suggestion::replace("**", span, "^"));
+        }
+    }
+
+    if error.kind == ErrorKind::ExpectedOperand {
+        if let Some(operator) = tokens.first() {
+            if operator.kind != TokenKind::Minus && BINARY_OPERATORS.contains(&operator.kind) {
+                error = error.with_suggestion(Suggestion::remove(operator.text, operator.span));
+            }
+        }
+        if let Some(operator) = tokens.last().filter(|_| tokens.len() > 1) {
+            if BINARY_OPERATORS.contains(&operator.kind) {
+                error = error.with_suggestion(Suggestion::remove(operator.text, operator.span));
+            }
+        }
+    }
+
+    if error.kind == ErrorKind::UnexpectedInput {
+        if let Some(suggestion) = misspelled_name(tokens, error.span.start_offset) {
+            error = error.with_suggestion(suggestion);
+        }
+    }
+
+    error
+}
+
+/// Suggests the nearest known name for the identifier starting at `offset`.
+fn misspelled_name(tokens: &[Token], offset: usize) -> Option<Suggestion> {
+    let identifier = tokens
+        .iter()
+        .find(|token| token.kind == TokenKind::Identifier && token.span.start_offset == offset)?;
+    if KNOWN_NAMES.contains(&identifier.text) {
+        return None;
+    }
+
+    let nearest = KNOWN_NAMES
+        .iter()
+        .map(|name| (edit_distance(identifier.text, name), name))
+        .filter(|(distance, _)| *distance <= 1)
+        .min()?
+        .1;
+    Some(Suggestion::replace(
+        identifier.text,
+        identifier.span,
+        nearest,
+    ))
+}
+
+/// The optimal string alignment distance: the number of insertions, deletions, substitutions
+/// and transpositions of adjacent characters that turn `from` into `to`.
+fn edit_distance(from: &str, to: &str) -> usize {
+    let from: Vec<char> = from.chars().collect();
+    let to: Vec<char> = to.chars().collect();
+
+    let mut distances = vec![vec![0; to.len() + 1]; from.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=from.len() {
+        for j in 1..=to.len() {
+            let substitution_cost = usize::from(from[i - 1] != to[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + substitution_cost);
+            if i > 1 && j > 1 && from[i - 1] == to[j - 2] && from[i - 2] == to[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[from.len()][to.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::edit_distance;
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(0, edit_distance("mod", "mod"));
+        assert_eq!(1, edit_distance("mdo", "mod"));
+        assert_eq!(1, edit_distance("md", "mod"));
+        assert_eq!(1, edit_distance("mode", "mod"));
+        assert_eq!(1, edit_distance("mud", "mod"));
+        assert_eq!(3, edit_distance("x", "mod"));
+    }
+}
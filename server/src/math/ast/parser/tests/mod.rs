@@ -17,6 +17,18 @@ impl From<usize> for Localization {
     }
 }
 
+/// Most tests only check the locations of the operators, the spans are tested separately.
+fn without_spans(
+    result: Result<Vec<LocalizedSyntaxNode>, ErrorMessage>,
+) -> Result<Vec<LocalizedSyntaxNode>, ErrorMessage> {
+    result.map(|nodes| {
+        nodes
+            .into_iter()
+            .map(LocalizedSyntaxNode::without_spans)
+            .collect()
+    })
+}
+
 #[derive(Debug, Clone)]
 struct SyntaxTreeMatcher {
     localization_matcher: Option<Localization>,
@@ -194,7 +206,7 @@ mod add {
 
         let message = result.expect_err("12.34.56 is not a number");
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got '+ 456'".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '+ 456'".to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 1), message.localization);
@@ -206,7 +218,8 @@ mod add {
 
         let message = result.unwrap_err();
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got ''".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''"
+                .to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 6), message.localization);
@@ -215,6 +228,7 @@ mod add {
 
 mod mul {
     use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
     use crate::math::ast::{Localization, LocalizedSyntaxNode};
 
     #[test]
@@ -227,7 +241,7 @@ mod mul {
             LocalizedSyntaxNode::number(Localization::at(0, 5), 456u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -244,7 +258,7 @@ mod mul {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -253,7 +267,7 @@ mod mul {
 
         let message = result.expect_err("12.34.56 is not a number");
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got '* 456'".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '* 456'".to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 1), message.localization);
@@ -265,7 +279,8 @@ mod mul {
 
         let message = result.unwrap_err();
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got ''".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''"
+                .to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 6), message.localization);
@@ -274,6 +289,7 @@ mod mul {
 
 mod div {
     use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
     use crate::math::ast::{Localization, LocalizedSyntaxNode};
 
     #[test]
@@ -286,7 +302,7 @@ mod div {
             LocalizedSyntaxNode::number(Localization::at(0, 5), 456u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -303,7 +319,7 @@ mod div {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -312,7 +328,7 @@ mod div {
 
         let message = result.expect_err("12.34.56 is not a number");
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got '/ 456'".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '/ 456'".to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 1), message.localization);
@@ -324,7 +340,8 @@ mod div {
 
         let message = result.unwrap_err();
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got ''".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''"
+                .to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 6), message.localization);
@@ -333,6 +350,7 @@ mod div {
 
 mod exp {
     use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
     use crate::math::ast::{Localization, LocalizedSyntaxNode};
 
     #[test]
@@ -345,7 +363,7 @@ mod exp {
             LocalizedSyntaxNode::number(Localization::at(0, 5), 456u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -362,7 +380,7 @@ mod exp {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -371,7 +389,7 @@ mod exp {
 
         let message = result.expect_err("12.34.56 is not a number");
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got '^ 456'".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '^ 456'".to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 1), message.localization);
@@ -380,6 +398,7 @@ mod exp {
 
 mod subtract {
     use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
     use crate::math::ast::{Localization, LocalizedSyntaxNode};
 
     #[test]
@@ -392,7 +411,7 @@ mod subtract {
             LocalizedSyntaxNode::number(Localization::at(0, 5), 456u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -409,7 +428,7 @@ mod subtract {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -418,7 +437,8 @@ mod subtract {
 
         let message = result.expect_err("12.34.56 is not a number");
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got ''".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''"
+                .to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 6), message.localization);
@@ -430,7 +450,8 @@ mod subtract {
 
         let message = result.unwrap_err();
         assert_eq!(
-            "Syntax Error: expected '-' or '(' or a number, got ''".to_string(),
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''"
+                .to_string(),
             message.message
         );
         assert_eq!(Localization::at(0, 6), message.localization);
@@ -440,6 +461,7 @@ mod subtract {
 mod combined_operations {
     use crate::math::ast::parser::parse;
     use crate::math::ast::parser::tests::number;
+    use crate::math::ast::parser::tests::without_spans;
     use crate::math::ast::{Localization, LocalizedSyntaxNode};
 
     #[test]
@@ -476,7 +498,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -493,7 +515,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -510,7 +532,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -527,7 +549,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -544,7 +566,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -561,7 +583,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -578,7 +600,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -595,7 +617,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -612,7 +634,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -629,7 +651,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -646,7 +668,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -663,7 +685,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -680,7 +702,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -697,7 +719,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -714,7 +736,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -731,7 +753,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -748,7 +770,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -765,7 +787,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -782,7 +804,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 11), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -799,7 +821,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -816,7 +838,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -833,7 +855,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -850,7 +872,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -867,7 +889,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -884,7 +906,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -901,7 +923,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -918,7 +940,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -935,7 +957,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -952,7 +974,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -969,7 +991,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -986,7 +1008,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1003,7 +1025,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1020,7 +1042,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1037,7 +1059,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1054,7 +1076,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1071,7 +1093,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1088,7 +1110,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1105,7 +1127,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1122,7 +1144,7 @@ mod combined_operations {
             LocalizedSyntaxNode::number(Localization::at(0, 13), 789u16),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1139,7 +1161,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1156,7 +1178,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1173,7 +1195,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1190,7 +1212,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1207,7 +1229,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1224,7 +1246,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1241,7 +1263,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1258,7 +1280,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1275,7 +1297,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1292,7 +1314,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1309,7 +1331,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1326,7 +1348,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1343,7 +1365,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1360,7 +1382,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1377,7 +1399,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1394,7 +1416,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1411,7 +1433,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1428,7 +1450,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1445,7 +1467,7 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
     }
 
     #[test]
@@ -1462,6 +1484,882 @@ mod combined_operations {
             ),
         )];
 
-        assert_eq!(Ok(expected), result);
+        assert_eq!(Ok(expected), without_spans(result));
+    }
+}
+
+mod factorial {
+    use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
+    use crate::math::ast::{Localization, LocalizedSyntaxNode};
+
+    #[test]
+    fn factorial_of_integer() {
+        let result = parse("5!".to_string());
+
+        let expected = vec![LocalizedSyntaxNode::factorial(
+            Localization::at(0, 1),
+            LocalizedSyntaxNode::number(Localization::at(0, 0), 5u16),
+        )];
+
+        assert_eq!(Ok(expected), without_spans(result));
+    }
+
+    #[test]
+    fn double_factorial_of_integer() {
+        let result = parse("5!!".to_string());
+
+        let expected = vec![LocalizedSyntaxNode::double_factorial(
+            Localization::at(0, 1),
+            LocalizedSyntaxNode::number(Localization::at(0, 0), 5u16),
+        )];
+
+        assert_eq!(Ok(expected), without_spans(result));
+    }
+
+    #[test]
+    fn triple_exclamation_mark_is_factorial_of_double_factorial() {
+        let result = parse("5!!!".to_string()).unwrap();
+
+        assert_eq!("5!!!", format!("{}", result[0]));
+        assert_eq!(Localization::at(0, 3), result[0].location);
+    }
+
+    #[test]
+    fn factorial_binds_tighter_than_operators() {
+        let result = parse("2 ^ 3! * -4!".to_string()).unwrap();
+
+        assert_eq!("((2 ^ 3!) * -4!)", format!("{}", result[0]));
+    }
+
+    #[test]
+    fn factorial_of_expression_in_brackets() {
+        let result = parse("(-3)!".to_string()).unwrap();
+
+        assert_eq!("(-3)!", format!("{}", result[0]));
+        assert_eq!(Localization::at(0, 4), result[0].location);
+    }
+}
+
+mod absolute_value {
+    use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
+    use crate::math::ast::{Localization, LocalizedSyntaxNode};
+
+    #[test]
+    fn absolute_value_of_integer() {
+        let result = parse("|5|".to_string());
+
+        let expected = vec![LocalizedSyntaxNode::abs(
+            Localization::at(0, 0),
+            LocalizedSyntaxNode::number(Localization::at(0, 0), 5u16),
+        )];
+
+        assert_eq!(Ok(expected), without_spans(result));
+    }
+
+    #[test]
+    fn nested_absolute_values() {
+        let result = parse("||-1| - 3| * |2|".to_string()).unwrap();
+
+        assert_eq!("(|(|-1| - 3)| * |2|)", format!("{}", result[0]));
+    }
+
+    #[test]
+    fn missing_closing_bar() {
+        let result = parse("|1 + 2".to_string());
+
+        let message = result.unwrap_err();
+        assert_eq!(
+            "Syntax Error: missing closing '|' of absolute value".to_string(),
+            message.message
+        );
+        assert_eq!(Localization::at(0, 6), message.localization);
+    }
+}
+
+mod modulo {
+    use crate::math::ast::parser::parse;
+    use crate::math::ast::parser::tests::without_spans;
+    use crate::math::ast::{Localization, LocalizedSyntaxNode};
+
+    #[test]
+    fn percent_sign() {
+        let result = parse("123 % 456".to_string());
+
+        let expected = vec![LocalizedSyntaxNode::modulo(
+            Localization::at(0, 4),
+            LocalizedSyntaxNode::number(Localization::at(0, 0), 123u16),
+            LocalizedSyntaxNode::number(Localization::at(0, 5), 456u16),
+        )];
+
+        assert_eq!(Ok(expected), without_spans(result));
+    }
+
+    #[test]
+    fn mod_keyword() {
+        let result = parse("123 mod 456".to_string()).unwrap();
+
+        assert_eq!("(123 mod 456)", format!("{}", result[0]));
+    }
+
+    #[test]
+    fn same_precedence_as_multiplication() {
+        let result = parse("1 + 2 * 3 % 4 / 5".to_string()).unwrap();
+
+        assert_eq!("(1 + (((2 * 3) mod 4) / 5))", format!("{}", result[0]));
+    }
+}
+
+mod comments {
+    use crate::math::ast::parser::tests::without_spans;
+    use crate::math::ast::parser::{parse, parse_comments};
+    use crate::math::ast::{Comment, Localization, LocalizedSyntaxNode, Span};
+
+    #[test]
+    fn line_comments_are_ignored() {
+        let result = parse("# first line\n123 + 456 // the sum\n".to_string()).unwrap();
+
+        assert_eq!(1, result.len());
+        assert_eq!("(123 + 456)", format!("{}", result[0]));
+    }
+
+    #[test]
+    fn block_comment_between_operands() {
+        let result = parse("123 /* one\ntwo */ * 456".to_string()).unwrap();
+
+        assert_eq!("(123 * 456)", format!("{}", result[0]));
+    }
+
+    #[test]
+    fn comment_after_last_semicolon() {
+        let result = parse("123; 456; # done".to_string()).unwrap();
+
+        assert_eq!(2, result.len());
+    }
+
+    #[test]
+    fn slash_without_second_slash_is_still_division() {
+        let result = parse("123 / 456".to_string());
+
+        let expected = vec![LocalizedSyntaxNode::div(
+            Localization::at(0, 4),
+            LocalizedSyntaxNode::number(Localization::at(0, 0), 123u16),
+            LocalizedSyntaxNode::number(Localization::at(0, 5), 456u16),
+        )];
+
+        assert_eq!(Ok(expected), without_spans(result));
+    }
+
+    #[test]
+    fn only_comments_are_no_expression() {
+        let result = parse("# nothing to see here".to_string());
+
+        let message = result.unwrap_err();
+        assert_eq!(
+            "Syntax Error: expected expression, got ''".to_string(),
+            message.message
+        );
+    }
+
+    #[test]
+    fn collect_comments_of_document() {
+        let result = parse_comments("# intro\n1; 2 // two\n/* unterminated");
+
+        let span = |start: (usize, usize), end: (usize, usize), offsets: (usize, usize)| Span {
+            start: Localization::at(start.0, start.1),
+            end: Localization::at(end.0, end.1),
+            start_offset: offsets.0,
+            end_offset: offsets.1,
+        };
+        let expected = vec![
+            Comment::new(span((0, 0), (0, 7), (0, 7)), "# intro".to_string()),
+            Comment::new(span((1, 5), (1, 11), (13, 19)), "// two".to_string()),
+            Comment::new(
+                span((2, 0), (2, 15), (20, 35)),
+                "/* unterminated".to_string(),
+            ),
+        ];
+        assert_eq!(expected, result);
+    }
+}
+
+mod error_recovery {
+    use crate::math::ast::parser::{parse_document, parse_statements};
+    use crate::math::ast::{Localization, LocalizedSyntaxNode, Span};
+
+    #[test]
+    fn valid_statements() {
+        let result = parse_document("123; 456;".to_string());
+
+        let expected = vec![
+            Ok(LocalizedSyntaxNode::number(Localization::at(0, 0), 123u16)),
+            Ok(LocalizedSyntaxNode::number(Localization::at(0, 4), 456u16)),
+        ];
+        let result: Vec<_> = result
+            .into_iter()
+            .map(|statement| statement.map(LocalizedSyntaxNode::without_spans))
+            .collect();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn error_in_first_statement_does_not_hide_later_statements() {
+        let result = parse_document("123 +; 456".to_string());
+
+        assert_eq!(2, result.len());
+        let message = result[0].clone().unwrap_err();
+        assert_eq!(
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got ''"
+                .to_string(),
+            message.message
+        );
+        assert_eq!(
+            Ok(LocalizedSyntaxNode::number(Localization::at(0, 6), 456u16)),
+            result[1].clone().map(LocalizedSyntaxNode::without_spans)
+        );
+    }
+
+    #[test]
+    fn reports_every_broken_statement() {
+        let result = parse_document("1 2;\n3 * 4;\n* 5;\n6".to_string());
+
+        let messages: Vec<_> = result
+            .iter()
+            .map(|statement| statement.as_ref().map_err(|error| error.message.clone()))
+            .map(|statement| statement.map(|node| format!("{node}")))
+            .collect();
+        assert_eq!(
+            vec![
+                Err("expected end of input, '2' was left".to_string()),
+                Ok("(3 * 4)".to_string()),
+                Err("Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '* 5'".to_string()),
+                Ok("6".to_string()),
+            ],
+            messages
+        );
+        assert_eq!(
+            Localization::at(0, 2),
+            result[0].clone().unwrap_err().localization
+        );
+    }
+
+    #[test]
+    fn statements_keep_their_spans() {
+        let result = parse_statements("1 +;\n  2 * 3 # three\n;".to_string());
+
+        let spans: Vec<_> = result.iter().map(|statement| statement.span).collect();
+        assert_eq!(
+            vec![
+                Span {
+                    start: Localization::at(0, 0),
+                    end: Localization::at(0, 3),
+                    start_offset: 0,
+                    end_offset: 3,
+                },
+                Span {
+                    start: Localization::at(1, 2),
+                    end: Localization::at(1, 7),
+                    start_offset: 7,
+                    end_offset: 12,
+                },
+            ],
+            spans
+        );
+        assert!(result[0].result.is_err());
+        assert!(result[1].result.is_ok());
+    }
+
+    #[test]
+    fn semicolons_in_comments_do_not_separate_statements() {
+        let result = parse_document("1 /* ; */ + 2 # ;\n".to_string());
+
+        assert_eq!(1, result.len());
+        assert_eq!("(1 + 2)", format!("{}", result[0].clone().unwrap()));
+    }
+
+    #[test]
+    fn empty_document_has_no_statements() {
+        assert_eq!(
+            Vec::<Result<_, _>>::new(),
+            parse_document(" // nothing\n".to_string())
+        );
+    }
+}
+
+mod spans {
+    use std::ops::Range;
+
+    use crate::math::ast::parser::{parse, parse_document};
+    use crate::math::ast::{Localization, LocalizedSyntaxNode, Span, SyntaxTree};
+
+    fn span(start: (usize, usize), end: (usize, usize), offsets: Range<usize>) -> Span {
+        Span {
+            start: start.into(),
+            end: end.into(),
+            start_offset: offsets.start,
+            end_offset: offsets.end,
+        }
+    }
+
+    fn operands(node: &LocalizedSyntaxNode) -> (&LocalizedSyntaxNode, &LocalizedSyntaxNode) {
+        match &node.tree {
+            SyntaxTree::Sum(left, right) | SyntaxTree::Product(left, right) => (left, right),
+            tree => panic!("{tree:?} is no binary operation"),
+        }
+    }
+
+    fn operand(node: &LocalizedSyntaxNode) -> &LocalizedSyntaxNode {
+        match &node.tree {
+            SyntaxTree::Negation(value)
+            | SyntaxTree::Factorial(value)
+            | SyntaxTree::AbsoluteValue(value) => value,
+            tree => panic!("{tree:?} is no unary operation"),
+        }
+    }
+
+    #[test]
+    fn binary_operation_covers_both_operands() {
+        let result = parse("12 + 345".to_string()).unwrap();
+
+        let (left, right) = operands(&result[0]);
+        assert_eq!(span((0, 0), (0, 8), 0..8), result[0].span);
+        assert_eq!(span((0, 0), (0, 2), 0..2), left.span);
+        assert_eq!(span((0, 5), (0, 8), 5..8), right.span);
+    }
+
+    #[test]
+    fn brackets_are_part_of_the_span() {
+        let result = parse("(1 + 2) * 3".to_string()).unwrap();
+
+        let (left, _) = operands(&result[0]);
+        assert_eq!(span((0, 0), (0, 11), 0..11), result[0].span);
+        assert_eq!(span((0, 0), (0, 7), 0..7), left.span);
+    }
+
+    #[test]
+    fn unary_operations_cover_their_operators() {
+        let result = parse("-|x|!".to_string()).unwrap();
+
+        let factorial = operand(&result[0]);
+        let absolute_value = operand(factorial);
+        assert_eq!(span((0, 0), (0, 5), 0..5), result[0].span);
+        assert_eq!(span((0, 1), (0, 5), 1..5), factorial.span);
+        assert_eq!(span((0, 1), (0, 4), 1..4), absolute_value.span);
+    }
+
+    #[test]
+    fn byte_offsets_count_bytes_and_localizations_count_characters() {
+        let result = parse("1 +\n ä".to_string()).unwrap();
+
+        let (_, right) = operands(&result[0]);
+        assert_eq!(span((1, 1), (1, 2), 5..7), right.span);
+        assert_eq!(span((0, 0), (1, 2), 0..7), result[0].span);
+    }
+
+    #[test]
+    fn error_at_end_of_input() {
+        let message = parse("123 + ".to_string()).unwrap_err();
+
+        assert_eq!(span((0, 6), (0, 6), 6..6), message.span);
+    }
+
+    #[test]
+    fn missing_closing_parenthesis() {
+        let message = parse("(1 + 2".to_string()).unwrap_err();
+
+        assert_eq!(
+            "Syntax Error: missing closing parenthesis".to_string(),
+            message.message
+        );
+        assert_eq!(Localization::at(0, 6), message.localization);
+        assert_eq!(span((0, 6), (0, 6), 6..6), message.span);
+    }
+
+    #[test]
+    fn leftover_covers_the_rest_of_the_statement() {
+        let result = parse_document("1; 12.34.56 ;".to_string());
+
+        let message = result[1].clone().unwrap_err();
+        assert_eq!(span((0, 8), (0, 12), 8..12), message.span);
+    }
+}
+
+mod error_kinds {
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::parser::parse_document;
+
+    fn kinds_of(input: &str) -> Vec<Option<ErrorKind>> {
+        parse_document(input.to_string())
+            .into_iter()
+            .map(|statement| statement.err().map(|error| error.kind))
+            .collect()
+    }
+
+    #[test]
+    fn reports_the_kind_of_each_broken_statement() {
+        let result = kinds_of("(1 + 2; |3; 4 5; 6 +; 7");
+
+        assert_eq!(
+            vec![
+                Some(ErrorKind::MissingClosingParenthesis),
+                Some(ErrorKind::MissingClosingAbsoluteValueBar),
+                Some(ErrorKind::UnexpectedInput),
+                Some(ErrorKind::ExpectedOperand),
+                None,
+            ],
+            result
+        );
+    }
+}
+
+mod suggestions {
+    use crate::math::ast::parser::error::{ErrorMessage, Suggestion};
+    use crate::math::ast::parser::parse_document;
+    use crate::math::ast::{Localization, Span};
+
+    fn error_of(input: &str) -> ErrorMessage {
+        let mut statements = parse_document(input.to_string());
+        assert_eq!(1, statements.len());
+        statements.remove(0).unwrap_err()
+    }
+
+    fn span(start: usize, end: usize) -> Span {
+        Span {
+            start: Localization::at(0, start),
+            end: Localization::at(0, end),
+            start_offset: start,
+            end_offset: end,
+        }
+    }
+
+    #[test]
+    fn insert_missing_closing_parenthesis() {
+        let error = error_of("(1 + 2");
+
+        assert_eq!(
+            vec![Suggestion {
+                message: "insert ')'".to_string(),
+                span: span(6, 6),
+                replacement: ")".to_string(),
+            }],
+            error.suggestions()
+        );
+    }
+
+    #[test]
+    fn insert_missing_closing_absolute_value_bar() {
+        let error = error_of("|1 - 2 # comment");
+
+        assert_eq!(
+            vec![Suggestion {
+                message: "insert '|'".to_string(),
+                span: span(6, 6),
+                replacement: "|".to_string(),
+            }],
+            error.suggestions()
+        );
+    }
+
+    #[test]
+    fn replace_double_asterisk_by_caret() {
+        let error = error_of("2 ** 3");
+
+        assert!(error.suggestions().contains(&Suggestion {
+            message: "did you mean '^' instead of '**'?".to_string(),
+            span: span(2, 4),
+            replacement: "^".to_string(),
+        }));
+    }
+
+    #[test]
+    fn remove_trailing_operator() {
+        let error = error_of("1 + /* nothing */");
+
+        assert_eq!(
+            vec![Suggestion {
+                message: "remove '+'".to_string(),
+                span: span(2, 3),
+                replacement: "".to_string(),
+            }],
+            error.suggestions()
+        );
+    }
+
+    #[test]
+    fn remove_leading_operator() {
+        let error = error_of("* 5");
+
+        assert_eq!(
+            vec![Suggestion {
+                message: "remove '*'".to_string(),
+                span: span(0, 1),
+                replacement: "".to_string(),
+            }],
+            error.suggestions()
+        );
+    }
+
+    #[test]
+    fn suggest_nearest_known_name() {
+        let error = error_of("7 mdo 3");
+
+        assert_eq!(
+            vec![Suggestion {
+                message: "did you mean 'mod' instead of 'mdo'?".to_string(),
+                span: span(2, 5),
+                replacement: "mod".to_string(),
+            }],
+            error.suggestions()
+        );
+    }
+
+    #[test]
+    fn no_suggestion_for_unrelated_names() {
+        let error = error_of("7 x 3");
+
+        assert_eq!(Vec::<Suggestion>::new(), error.suggestions());
+    }
+}
+
+mod limits {
+    use crate::math::ast::parser::error::ErrorMessage;
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::parser::{
+        parse, parse_document, parse_statements, MAX_INPUT_LENGTH, MAX_NESTING_DEPTH,
+    };
+
+    fn error_of(input: String) -> ErrorMessage {
+        let mut statements = parse_document(input);
+        assert_eq!(1, statements.len());
+        statements.remove(0).unwrap_err()
+    }
+
+    fn nested_parentheses(depth: usize) -> String {
+        format!("{}1{}", "(".repeat(depth), ")".repeat(depth))
+    }
+
+    #[test]
+    fn nesting_up_to_the_limit_is_accepted() {
+        let result = parse(nested_parentheses(MAX_NESTING_DEPTH));
+
+        assert_eq!("1", format!("{}", result.unwrap()[0]));
+    }
+
+    #[test]
+    fn numbers_with_huge_exponents_are_rejected() {
+        for number in [
+            "1e99999999999999999999",
+            "1e-9223372036854775808",
+            "1e-100001",
+        ] {
+            let error = error_of(format!("2 * {number}"));
+
+            assert_eq!(ErrorKind::NumberOutOfRange, error.kind);
+            assert_eq!(
+                format!("Syntax Error: the number {number} is out of range"),
+                error.message
+            );
+            assert_eq!(4, error.span.start_offset);
+            assert_eq!(4 + number.len(), error.span.end_offset);
+        }
+    }
+
+    #[test]
+    fn exponents_up_to_the_limit_are_accepted() {
+        let result = parse("1e100000 + 1e-100000".to_string());
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn deeply_nested_parentheses_are_rejected() {
+        let error = error_of(nested_parentheses(10 * MAX_NESTING_DEPTH));
+
+        assert_eq!(ErrorKind::NestingTooDeep, error.kind);
+        assert_eq!(
+            "Syntax Error: expression is nested deeper than 100 levels".to_string(),
+            error.message
+        );
+        assert_eq!(100, error.span.start_offset);
+        assert_eq!(101, error.span.end_offset);
+    }
+
+    #[test]
+    fn deeply_nested_absolute_values_are_rejected() {
+        let depth = 2 * MAX_NESTING_DEPTH;
+        let input = format!("{}1{}", "|".repeat(depth), "|".repeat(depth));
+
+        assert_eq!(ErrorKind::NestingTooDeep, error_of(input).kind);
+    }
+
+    #[test]
+    fn long_chains_of_signs_are_rejected() {
+        let input = format!("{}1", "-".repeat(10 * MAX_NESTING_DEPTH));
+
+        assert_eq!(ErrorKind::NestingTooDeep, error_of(input).kind);
+    }
+
+    #[test]
+    fn towers_of_exponents_are_rejected() {
+        let input = format!("{}2", "2 ^ ".repeat(10 * MAX_NESTING_DEPTH));
+
+        assert_eq!(ErrorKind::NestingTooDeep, error_of(input).kind);
+    }
+
+    #[test]
+    fn long_chains_of_operators_are_rejected() {
+        let input = format!("{}1", "1 + 2 * ".repeat(MAX_NESTING_DEPTH));
+
+        assert_eq!(ErrorKind::NestingTooDeep, error_of(input).kind);
+    }
+
+    #[test]
+    fn long_chains_of_factorials_are_rejected() {
+        let input = format!("3{}", "!".repeat(2 * MAX_NESTING_DEPTH));
+
+        assert_eq!(ErrorKind::NestingTooDeep, error_of(input).kind);
+    }
+
+    #[test]
+    fn long_inputs_within_the_limit_are_parsed() {
+        let input = "(1 + 2) * 3; ".repeat(MAX_INPUT_LENGTH / 20);
+
+        let result = parse_document(input);
+
+        assert_eq!(MAX_INPUT_LENGTH / 20, result.len());
+        assert!(result.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn inputs_above_the_limit_are_rejected_as_a_whole() {
+        let input = "1;".repeat(MAX_INPUT_LENGTH);
+
+        let result = parse_statements(input);
+
+        assert_eq!(1, result.len());
+        let error = result[0].result.clone().unwrap_err();
+        assert_eq!(ErrorKind::InputTooLarge, error.kind);
+        assert_eq!(
+            "the document has 200000 bytes, at most 100000 are accepted".to_string(),
+            error.message
+        );
+        assert_eq!(200_000, error.span.end_offset);
+    }
+
+    #[test]
+    fn error_messages_show_the_beginning_of_the_remaining_input() {
+        let error = error_of(format!("* {}", "1234567890".repeat(1000)));
+
+        assert_eq!(
+            "Syntax Error: expected a number, a variable, ans, %n, out[n], '-', '(' or '|', got '* 123456789012345678...'"
+                .to_string(),
+            error.message
+        );
+    }
+}
+
+mod operator_definitions {
+    use crate::math::ast::parser::error::ErrorMessage;
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::parser::operators::OperatorTable;
+    use crate::math::ast::parser::{parse, parse_document, parse_statements_with};
+
+    fn displayed(input: &str) -> Vec<String> {
+        parse_document(input.to_string())
+            .into_iter()
+            .map(|statement| match statement {
+                Ok(node) => node.to_string(),
+                Err(error) => error.message,
+            })
+            .collect()
+    }
+
+    fn error_of(input: &str) -> ErrorMessage {
+        parse_document(input.to_string()).remove(0).unwrap_err()
+    }
+
+    #[test]
+    fn definitions_are_statements() {
+        let result = displayed("infixl 6 a <+> b = (a + b) / 2");
+
+        assert_eq!(vec!["infixl 6 a <+> b = ((a + b) / 2)"], result);
+    }
+
+    #[test]
+    fn defined_operators_bind_by_their_precedence() {
+        let result = displayed("infixl 5 a <+> b = a + b; 1 <+> 2 * 3 <+> 4; 1 + 2 <+> 3");
+
+        assert_eq!("((1 <+> (2 * 3)) <+> 4)", result[1]);
+        assert_eq!("((1 + 2) <+> 3)", result[2]);
+    }
+
+    #[test]
+    fn defined_operators_bind_tighter_than_built_in_ones_with_a_higher_precedence() {
+        let result = displayed("infixl 9 a <> b = a; 1 ^ 2 <> 3 * 4");
+
+        assert_eq!("((1 ^ (2 <> 3)) * 4)", result[1]);
+    }
+
+    #[test]
+    fn right_associative_operators_group_to_the_right() {
+        let result =
+            displayed("infixr 2 a ~> b = b; 1 ~> 2 ~> 3; infixl 2 a <~ b = a; 1 <~ 2 <~ 3");
+
+        assert_eq!("(1 ~> (2 ~> 3))", result[1]);
+        assert_eq!("((1 <~ 2) <~ 3)", result[3]);
+    }
+
+    #[test]
+    fn operators_can_be_named() {
+        let result = displayed("infixl 7 a dot b = a * b; x dot y");
+
+        assert_eq!("(x dot y)", result[1]);
+    }
+
+    #[test]
+    fn operators_apply_to_later_statements_only() {
+        let result = parse_document("1 <+> 2; infixl 6 a <+> b = a; 1 <+> 2".to_string());
+
+        assert_eq!(
+            ErrorKind::UnexpectedInput,
+            result[0].as_ref().unwrap_err().kind
+        );
+        assert!(result[2].is_ok());
+    }
+
+    #[test]
+    fn broken_definitions_define_nothing() {
+        let result = parse_document("infixl 6 a <+> b = a +; 1 <+> 2".to_string());
+
+        assert!(result[0].is_err());
+        assert_eq!(
+            ErrorKind::UnexpectedInput,
+            result[1].as_ref().unwrap_err().kind
+        );
+    }
+
+    #[test]
+    fn parse_applies_definitions_to_the_rest_of_the_document() {
+        let result = parse("infixl 6 a <+> b = a; 1 <+> 2".to_string()).unwrap();
+
+        assert_eq!("(1 <+> 2)", result[1].to_string());
+    }
+
+    #[test]
+    fn definitions_are_added_to_the_table() {
+        let mut operators = OperatorTable::new();
+        parse_statements_with("infixl 6 a <+> b = a".to_string(), &mut operators);
+
+        let result = parse_statements_with("1 <+> 2".to_string(), &mut operators);
+
+        assert_eq!("(1 <+> 2)", result[0].result.as_ref().unwrap().to_string());
+    }
+
+    #[test]
+    fn built_in_operators_can_not_be_redefined() {
+        let error = error_of("infixl 6 a + b = a - b");
+
+        assert_eq!(ErrorKind::InvalidOperatorDefinition, error.kind);
+        assert_eq!(
+            "Syntax Error: '+' is a built-in operator and can't be redefined",
+            error.message
+        );
+        assert_eq!((11, 12), (error.span.start_offset, error.span.end_offset));
+    }
+
+    #[test]
+    fn malformed_definitions_are_rejected() {
+        let messages: Vec<_> = [
+            "infixl 10 a <+> b = a",
+            "infixl 6 1 <+> b = a",
+            "infixl 6 a ( b = a",
+            "infixl 6 a <+> a = a",
+            "infixl 6 a <+> b a",
+            "infixr 6 a <+> b =",
+        ]
+        .into_iter()
+        .map(|input| error_of(input).message)
+        .collect();
+
+        assert_eq!(
+            vec![
+                "Syntax Error: expected a precedence from 0 to 9, got '10 a <+> b = a'",
+                "Syntax Error: expected the name of the left operand, got '1 <+> b = a'",
+                "Syntax Error: expected an operator symbol, got '( b = a'",
+                "Syntax Error: both operands are named 'a'",
+                "Syntax Error: expected '=' and the definition of the operator, got 'a'",
+                "Syntax Error: expected expression, got ''",
+            ],
+            messages
+        );
+    }
+}
+
+mod history_references {
+    use crate::math::ast::parser::error_kind::ErrorKind;
+    use crate::math::ast::parser::{parse_document, split_statements};
+    use crate::math::ast::HistoryReference;
+
+    fn displayed(input: &str) -> Vec<String> {
+        parse_document(input.to_string())
+            .into_iter()
+            .map(|statement| match statement {
+                Ok(node) => node.to_string(),
+                Err(error) => error.message,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn references_are_operands() {
+        let result = displayed("ans + %2 * out[3]; -%1!; 5 %3");
+
+        assert_eq!(vec!["(ans + (%2 * out[3]))", "-%1!", "(5 mod 3)"], result);
+    }
+
+    #[test]
+    fn references_cover_their_tokens() {
+        let result = parse_document("1 + out[ 12 ]".to_string());
+
+        let span = result[0].as_ref().unwrap().span();
+        assert_eq!((0, 13), (span.start_offset, span.end_offset));
+    }
+
+    #[test]
+    fn malformed_references_are_rejected() {
+        let input = "%x; %0; %1.5; out[2; infixl 6 a <+> b = a + ans";
+
+        let result = displayed(input);
+
+        assert_eq!(
+            vec![
+                "Syntax Error: expected the number of a statement, got 'x'",
+                "Syntax Error: expected the number of a statement, got '0'",
+                "Syntax Error: expected the number of a statement, got '1.5'",
+                "Syntax Error: expected ']', got ''",
+                "Syntax Error: 'ans' can't be used in the definition of an operator",
+            ],
+            result
+        );
+        let kinds: Vec<_> = parse_document(input.to_string())
+            .into_iter()
+            .map(|statement| statement.unwrap_err().kind)
+            .collect();
+        assert_eq!(vec![ErrorKind::InvalidHistoryReference; 5], kinds);
+    }
+
+    #[test]
+    fn sources_list_their_references() {
+        let sources = split_statements("ans * %2 - out[3] + out + %x").unwrap();
+
+        assert_eq!(
+            vec![
+                HistoryReference::Answer,
+                HistoryReference::Percent(2),
+                HistoryReference::Out(3),
+            ],
+            sources[0].history_references()
+        );
     }
 }
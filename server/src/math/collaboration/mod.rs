@@ -0,0 +1,229 @@
+//! Several users editing one document at the same time. The server puts their changes in order:
+//! a change made against an older revision is transformed past the changes applied since then,
+//! like the server of ot.js does, so that all participants end up with the same text.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use operation::Operation;
+
+use crate::math::application::{Application, Settings};
+use crate::math::ast::budget::CancellationToken;
+use crate::math::protocol::Evaluation;
+
+pub mod operation;
+
+/// Older changes are forgotten, a participant this far behind has to load the document again.
+const MAX_HISTORY_LENGTH: usize = 1000;
+
+pub struct SharedDocument {
+    text: String,
+    /// the number of changes applied to the text
+    revision: u64,
+    /// the latest changes, the last one led to `revision`
+    history: VecDeque<Operation>,
+    /// the character offsets of the participants' cursors by their id
+    cursors: HashMap<String, usize>,
+    application: Arc<Mutex<Application>>,
+    latest_evaluation: Option<CancellationToken>,
+}
+
+impl SharedDocument {
+    pub fn new(text: String) -> Self {
+        SharedDocument {
+            text,
+            revision: 0,
+            history: VecDeque::new(),
+            cursors: HashMap::new(),
+            application: Arc::new(Mutex::new(Application::create())),
+            latest_evaluation: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn cursors(&self) -> &HashMap<String, usize> {
+        &self.cursors
+    }
+
+    /// Adds a participant with the cursor at the start, or keeps the cursor of a participant.
+    pub fn join(&mut self, participant: &str) -> usize {
+        *self.cursors.entry(participant.to_string()).or_insert(0)
+    }
+
+    pub fn leave(&mut self, participant: &str) {
+        self.cursors.remove(participant);
+    }
+
+    /// Applies `operation`, made against the text of `revision`, and moves the cursors along.
+    /// Returns the operation as it was applied to the latest text, the other participants
+    /// apply it after their own changes that the server already knows.
+    pub fn apply(&mut self, revision: u64, operation: Operation) -> Result<Operation, String> {
+        let mut operation = operation;
+        for change in self.changes_since(revision)? {
+            operation = operation.transform(change)?.0;
+        }
+        self.text = operation.apply(&self.text)?;
+
+        for position in self.cursors.values_mut() {
+            *position = operation.transform_position(*position);
+        }
+        self.history.push_back(operation.clone());
+        if self.history.len() > MAX_HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.revision += 1;
+        Ok(operation)
+    }
+
+    /// Puts the cursor of `participant` at the character offset `position` of the text of
+    /// `revision`. Returns where it is in the latest text.
+    pub fn move_cursor(
+        &mut self,
+        participant: &str,
+        revision: u64,
+        position: usize,
+    ) -> Result<usize, String> {
+        let mut position = position;
+        for change in self.changes_since(revision)? {
+            position = change.transform_position(position);
+        }
+        let position = position.min(self.text.chars().count());
+        self.cursors.insert(participant.to_string(), position);
+        Ok(position)
+    }
+
+    /// The evaluation of the latest text, with the revision as id. The running evaluation of
+    /// an earlier revision is cancelled.
+    pub fn evaluation(&mut self) -> Evaluation {
+        self.close();
+        let cancellation = CancellationToken::new();
+        self.latest_evaluation = Some(cancellation.clone());
+        Evaluation::new(
+            self.revision,
+            self.text.clone(),
+            Settings::default(),
+            cancellation,
+            self.application.clone(),
+        )
+    }
+
+    /// Stops the running evaluation.
+    pub fn close(&mut self) {
+        if let Some(cancellation) = self.latest_evaluation.take() {
+            cancellation.cancel();
+        }
+    }
+
+    fn changes_since(&self, revision: u64) -> Result<impl Iterator<Item = &Operation>, String> {
+        if revision > self.revision {
+            return Err(format!(
+                "revision {revision} doesn't exist, the latest is {}",
+                self.revision
+            ));
+        }
+        let missed = (self.revision - revision) as usize;
+        if missed > self.history.len() {
+            return Err(format!(
+                "revision {revision} is too old, the document has to be loaded again"
+            ));
+        }
+        Ok(self.history.range(self.history.len() - missed..))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::math::ast::budget::Limits;
+    use crate::math::collaboration::operation::Operation;
+    use crate::math::collaboration::SharedDocument;
+    use crate::math::protocol::ServerMessage;
+
+    fn operation(components: serde_json::Value) -> Operation {
+        serde_json::from_value(components).unwrap()
+    }
+
+    #[test]
+    fn concurrent_changes_are_merged() {
+        let mut document = SharedDocument::new("1 + 2".to_string());
+
+        document.apply(0, operation(json!([4, -1, "3"]))).unwrap();
+        let applied = document
+            .apply(0, operation(json!(["(", 5, ") * 2"])))
+            .unwrap();
+
+        assert_eq!("(1 + 3) * 2", document.text());
+        assert_eq!(2, document.revision());
+        assert_eq!(operation(json!(["(", 5, ") * 2"])), applied);
+    }
+
+    #[test]
+    fn unknown_revisions_are_rejected() {
+        let mut document = SharedDocument::new("x".to_string());
+
+        let result = document.apply(1, operation(json!([1, "!"])));
+
+        assert_eq!(
+            Err("revision 1 doesn't exist, the latest is 0".to_string()),
+            result
+        );
+        assert_eq!("x", document.text());
+    }
+
+    #[test]
+    fn forgotten_revisions_are_rejected() {
+        let mut document = SharedDocument::new(String::new());
+        for revision in 0..=super::MAX_HISTORY_LENGTH as u64 {
+            let length = revision as usize;
+            document
+                .apply(revision, operation(json!([length, "1"])))
+                .unwrap();
+        }
+
+        let result = document.apply(0, operation(json!(["2"])));
+
+        assert_eq!(
+            Err("revision 0 is too old, the document has to be loaded again".to_string()),
+            result
+        );
+    }
+
+    #[test]
+    fn cursors_follow_the_changes() {
+        let mut document = SharedDocument::new("1 + 2".to_string());
+        document.join("alice");
+        document.move_cursor("alice", 0, 4).unwrap();
+
+        document.apply(0, operation(json!(["10", -1, 4]))).unwrap();
+        let bob = document.move_cursor("bob", 0, 5).unwrap();
+
+        assert_eq!(Some(&5), document.cursors().get("alice"));
+        assert_eq!(6, bob);
+    }
+
+    #[test]
+    fn the_latest_text_is_evaluated() {
+        let mut document = SharedDocument::new("1 + 2".to_string());
+        let outdated = document.evaluation();
+        document.apply(0, operation(json!([4, -1, "3"]))).unwrap();
+
+        let messages = document.evaluation().run(Limits::default());
+
+        assert!(outdated.run(Limits::default()).is_empty());
+        match &messages[0] {
+            ServerMessage::Result { id, statements } => {
+                assert_eq!(1, *id);
+                assert_eq!("(1 + 3) = 4", statements[0].text);
+            }
+            message => panic!("expected a result, got {message:?}"),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::ast::parser::MAX_INPUT_LENGTH;
+
+/// A change of a whole text, in the JSON format of ot.js: a positive number keeps that many
+/// characters, a negative number deletes that many and a string is inserted, e.g.
+/// `[4, -1, "20", 3]` replaces the fifth of eight characters by `20`.
+///
+/// Lengths count characters, like the columns of positions. Operations received as JSON apply to
+/// and result in at most `MAX_INPUT_LENGTH` characters, and [Operation::apply] rejects results
+/// of more bytes than the parser accepts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+#[serde(try_from = "Vec<RawComponent>", into = "Vec<RawComponent>")]
+pub struct Operation {
+    /// adjacent components of the same kind are merged, an insert comes before a delete
+    components: Vec<Component>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Component {
+    Retain(usize),
+    Insert(String),
+    Delete(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawComponent {
+    Count(i64),
+    Insert(String),
+}
+
+impl Operation {
+    pub fn new() -> Self {
+        Operation::default()
+    }
+
+    pub fn retain(&mut self, length: usize) -> &mut Self {
+        if length == 0 {
+            return self;
+        }
+        match self.components.last_mut() {
+            Some(Component::Retain(retained)) => *retained += length,
+            _ => self.components.push(Component::Retain(length)),
+        }
+        self
+    }
+
+    pub fn insert(&mut self, text: &str) -> &mut Self {
+        if text.is_empty() {
+            return self;
+        }
+        match self.components.as_mut_slice() {
+            [.., Component::Insert(inserted)]
+            | [.., Component::Insert(inserted), Component::Delete(_)] => inserted.push_str(text),
+            // deleting and inserting commute, so they are kept in one order
+            [.., Component::Delete(_)] => {
+                let delete = self.components.pop().expect("there is a last component");
+                self.components.push(Component::Insert(text.to_string()));
+                self.components.push(delete);
+            }
+            _ => self.components.push(Component::Insert(text.to_string())),
+        }
+        self
+    }
+
+    pub fn delete(&mut self, length: usize) -> &mut Self {
+        if length == 0 {
+            return self;
+        }
+        match self.components.last_mut() {
+            Some(Component::Delete(deleted)) => *deleted += length,
+            _ => self.components.push(Component::Delete(length)),
+        }
+        self
+    }
+
+    /// The length of the texts the operation applies to.
+    pub fn base_length(&self) -> usize {
+        self.components
+            .iter()
+            .map(|component| match component {
+                Component::Retain(length) | Component::Delete(length) => *length,
+                Component::Insert(_) => 0,
+            })
+            .sum()
+    }
+
+    pub fn apply(&self, text: &str) -> Result<String, String> {
+        let length = text.chars().count();
+        if length != self.base_length() {
+            return Err(format!(
+                "the operation applies to texts of {} characters, the document has {length}",
+                self.base_length()
+            ));
+        }
+
+        let mut characters = text.chars();
+        let mut result = String::with_capacity(text.len());
+        for component in &self.components {
+            match component {
+                Component::Retain(length) => result.extend(characters.by_ref().take(*length)),
+                Component::Insert(text) => result.push_str(text),
+                Component::Delete(length) => {
+                    characters.by_ref().take(*length).for_each(drop);
+                }
+            }
+        }
+        // the parser's limit counts bytes, multi-byte characters make a text longer than its length
+        if result.len() > MAX_INPUT_LENGTH {
+            return Err(format!(
+                "the operation makes the document {} bytes long, at most {MAX_INPUT_LENGTH} are accepted",
+                result.len()
+            ));
+        }
+        Ok(result)
+    }
+
+    /// For two operations made against the same text, returns `(a', b')` such that applying
+    /// `self` and then `b'` has the same result as applying `other` and then `a'`.
+    /// Where both insert at the same position, the text of `self` comes first.
+    pub fn transform(&self, other: &Operation) -> Result<(Operation, Operation), String> {
+        if self.base_length() != other.base_length() {
+            return Err(format!(
+                "the operations apply to texts of {} and {} characters",
+                self.base_length(),
+                other.base_length()
+            ));
+        }
+
+        let (mut transformed_self, mut transformed_other) = (Operation::new(), Operation::new());
+        let mut own = self.components.iter().cloned();
+        let mut others = other.components.iter().cloned();
+        let (mut a, mut b) = (own.next(), others.next());
+        loop {
+            match (a.take(), b.take()) {
+                (None, None) => break,
+                (Some(Component::Insert(text)), next_b) => {
+                    transformed_self.insert(&text);
+                    transformed_other.retain(text.chars().count());
+                    (a, b) = (own.next(), next_b);
+                }
+                (next_a, Some(Component::Insert(text))) => {
+                    transformed_self.retain(text.chars().count());
+                    transformed_other.insert(&text);
+                    (a, b) = (next_a, others.next());
+                }
+                (Some(first), Some(second)) => {
+                    let length = component_length(&first).min(component_length(&second));
+                    match (&first, &second) {
+                        (Component::Retain(_), Component::Retain(_)) => {
+                            transformed_self.retain(length);
+                            transformed_other.retain(length);
+                        }
+                        (Component::Delete(_), Component::Retain(_)) => {
+                            transformed_self.delete(length);
+                        }
+                        (Component::Retain(_), Component::Delete(_)) => {
+                            transformed_other.delete(length);
+                        }
+                        // both deleted the same characters
+                        _ => {}
+                    }
+                    a = shortened(first, length).or_else(|| own.next());
+                    b = shortened(second, length).or_else(|| others.next());
+                }
+                _ => unreachable!("both operations have the same base length"),
+            }
+        }
+        Ok((transformed_self, transformed_other))
+    }
+
+    /// Where the character offset `position` of the text before the operation ends up.
+    /// Text inserted at `position` moves it to the end of the insertion.
+    pub fn transform_position(&self, position: usize) -> usize {
+        let mut remaining = position;
+        let mut transformed = position;
+        for component in &self.components {
+            match component {
+                Component::Retain(length) if *length > remaining => break,
+                Component::Retain(length) => remaining -= length,
+                Component::Insert(text) => transformed += text.chars().count(),
+                Component::Delete(length) => {
+                    let deleted = (*length).min(remaining);
+                    transformed -= deleted;
+                    remaining -= deleted;
+                    if remaining == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        transformed
+    }
+}
+
+fn component_length(component: &Component) -> usize {
+    match component {
+        Component::Retain(length) | Component::Delete(length) => *length,
+        Component::Insert(text) => text.chars().count(),
+    }
+}
+
+/// The rest of a retain or delete after `length` characters, `None` if nothing is left.
+fn shortened(component: Component, length: usize) -> Option<Component> {
+    match component {
+        Component::Retain(total) if total > length => Some(Component::Retain(total - length)),
+        Component::Delete(total) if total > length => Some(Component::Delete(total - length)),
+        _ => None,
+    }
+}
+
+impl TryFrom<Vec<RawComponent>> for Operation {
+    type Error = String;
+
+    fn try_from(components: Vec<RawComponent>) -> Result<Self, Self::Error> {
+        let mut operation = Operation::new();
+        // checked before the components are merged, so that the lengths can't overflow
+        let (mut base_length, mut target_length) = (0, 0);
+        for component in components {
+            match component {
+                RawComponent::Count(count) => {
+                    let length = usize::try_from(count.unsigned_abs())
+                        .map_err(|_| format!("{count} characters are too many"))?;
+                    base_length = bounded_sum(base_length, length).ok_or_else(too_long_base)?;
+                    if count >= 0 {
+                        target_length =
+                            bounded_sum(target_length, length).ok_or_else(too_long_result)?;
+                        operation.retain(length);
+                    } else {
+                        operation.delete(length);
+                    }
+                }
+                RawComponent::Insert(text) => {
+                    target_length = bounded_sum(target_length, text.chars().count())
+                        .ok_or_else(too_long_result)?;
+                    operation.insert(&text);
+                }
+            }
+        }
+        Ok(operation)
+    }
+}
+
+/// `None` if the sum exceeds the length of the documents.
+fn bounded_sum(length: usize, added: usize) -> Option<usize> {
+    length
+        .checked_add(added)
+        .filter(|&sum| sum <= MAX_INPUT_LENGTH)
+}
+
+fn too_long_base() -> String {
+    format!("the operation applies to texts of more than {MAX_INPUT_LENGTH} characters")
+}
+
+fn too_long_result() -> String {
+    format!("the operation makes texts longer than {MAX_INPUT_LENGTH} characters")
+}
+
+impl From<Operation> for Vec<RawComponent> {
+    fn from(operation: Operation) -> Self {
+        operation
+            .components
+            .into_iter()
+            .map(|component| match component {
+                Component::Retain(length) => RawComponent::Count(length as i64),
+                Component::Insert(text) => RawComponent::Insert(text),
+                Component::Delete(length) => RawComponent::Count(-(length as i64)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::math::ast::parser::MAX_INPUT_LENGTH;
+    use crate::math::collaboration::operation::Operation;
+
+    fn operation(components: serde_json::Value) -> Operation {
+        serde_json::from_value(components).unwrap()
+    }
+
+    #[test]
+    fn apply_replaces_and_keeps_characters() {
+        let result = operation(json!([4, -1, "20", 4])).apply("1 + 2 * ä");
+
+        assert_eq!(Ok("1 + 20 * ä".to_string()), result);
+    }
+
+    #[test]
+    fn apply_checks_the_length() {
+        let result = operation(json!([2, "x"])).apply("abc");
+
+        assert_eq!(
+            Err("the operation applies to texts of 2 characters, the document has 3".to_string()),
+            result
+        );
+    }
+
+    #[test]
+    fn components_are_normalized() {
+        let result = operation(json!([1, 2, -1, "a", -2, "b", 0]));
+
+        assert_eq!(json!([3, "ab", -3]), serde_json::to_value(result).unwrap());
+    }
+
+    #[test]
+    fn lengths_beyond_the_documents_are_rejected() {
+        let too_long = (MAX_INPUT_LENGTH + 1) as i64;
+        for components in [
+            json!([i64::MAX, i64::MAX]),
+            json!([-i64::MAX, -i64::MAX, 1]),
+            json!([MAX_INPUT_LENGTH, 1]),
+            json!([too_long]),
+            json!([-too_long]),
+            json!(["a".repeat(MAX_INPUT_LENGTH), "b"]),
+            json!([MAX_INPUT_LENGTH, "b", -1]),
+        ] {
+            let result = serde_json::from_value::<Operation>(components.clone());
+
+            assert!(result.is_err(), "{components}");
+        }
+    }
+
+    #[test]
+    fn results_beyond_the_documents_are_rejected_in_bytes() {
+        let text = "ä".repeat(MAX_INPUT_LENGTH / 2);
+
+        let at_the_limit = operation(json!([MAX_INPUT_LENGTH / 2])).apply(&text);
+        let beyond_the_limit = operation(json!([MAX_INPUT_LENGTH / 2, "ä"])).apply(&text);
+
+        assert_eq!(Ok(text), at_the_limit);
+        assert_eq!(
+            Err(format!(
+                "the operation makes the document 100002 bytes long, at most {MAX_INPUT_LENGTH} are accepted"
+            )),
+            beyond_the_limit
+        );
+    }
+
+    #[test]
+    fn lengths_up_to_the_documents_are_accepted() {
+        let result = operation(json!([
+            -(MAX_INPUT_LENGTH as i64),
+            "a".repeat(MAX_INPUT_LENGTH)
+        ]));
+
+        assert_eq!(MAX_INPUT_LENGTH, result.base_length());
+    }
+
+    #[test]
+    fn transformed_operations_converge() {
+        let text = "1 + 2; x * 3";
+        let cases = [
+            (json!([4, -1, "20", 7]), json!([7, -1, "y", 4])),
+            (json!([2, "7 ", 10]), json!([2, "8 ", 10])),
+            (json!([2, -6, 4]), json!([4, -4, "+", 4])),
+            (json!([-12]), json!([6, "z", 6])),
+        ];
+
+        for (a, b) in cases {
+            let (a, b) = (operation(a), operation(b));
+            let (transformed_a, transformed_b) = a.transform(&b).unwrap();
+
+            let a_first = transformed_b.apply(&a.apply(text).unwrap()).unwrap();
+            let b_first = transformed_a.apply(&b.apply(text).unwrap()).unwrap();
+            assert_eq!(a_first, b_first, "{a:?} and {b:?}");
+        }
+    }
+
+    #[test]
+    fn concurrent_inserts_keep_both_in_order() {
+        let (a, b) = (operation(json!([1, "a", 1])), operation(json!([1, "b", 1])));
+
+        let (transformed_a, _) = a.transform(&b).unwrap();
+
+        assert_eq!(Ok("xaby".to_string()), transformed_a.apply("xby"));
+    }
+
+    #[test]
+    fn positions_move_with_the_text() {
+        let replace = operation(json!([2, -3, "abcd", 5]));
+
+        let result: Vec<_> = [0, 2, 3, 5, 10]
+            .into_iter()
+            .map(|position| replace.transform_position(position))
+            .collect();
+
+        assert_eq!(vec![0, 6, 6, 6, 11], result);
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::math::ast::parser::highlighting::{classify_tokens, TokenClass};
+use crate::math::ast::parser::lexer::{tokenize, TokenKind};
+use crate::math::ast::parser::{parse_statements, split_statements};
+use crate::math::ast::{Localization, Span};
+use crate::math::document::offset_of;
+use crate::math::navigation::definition_header;
+
+/// Names with a meaning of their own, they are never variables.
+const BUILT_INS: [(&str, &str, &str); 5] = [
+    (
+        "ans",
+        "ans",
+        "the value of the latest statement that has one",
+    ),
+    (
+        "out",
+        "out[n]",
+        "the value of the n-th statement, counted from 1",
+    ),
+    (
+        "mod",
+        "a mod b",
+        "the remainder of the floored division of a by b, like %",
+    ),
+    (
+        "infixl",
+        "infixl precedence a symbol b = body",
+        "defines a left associative operator for the statements after it",
+    ),
+    (
+        "infixr",
+        "infixr precedence a symbol b = body",
+        "defines a right associative operator for the statements after it",
+    ),
+];
+
+#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionKind {
+    /// an operand of the operator definition around the cursor
+    Parameter,
+    /// a name without value used somewhere in the document
+    Variable,
+    /// a user defined operator
+    Operator,
+    /// `out[n]` for an earlier statement
+    Reference,
+    BuiltIn,
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub signature: String,
+    pub documentation: String,
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct Completions {
+    /// the name or symbol a completion replaces, empty at the cursor if there is none
+    pub span: Span,
+    pub items: Vec<Completion>,
+}
+
+/// What can be written at `position`, starting with the text of the name or symbol before the
+/// cursor. The names closest to the cursor come first: the parameters of the enclosing
+/// definition, the variables of the document, the operators defined before the statement with
+/// the latest definition first, the earlier statements and the built-in names.
+///
+/// Statements with syntax errors still contribute their names, only their definitions are lost.
+pub fn completions_at(input: &str, position: Localization) -> Result<Completions, String> {
+    let offset = offset_of(input, position)?;
+    let cursor = Span {
+        start: position,
+        end: position,
+        start_offset: offset,
+        end_offset: offset,
+    };
+    let nothing = |span| {
+        Ok(Completions {
+            span,
+            items: vec![],
+        })
+    };
+
+    let tokens = tokenize(input);
+    let touched = tokens
+        .iter()
+        .find(|token| token.span.start_offset < offset && offset <= token.span.end_offset);
+    let (span, prefix) = match touched {
+        Some(token) if matches!(token.kind, TokenKind::Identifier | TokenKind::Operator) => {
+            (token.span, &input[token.span.start_offset..offset])
+        }
+        // nothing can be appended to a number or written into a comment
+        Some(token)
+            if matches!(
+                token.kind,
+                TokenKind::Number | TokenKind::Comment | TokenKind::Unknown
+            ) =>
+        {
+            return nothing(cursor)
+        }
+        _ => (cursor, ""),
+    };
+    let Ok(sources) = split_statements(input) else {
+        return nothing(span);
+    };
+    let index = sources
+        .iter()
+        .position(|source| {
+            source.text_span.start_offset <= offset && offset <= source.text_span.end_offset
+        })
+        .unwrap_or(sources.len());
+
+    let mut candidates = vec![];
+    if let Some(source) = sources.get(index) {
+        if let Some([left, symbol, right]) = definition_header(source.tokens()) {
+            let equals = source.tokens()[5];
+            if offset >= equals.span.end_offset {
+                for (parameter, side) in [(left, "left"), (right, "right")] {
+                    candidates.push(Completion {
+                        label: parameter.text.to_string(),
+                        kind: CompletionKind::Parameter,
+                        signature: parameter.text.to_string(),
+                        documentation: format!("the {side} operand of {}", symbol.text),
+                    });
+                }
+            }
+        }
+    }
+
+    let identifiers: HashSet<usize> = classify_tokens(input)
+        .into_iter()
+        .filter(|token| token.class == TokenClass::Identifier)
+        .map(|token| token.span.start_offset)
+        .collect();
+    // the names in definitions are their parameters
+    for source in sources
+        .iter()
+        .filter(|source| definition_header(source.tokens()).is_none())
+    {
+        for token in source.tokens() {
+            let is_variable = identifiers.contains(&token.span.start_offset)
+                && token.span != span
+                && BUILT_INS.iter().all(|(name, ..)| *name != token.text);
+            if is_variable {
+                candidates.push(Completion {
+                    label: token.text.to_string(),
+                    kind: CompletionKind::Variable,
+                    signature: token.text.to_string(),
+                    documentation: "a variable without value".to_string(),
+                });
+            }
+        }
+    }
+
+    let statements = parse_statements(input.to_string());
+    for statement in statements[..index.min(statements.len())].iter().rev() {
+        let Some(definition) = statement
+            .result
+            .as_ref()
+            .ok()
+            .and_then(|node| node.operator_definition())
+        else {
+            continue;
+        };
+        let [left, right] = &definition.parameters;
+        candidates.push(Completion {
+            label: definition.symbol.clone(),
+            kind: CompletionKind::Operator,
+            signature: format!(
+                "{} {} {left} {} {right}",
+                definition.associativity, definition.precedence, definition.symbol
+            ),
+            documentation: definition.to_string(),
+        });
+    }
+
+    for (number, source) in sources[..index].iter().enumerate() {
+        if definition_header(source.tokens()).is_none() {
+            let reference = format!("out[{}]", number + 1);
+            candidates.push(Completion {
+                label: reference.clone(),
+                kind: CompletionKind::Reference,
+                signature: reference,
+                documentation: format!("the value of {}", source.statement()),
+            });
+        }
+    }
+
+    for (name, signature, documentation) in BUILT_INS {
+        candidates.push(Completion {
+            label: name.to_string(),
+            kind: CompletionKind::BuiltIn,
+            signature: signature.to_string(),
+            documentation: documentation.to_string(),
+        });
+    }
+
+    let mut labels = HashSet::new();
+    let items = candidates
+        .into_iter()
+        .filter(|candidate| candidate.label.starts_with(prefix))
+        .filter(|candidate| labels.insert(candidate.label.clone()))
+        .collect();
+    Ok(Completions { span, items })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ast::Localization;
+    use crate::math::completion::{completions_at, CompletionKind};
+
+    /// The labels of the completions at the end of `input`.
+    fn labels(input: &str) -> Vec<String> {
+        let line = input.matches('\n').count();
+        let column = input.rsplit('\n').next().unwrap().chars().count();
+        completions_at(input, Localization::at(line, column))
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|completion| completion.label)
+            .collect()
+    }
+
+    #[test]
+    fn everything_in_scope_without_prefix() {
+        let result = labels("infixl 6 a <+> b = a; x * 2; infixl 7 c <*> d = ");
+
+        let expected = vec![
+            "c", "d", "x", "<+>", "out[2]", "ans", "out", "mod", "infixl", "infixr",
+        ];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn names_starting_with_the_prefix() {
+        let result = labels("speed * time; infixl 6 a <+> b = a; s");
+
+        assert_eq!(vec!["speed"], result);
+    }
+
+    #[test]
+    fn operators_with_signature_and_definition() {
+        let input = "infixl 6 a <+> b = a; infixr 5 a <^> b = b; 1 <";
+
+        let result = completions_at(input, Localization::at(0, 47)).unwrap();
+
+        let labels: Vec<_> = result
+            .items
+            .iter()
+            .map(|item| item.label.as_str())
+            .collect();
+        assert_eq!(vec!["<^>", "<+>"], labels);
+        assert_eq!(CompletionKind::Operator, result.items[0].kind);
+        assert_eq!("infixr 5 a <^> b", result.items[0].signature);
+        assert_eq!("infixr 5 a <^> b = b", result.items[0].documentation);
+        assert_eq!(46, result.span.start_offset);
+    }
+
+    #[test]
+    fn invalid_statements_still_contribute_names() {
+        assert_eq!(vec!["temperature"], labels("1 + (2; temperature * 2 +; te"));
+    }
+
+    #[test]
+    fn nothing_after_numbers_and_in_comments() {
+        assert!(labels("12").is_empty());
+        assert!(labels("1; # a").is_empty());
+    }
+
+    #[test]
+    fn positions_outside_of_the_document_are_rejected() {
+        assert_eq!(
+            Err("line 3 column 0 is outside of the document".to_string()),
+            completions_at("1", Localization::at(3, 0))
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use serde::Deserialize;
+
+use crate::math::ast::parser::MAX_INPUT_LENGTH;
+use crate::math::ast::Localization;
+
+/// How many bytes the edits of one message may scan and copy, about a hundred edits of the longest
+/// document, so that one message can't stall the connection.
+pub const MAX_EDIT_WORK: usize = 100 * MAX_INPUT_LENGTH;
+
+/// The replacement of the text between `start` and `end` by `text`.
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TextEdit {
+    pub start: Localization,
+    pub end: Localization,
+    pub text: String,
+}
+
+/// The server's copy of the document a client is editing. Every change increments the version,
+/// so that edits made against another version are detected instead of corrupting the text.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Document {
+    version: u64,
+    text: String,
+}
+
+impl Document {
+    pub fn new(version: u64, text: String) -> Self {
+        Document { version, text }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Applies `edits` one after the other, each one to the text left by the previous one.
+    /// `version` has to follow the current version, otherwise the document stays unchanged,
+    /// as it does if any of the edits doesn't fit the text or makes it longer than the parser
+    /// accepts, or if the edits are more work than [MAX_EDIT_WORK].
+    pub fn apply(&mut self, version: u64, edits: &[TextEdit]) -> Result<(), String> {
+        if version != self.version + 1 {
+            return Err(format!(
+                "expected version {}, got {version}",
+                self.version + 1
+            ));
+        }
+
+        let mut text = self.text.clone();
+        // every edit scans the text up to its end and copies the rest
+        let mut work = 0;
+        for edit in edits {
+            work += text.len() + edit.text.len();
+            if work > MAX_EDIT_WORK {
+                return Err(format!(
+                    "the {} edits change too much at once, open the document again instead",
+                    edits.len()
+                ));
+            }
+            let start = offset_of(&text, edit.start)?;
+            let end = offset_of(&text, edit.end)?;
+            if start > end {
+                return Err(format!(
+                    "the edit starts at {} after its end {}",
+                    describe(edit.start),
+                    describe(edit.end)
+                ));
+            }
+            let length = text.len() - (end - start) + edit.text.len();
+            if length > MAX_INPUT_LENGTH {
+                return Err(format!(
+                    "the edits make the document {length} bytes long, at most {MAX_INPUT_LENGTH} are accepted"
+                ));
+            }
+            text.replace_range(start..end, &edit.text);
+        }
+
+        self.version = version;
+        self.text = text;
+        Ok(())
+    }
+}
+
+/// The byte offset of `position`, which may be at the end of a line but not beyond it.
+pub(crate) fn offset_of(text: &str, position: Localization) -> Result<usize, String> {
+    let outside = || format!("{} is outside of the document", describe(position));
+
+    let mut line_start = 0;
+    for _ in 0..position.line() {
+        line_start += text[line_start..].find('\n').ok_or_else(outside)? + 1;
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let column = line
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([line.len()])
+        .nth(position.column())
+        .ok_or_else(outside)?;
+    Ok(line_start + column)
+}
+
+fn describe(position: Localization) -> String {
+    format!("line {} column {}", position.line(), position.column())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ast::parser::MAX_INPUT_LENGTH;
+    use crate::math::ast::Localization;
+    use crate::math::document::{Document, TextEdit, MAX_EDIT_WORK};
+
+    fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> TextEdit {
+        TextEdit {
+            start: Localization::at(start.0, start.1),
+            end: Localization::at(end.0, end.1),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn edits_replace_ranges_in_order() {
+        let mut document = Document::new(1, "1 + 2;\nä * 3".to_string());
+
+        let result = document.apply(
+            2,
+            &[
+                edit((1, 0), (1, 1), "x"),
+                edit((0, 4), (0, 5), "20"),
+                edit((1, 5), (1, 5), "!"),
+            ],
+        );
+
+        assert_eq!(Ok(()), result);
+        assert_eq!("1 + 20;\nx * 3!", document.text());
+        assert_eq!(2, document.version());
+    }
+
+    #[test]
+    fn out_of_order_versions_are_rejected() {
+        let mut document = Document::new(3, "1".to_string());
+
+        let outdated = document.apply(3, &[edit((0, 0), (0, 1), "2")]);
+        let skipped = document.apply(5, &[edit((0, 0), (0, 1), "2")]);
+
+        assert_eq!(Err("expected version 4, got 3".to_string()), outdated);
+        assert_eq!(Err("expected version 4, got 5".to_string()), skipped);
+        assert_eq!(("1", 3), (document.text(), document.version()));
+    }
+
+    #[test]
+    fn edits_outside_of_the_document_change_nothing() {
+        let mut document = Document::new(1, "12\n3".to_string());
+
+        let beyond_the_line =
+            document.apply(2, &[edit((0, 0), (0, 1), "x"), edit((1, 2), (1, 2), "4")]);
+        let beyond_the_end = document.apply(2, &[edit((2, 0), (2, 0), "4")]);
+        let reversed = document.apply(2, &[edit((1, 0), (0, 1), "4")]);
+
+        assert_eq!(
+            Err("line 1 column 2 is outside of the document".to_string()),
+            beyond_the_line
+        );
+        assert_eq!(
+            Err("line 2 column 0 is outside of the document".to_string()),
+            beyond_the_end
+        );
+        assert_eq!(
+            Err("the edit starts at line 1 column 0 after its end line 0 column 1".to_string()),
+            reversed
+        );
+        assert_eq!(("12\n3", 1), (document.text(), document.version()));
+    }
+
+    #[test]
+    fn documents_stay_within_the_input_length() {
+        let mut document = Document::new(1, "1".repeat(MAX_INPUT_LENGTH - 1));
+
+        let at_the_limit = document.apply(2, &[edit((0, 0), (0, 0), "2")]);
+        let beyond_the_limit =
+            document.apply(3, &[edit((0, 0), (0, 1), ""), edit((0, 0), (0, 0), "34")]);
+
+        assert_eq!(Ok(()), at_the_limit);
+        assert_eq!(
+            Err(
+                "the edits make the document 100001 bytes long, at most 100000 are accepted"
+                    .to_string()
+            ),
+            beyond_the_limit
+        );
+        assert_eq!(MAX_INPUT_LENGTH, document.text().len());
+    }
+
+    #[test]
+    fn the_work_of_the_edits_is_limited() {
+        let text = "1".repeat(MAX_INPUT_LENGTH / 2);
+        let mut document = Document::new(1, text.clone());
+        let edits = vec![edit((0, 0), (0, 1), "1"); MAX_EDIT_WORK / text.len()];
+
+        let too_many = document.apply(2, &[edits.clone(), edits.clone()].concat());
+        let within_the_limit = document.apply(2, &edits[1..]);
+
+        assert_eq!(
+            Err(
+                "the 400 edits change too much at once, open the document again instead"
+                    .to_string()
+            ),
+            too_many
+        );
+        assert_eq!(Ok(()), within_the_limit);
+    }
+}
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::sync::Semaphore;
+
+use crate::math::ast::budget::Limits;
+use crate::math::protocol::{Evaluation, ServerMessage};
+
+/// Runs evaluations on the blocking threads of the runtime, so that an expensive document
+/// doesn't stall other connections. At most `workers` evaluations run at the same time,
+/// the others wait for a free worker.
+#[derive(Clone)]
+pub struct EvaluationPool {
+    limits: Limits,
+    workers: Arc<Semaphore>,
+}
+
+impl EvaluationPool {
+    pub fn new(limits: Limits, workers: usize) -> Self {
+        EvaluationPool {
+            limits,
+            workers: Arc::new(Semaphore::new(workers)),
+        }
+    }
+
+    /// Configured by `MATH_TIMEOUT_MS`, `MATH_MAX_DIGITS` and `MATH_WORKERS`,
+    /// defaults to the `Limits` defaults and one worker per CPU.
+    pub fn from_env() -> Self {
+        let default_limits = Limits::default();
+        let limits = Limits {
+            timeout: env_variable("MATH_TIMEOUT_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default_limits.timeout),
+            max_digits: env_variable("MATH_MAX_DIGITS").unwrap_or(default_limits.max_digits),
+        };
+        let workers = workers(env_variable("MATH_WORKERS"));
+
+        info!("math evaluation: {workers} workers, {limits:?}");
+        EvaluationPool::new(limits, workers)
+    }
+
+    pub async fn run(&self, evaluation: Evaluation) -> Vec<ServerMessage> {
+        self.run_with(move |limits| evaluation.run(limits))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Runs `job` with the limits of the pool on a worker, `None` if it panicked.
+    pub async fn run_with<T: Send + 'static>(
+        &self,
+        job: impl FnOnce(Limits) -> T + Send + 'static,
+    ) -> Option<T> {
+        let worker = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let limits = self.limits;
+
+        tokio::task::spawn_blocking(move || {
+            let result = job(limits);
+            drop(worker);
+            result
+        })
+        .await
+        .map_err(|e| error!("math evaluation failed: {e}"))
+        .ok()
+    }
+}
+
+/// Without any worker, every evaluation would wait forever.
+fn workers(configured: Option<usize>) -> usize {
+    match configured {
+        Some(0) => error!("ignoring MATH_WORKERS=0, at least one worker is needed"),
+        Some(workers) => return workers,
+        None => {}
+    }
+    std::thread::available_parallelism()
+        .map(|parallelism| parallelism.get())
+        .unwrap_or(1)
+}
+
+fn env_variable<T: FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        error!("ignoring {name}={value}, it is no valid number");
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::ast::budget::Limits;
+    use crate::math::evaluation_pool::{workers, EvaluationPool};
+    use crate::math::protocol::{Reply, ServerMessage, Session};
+
+    #[tokio::test]
+    async fn runs_evaluations() {
+        let pool = EvaluationPool::new(Limits::default(), 1);
+        let mut session = Session::new();
+        session.handle(r#"{"type": "hello", "version": 1}"#);
+        let evaluation = match session
+            .handle(r#"{"type": "evaluate", "id": 1, "document": "1 + 2"}"#)
+            .pop()
+        {
+            Some(Reply::Evaluate(evaluation)) => evaluation,
+            _ => panic!("expected an evaluation"),
+        };
+
+        let result = pool.run(evaluation).await;
+
+        assert_eq!(2, result.len());
+        assert!(matches!(result[0], ServerMessage::Result { id: 1, .. }));
+    }
+
+    #[test]
+    fn zero_workers_fall_back_to_the_default() {
+        assert_eq!(workers(None), workers(Some(0)));
+        assert!(workers(Some(0)) >= 1);
+        assert_eq!(3, workers(Some(3)));
+    }
+}
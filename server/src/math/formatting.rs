@@ -0,0 +1,141 @@
+use crate::math::ast::parser::lexer::{tokenize, Token, TokenKind};
+
+/// How a token relates to its neighbours, which decides the spaces around it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Role {
+    Operand,
+    /// a unary minus, or the `%` of a history reference
+    Prefix,
+    Infix,
+    Postfix,
+    Opening,
+    Closing,
+    Separator,
+    Comment,
+}
+
+/// Lays out a document with single spaces around binary operators and after `;`, and without
+/// spaces inside of delimiters or after unary operators. Line breaks and comments are kept,
+/// but no more than one empty line in a row.
+///
+/// Returns `None` if the result would consist of other tokens than `input`,
+/// e.g. because two adjacent symbols would merge into one.
+pub fn format_document(input: &str) -> Option<String> {
+    let tokens = tokenize(input);
+
+    let mut formatted = String::new();
+    let mut previous: Option<Role> = None;
+    let mut after_operand = false;
+    let mut line_breaks = 0;
+    for token in &tokens {
+        if token.kind == TokenKind::Whitespace {
+            line_breaks += token.text.matches('\n').count();
+            continue;
+        }
+
+        let role = role_of(token, after_operand);
+        if let Some(previous_role) = previous {
+            if line_breaks > 0 {
+                formatted.push_str(&"\n".repeat(line_breaks.min(2)));
+            } else if needs_space(previous_role, *token, role) {
+                formatted.push(' ');
+            }
+        }
+        formatted.push_str(token.text);
+
+        after_operand = match role {
+            Role::Operand | Role::Postfix | Role::Closing => true,
+            Role::Comment => after_operand,
+            Role::Prefix | Role::Infix | Role::Opening | Role::Separator => false,
+        };
+        previous = Some(role);
+        line_breaks = 0;
+    }
+    if line_breaks > 0 && previous.is_some() {
+        formatted.push('\n');
+    }
+
+    let texts = |tokens: Vec<Token>| -> Vec<String> {
+        tokens
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::Whitespace)
+            .map(|token| token.text.to_string())
+            .collect()
+    };
+    if texts(tokenize(&formatted)) == texts(tokens) {
+        Some(formatted)
+    } else {
+        None
+    }
+}
+
+/// `after_operand` tells binary from unary operators and closing from opening bars.
+fn role_of(token: &Token, after_operand: bool) -> Role {
+    match token.kind {
+        TokenKind::Number | TokenKind::Unknown => Role::Operand,
+        TokenKind::Identifier if token.text == "mod" && after_operand => Role::Infix,
+        TokenKind::Identifier => Role::Operand,
+        TokenKind::Plus | TokenKind::Minus | TokenKind::Percent if !after_operand => Role::Prefix,
+        TokenKind::Plus
+        | TokenKind::Minus
+        | TokenKind::Percent
+        | TokenKind::Asterisk
+        | TokenKind::Slash
+        | TokenKind::Caret
+        | TokenKind::Operator => Role::Infix,
+        TokenKind::Factorial | TokenKind::DoubleFactorial => Role::Postfix,
+        TokenKind::OpeningParenthesis | TokenKind::OpeningBracket => Role::Opening,
+        TokenKind::ClosingParenthesis | TokenKind::ClosingBracket => Role::Closing,
+        TokenKind::Bar if after_operand => Role::Closing,
+        TokenKind::Bar => Role::Opening,
+        TokenKind::Semicolon => Role::Separator,
+        TokenKind::Comment => Role::Comment,
+        TokenKind::Whitespace => unreachable!("whitespace is replaced"),
+    }
+}
+
+fn needs_space(previous_role: Role, token: Token, role: Role) -> bool {
+    match (previous_role, role) {
+        (_, Role::Comment) => true,
+        (_, Role::Closing | Role::Postfix | Role::Separator) => false,
+        (Role::Comment, _) => true,
+        (Role::Opening | Role::Prefix, _) => false,
+        // `out[3]`
+        (Role::Operand, Role::Opening) => token.kind != TokenKind::OpeningBracket,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::formatting::format_document;
+
+    #[test]
+    fn spaces_around_binary_operators_only() {
+        let result = format_document("-( 1+2 )*-3!^|-x|mod 2 ;out [ 1 ]-%1").unwrap();
+
+        assert_eq!("-(1 + 2) * -3! ^ |-x| mod 2; out[1] - %1", result);
+    }
+
+    #[test]
+    fn line_breaks_and_comments_are_kept() {
+        let input = "  # sum\n1+  2 /* two */;\n\n\n\ninfixl 6 a<+>b=a  // mean\n";
+
+        let result = format_document(input).unwrap();
+
+        assert_eq!(
+            "# sum\n1 + 2 /* two */;\n\ninfixl 6 a <+> b = a // mean\n",
+            result
+        );
+    }
+
+    #[test]
+    fn merging_tokens_are_left_alone() {
+        assert_eq!(None, format_document("(- <+> 2)"));
+    }
+
+    #[test]
+    fn empty_documents() {
+        assert_eq!(Some(String::new()), format_document(" \n "));
+    }
+}
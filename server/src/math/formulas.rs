@@ -0,0 +1,285 @@
+//! Formulas in text, like chat messages, between dollar signs as in LaTeX: `$1 + 2$ is three`.
+//!
+//! Like in pandoc, the opening `$` has to be followed by a character that isn't a space and the
+//! closing `$` has to follow one and mustn't be followed by a digit, so that `it costs $5 or $6`
+//! has no formula. `\$` is a dollar sign.
+
+use crate::math::application::{Application, Output};
+use crate::math::ast::budget::Budget;
+use crate::math::ast::parser::parse;
+use crate::math::ast::LocalizedSyntaxNode;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Formula(Formula),
+}
+
+/// At most one of `result` and `error` is set, `latex` is `None` if there is an error.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Formula {
+    /// the text between the dollar signs
+    pub source: String,
+    /// the statements for LaTeX's math mode, with their values if they were evaluated
+    pub latex: Option<String>,
+    /// the results of the statements like in the `result` message of the websocket protocol,
+    /// `None` if the formula wasn't evaluated
+    pub result: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Separates the statements of a formula in LaTeX.
+const LATEX_SEPARATOR: &str = ", \\quad ";
+
+/// Splits `text` into text and formulas. The formulas are parsed, and evaluated within `budget`
+/// if there is one. Every formula is evaluated on its own.
+pub fn segments(text: &str, budget: Option<&Budget>) -> Vec<Segment> {
+    split(text)
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => Segment::Text(text),
+            Part::Formula(source) => Segment::Formula(formula(source, budget)),
+        })
+        .collect()
+}
+
+enum Part<'a> {
+    Text(String),
+    Formula(&'a str),
+}
+
+fn split(text: &str) -> Vec<Part<'_>> {
+    let characters: Vec<_> = text.char_indices().collect();
+    let closings = closing_dollars(&characters);
+    let mut parts = vec![];
+    let mut plain = String::new();
+    let mut index = 0;
+    while index < characters.len() {
+        let (offset, character) = characters[index];
+        match character {
+            '\\' if matches!(characters.get(index + 1), Some((_, '$'))) => {
+                plain.push('$');
+                index += 2;
+                continue;
+            }
+            '$' => {
+                if let Some(closing) = closing_dollar(&characters, &closings, index) {
+                    if !plain.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut plain)));
+                    }
+                    parts.push(Part::Formula(&text[offset + 1..characters[closing].0]));
+                    index = closing + 1;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        plain.push(character);
+        index += 1;
+    }
+    if !plain.is_empty() {
+        parts.push(Part::Text(plain));
+    }
+    parts
+}
+
+/// The indexes of the `$` that can close a formula, which only depends on their neighbours.
+fn closing_dollars(characters: &[(usize, char)]) -> Vec<usize> {
+    (1..characters.len())
+        .filter(|&index| {
+            let previous = characters[index - 1].1;
+            characters[index].1 == '$'
+                && !previous.is_whitespace()
+                && previous != '\\'
+                && !matches!(characters.get(index + 1), Some((_, next)) if next.is_ascii_digit())
+        })
+        .collect()
+}
+
+/// The index of the `$` that closes the formula opened at `opening`, if there is one.
+fn closing_dollar(
+    characters: &[(usize, char)],
+    closings: &[usize],
+    opening: usize,
+) -> Option<usize> {
+    match characters.get(opening + 1) {
+        Some((_, next)) if !next.is_whitespace() && *next != '$' => {}
+        _ => return None,
+    }
+    let next = closings.partition_point(|&index| index < opening + 2);
+    closings.get(next).copied()
+}
+
+fn formula(source: &str, budget: Option<&Budget>) -> Formula {
+    let trees = match parse(source.to_string()) {
+        Ok(trees) => trees,
+        Err(error) => return failed(source, error.message),
+    };
+    let Some(budget) = budget else {
+        return Formula {
+            source: source.to_string(),
+            latex: Some(latex(trees.iter().map(LocalizedSyntaxNode::to_latex))),
+            result: None,
+            error: None,
+        };
+    };
+
+    let mut application = Application::create();
+    let mut results = vec![];
+    for statement in application.run_within(source.to_string(), budget) {
+        match statement.output {
+            Output::Value(text) => results.push(text),
+            Output::Comment(_) => {}
+            Output::Error(diagnostic) => return failed(source, diagnostic.message),
+        }
+    }
+    let statements = trees
+        .iter()
+        .zip(application.history())
+        .map(|(tree, entry)| match &entry.value {
+            Some(value) => format!("{} = {value}", tree.to_latex()),
+            None => tree.to_latex(),
+        });
+    Formula {
+        source: source.to_string(),
+        latex: Some(latex(statements)),
+        result: Some(results.join("; ")),
+        error: None,
+    }
+}
+
+fn latex(statements: impl Iterator<Item = String>) -> String {
+    statements.collect::<Vec<_>>().join(LATEX_SEPARATOR)
+}
+
+fn failed(source: &str, error: String) -> Formula {
+    Formula {
+        source: source.to_string(),
+        latex: None,
+        result: None,
+        error: Some(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::math::ast::budget::{Budget, CancellationToken, Limits};
+    use crate::math::formulas::{segments, Formula, Segment};
+
+    fn text(text: &str) -> Segment {
+        Segment::Text(text.to_string())
+    }
+
+    fn sources(segments: Vec<Segment>) -> Vec<String> {
+        segments
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Formula(formula) => Some(formula.source),
+                Segment::Text(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn text_without_formulas_is_one_segment() {
+        assert_eq!(
+            vec![text("no formula here")],
+            segments("no formula here", None)
+        );
+        assert!(segments("", None).is_empty());
+    }
+
+    #[test]
+    fn formulas_are_between_dollar_signs() {
+        let result = segments("so $1 + 2$ and $x^2$", None);
+
+        assert_eq!(4, result.len());
+        assert_eq!(text("so "), result[0]);
+        assert_eq!(text(" and "), result[2]);
+        assert_eq!(vec!["1 + 2", "x^2"], sources(result));
+    }
+
+    #[test]
+    fn prices_and_escaped_dollars_are_text() {
+        for message in [
+            "it costs $5 or $6",
+            "5$ and 6$",
+            "$ 1 + 2 $",
+            "$$",
+            "from $1$2 on",
+        ] {
+            assert_eq!(vec![text(message)], segments(message, None), "{message}");
+        }
+        assert_eq!(vec![text("$1 + 2$")], segments("\\$1 + 2\\$", None));
+    }
+
+    #[test]
+    fn long_messages_are_split_in_linear_time() {
+        let message = "$1".repeat(100_000);
+
+        let start = Instant::now();
+        let result = segments(&message, None);
+
+        assert_eq!(vec![text(&message)], result);
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn formulas_are_parsed_without_budget() {
+        let result = segments("$1/2 + x$", None);
+
+        assert_eq!(
+            vec![Segment::Formula(Formula {
+                source: "1/2 + x".to_string(),
+                latex: Some("\\frac{1}{2} + x".to_string()),
+                result: None,
+                error: None,
+            })],
+            result
+        );
+    }
+
+    #[test]
+    fn formulas_are_evaluated_within_the_budget() {
+        let budget = Budget::new(Limits::default(), CancellationToken::new());
+
+        let result = segments("$1 + 2; 3!$", Some(&budget));
+
+        assert_eq!(
+            vec![Segment::Formula(Formula {
+                source: "1 + 2; 3!".to_string(),
+                latex: Some("1 + 2 = 3, \\quad 3! = 6".to_string()),
+                result: Some("(1 + 2) = 3; 3! = 6".to_string()),
+                error: None,
+            })],
+            result
+        );
+    }
+
+    #[test]
+    fn failing_formulas_have_an_error() {
+        let exhausted = Budget::new(
+            Limits {
+                timeout: Duration::ZERO,
+                ..Limits::default()
+            },
+            CancellationToken::new(),
+        );
+
+        for (message, budget) in [("$1 +$", None), ("$2^3$", Some(&exhausted))] {
+            match &segments(message, budget)[0] {
+                Segment::Formula(formula) => {
+                    assert!(formula.error.is_some(), "{message}");
+                    assert_eq!(None, formula.latex);
+                }
+                segment => panic!("expected a formula, got {segment:?}"),
+            }
+        }
+    }
+}
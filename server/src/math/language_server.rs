@@ -0,0 +1,277 @@
+//! The documents of the `lyng2-lsp` binary, which speaks the Language Server Protocol.
+
+use lsp_types::{
+    Diagnostic as LspDiagnostic, DiagnosticSeverity, Hover, HoverContents, MarkupContent,
+    MarkupKind, NumberOrString, Position, Range, TextEdit,
+};
+
+use crate::math::application::diagnostic::Diagnostic;
+use crate::math::application::{Application, Output};
+use crate::math::ast::budget::{Budget, CancellationToken, Limits};
+use crate::math::ast::parser::{parse_statements, split_statements};
+use crate::math::ast::{Localization, Span};
+use crate::math::formatting::format_document;
+use crate::math::navigation::definition_at;
+
+/// A document the editor has opened, evaluated after every change.
+pub struct OpenDocument {
+    pub text: String,
+    application: Application,
+}
+
+impl OpenDocument {
+    pub fn new(text: String) -> Self {
+        OpenDocument {
+            text,
+            application: Application::create(),
+        }
+    }
+
+    /// Evaluates the document again, unchanged statements are taken from the previous run.
+    pub fn diagnostics(&mut self) -> Vec<LspDiagnostic> {
+        let budget = Budget::new(Limits::default(), CancellationToken::new());
+        self.application
+            .run_within(self.text.clone(), &budget)
+            .into_iter()
+            .filter_map(|result| match result.output {
+                Output::Error(diagnostic) => Some(to_lsp_diagnostic(&self.text, diagnostic)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The parsed tree and the value of the statement at `position`.
+    pub fn hover(&self, position: Localization) -> Option<Hover> {
+        let sources = split_statements(&self.text).ok()?;
+        let index = sources
+            .iter()
+            .position(|source| contains(source.span, position))?;
+        let tree = parse_statements(self.text.clone())
+            .swap_remove(index)
+            .result
+            .ok()?;
+        let value = self
+            .application
+            .history()
+            .get(index)
+            .and_then(|entry| entry.value.as_ref());
+
+        let statement = match value {
+            Some(value) => format!("{tree} = {value}"),
+            None => tree.to_string(),
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```\n{statement}\n```\n\n```\n{}```", tree.outline()),
+            }),
+            range: Some(to_range(&self.text, sources[index].span)),
+        })
+    }
+
+    pub fn definition(&self, position: Localization) -> Option<Span> {
+        let values: Vec<_> = self
+            .application
+            .history()
+            .iter()
+            .map(|entry| entry.value.clone())
+            .collect();
+        definition_at(&self.text, position, &values)
+    }
+
+    /// One edit replacing the whole document, none if it is formatted already.
+    pub fn formatting(&self) -> Vec<TextEdit> {
+        match format_document(&self.text) {
+            Some(formatted) if formatted != self.text => {
+                let end = Localization::at(
+                    self.text.matches('\n').count(),
+                    self.text
+                        .rsplit('\n')
+                        .next()
+                        .unwrap_or_default()
+                        .chars()
+                        .count(),
+                );
+                vec![TextEdit {
+                    range: Range::new(Position::new(0, 0), to_position(&self.text, end)),
+                    new_text: formatted,
+                }]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// The help and the notes follow the message on lines of their own.
+pub fn to_lsp_diagnostic(text: &str, diagnostic: Diagnostic) -> LspDiagnostic {
+    let mut message = diagnostic.message;
+    if let Some(help) = diagnostic.help {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+    for note in diagnostic.notes {
+        message.push_str(&format!("\nnote: {note}"));
+    }
+    LspDiagnostic {
+        range: to_range(text, diagnostic.span),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(diagnostic.code.to_string())),
+        source: Some("lyng2".to_string()),
+        message,
+        ..LspDiagnostic::default()
+    }
+}
+
+fn contains(span: Span, position: Localization) -> bool {
+    let key = |localization: Localization| (localization.line(), localization.column());
+    key(span.start) <= key(position) && key(position) <= key(span.end)
+}
+
+pub fn to_range(text: &str, span: Span) -> Range {
+    Range::new(to_position(text, span.start), to_position(text, span.end))
+}
+
+/// LSP counts the columns in UTF-16 code units, `Localization` in characters.
+pub fn to_position(text: &str, localization: Localization) -> Position {
+    let line = text
+        .split('\n')
+        .nth(localization.line())
+        .unwrap_or_default();
+    let character: usize = line
+        .chars()
+        .take(localization.column())
+        .map(char::len_utf16)
+        .sum();
+    Position::new(localization.line() as u32, character as u32)
+}
+
+pub fn to_localization(text: &str, position: Position) -> Localization {
+    let line = text
+        .split('\n')
+        .nth(position.line as usize)
+        .unwrap_or_default();
+    let mut code_units = 0;
+    let mut column = 0;
+    for character in line.chars() {
+        code_units += character.len_utf16();
+        if code_units > position.character as usize {
+            break;
+        }
+        column += 1;
+    }
+    Localization::at(position.line as usize, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{DiagnosticSeverity, HoverContents, NumberOrString, Position, Range, TextEdit};
+
+    use crate::math::application::diagnostic::Diagnostic;
+    use crate::math::ast::parser::parse_statements;
+    use crate::math::ast::Localization;
+    use crate::math::language_server::{
+        to_localization, to_lsp_diagnostic, to_position, OpenDocument,
+    };
+
+    fn hover_text(document: &OpenDocument, position: Localization) -> String {
+        match document.hover(position).unwrap().contents {
+            HoverContents::Markup(markup) => markup.value,
+            contents => panic!("expected markup, got {contents:?}"),
+        }
+    }
+
+    #[test]
+    fn positions_count_utf16_code_units() {
+        // '𝑥' is one character, but two UTF-16 code units
+        let text = "1;\n𝑥 + é";
+
+        assert_eq!(
+            Position::new(1, 2),
+            to_position(text, Localization::at(1, 1))
+        );
+        assert_eq!(
+            Position::new(1, 6),
+            to_position(text, Localization::at(1, 5))
+        );
+        assert_eq!(
+            Localization::at(1, 1),
+            to_localization(text, Position::new(1, 2))
+        );
+        assert_eq!(
+            Localization::at(1, 5),
+            to_localization(text, Position::new(1, 6))
+        );
+    }
+
+    #[test]
+    fn positions_within_a_character_point_at_it() {
+        assert_eq!(
+            Localization::at(0, 0),
+            to_localization("𝑥", Position::new(0, 1))
+        );
+        assert_eq!(
+            Localization::at(0, 1),
+            to_localization("𝑥", Position::new(0, 9))
+        );
+        assert_eq!(
+            Localization::at(3, 0),
+            to_localization("𝑥", Position::new(3, 0))
+        );
+    }
+
+    #[test]
+    fn hover_shows_the_value_of_the_statement() {
+        let mut document = OpenDocument::new("1 +; 2 * 3;\n4".to_string());
+        document.diagnostics();
+
+        let text = hover_text(&document, Localization::at(0, 7));
+
+        assert!(text.starts_with("```\n(2 * 3) = 6\n```"), "{text}");
+        assert_eq!(
+            Some(Range::new(Position::new(0, 5), Position::new(0, 10))),
+            document.hover(Localization::at(0, 7)).unwrap().range
+        );
+        assert!(hover_text(&document, Localization::at(1, 0)).starts_with("```\n4 = 4\n```"));
+        assert!(document.hover(Localization::at(0, 1)).is_none());
+    }
+
+    #[test]
+    fn formatting_replaces_the_whole_document() {
+        let document = OpenDocument::new("1+2;\n𝑥𝑥".to_string());
+        let formatted = OpenDocument::new("1 + 2".to_string());
+
+        let edits = document.formatting();
+
+        assert_eq!(1, edits.len());
+        assert_eq!(
+            Range::new(Position::new(0, 0), Position::new(1, 4)),
+            edits[0].range
+        );
+        assert_eq!(Vec::<TextEdit>::new(), formatted.formatting());
+    }
+
+    #[test]
+    fn diagnostics_have_the_code_and_the_help() {
+        let text = "1 + (2";
+        let diagnostic = parse_statements(text.to_string())
+            .into_iter()
+            .find_map(|statement| statement.result.err())
+            .map(Diagnostic::from)
+            .unwrap();
+
+        let converted = to_lsp_diagnostic(text, diagnostic);
+
+        assert_eq!(
+            Some(NumberOrString::String("E0008".to_string())),
+            converted.code
+        );
+        assert_eq!(Some(DiagnosticSeverity::ERROR), converted.severity);
+        assert_eq!(Position::new(0, 6), converted.range.end);
+        assert!(
+            converted
+                .message
+                .ends_with("\nhelp: insert ')' to close the parenthesis"),
+            "{}",
+            converted.message
+        );
+    }
+}
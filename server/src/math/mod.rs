@@ -1,17 +1,42 @@
-mod application;
-mod ast;
+pub mod application;
+pub mod ast;
+pub mod collaboration;
+pub mod completion;
+pub mod document;
+pub mod evaluation_pool;
+pub mod formatting;
+pub mod formulas;
+pub mod language_server;
+pub mod navigation;
+pub mod protocol;
 
-use crate::math::application::Application;
-use futures_util::{SinkExt, StreamExt, TryFutureExt};
+use crate::math::evaluation_pool::EvaluationPool;
+use crate::math::protocol::{Reply, ServerMessage, Session};
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures_util::{SinkExt, StreamExt};
 use log::{debug, error, info};
 use warp::ws::{Message, WebSocket};
 
-pub async fn handle_websocket_connection(websocket: WebSocket) {
+pub async fn handle_websocket_connection(websocket: WebSocket, pool: EvaluationPool) {
     info!("New websocket connection");
 
     let (mut writer, mut reader) = websocket.split();
+    // evaluations finish in the background, so all responses go through one channel
+    let (sender, mut responses) = unbounded::<ServerMessage>();
 
     tokio::task::spawn(async move {
+        while let Some(response) = responses.next().await {
+            let response = serde_json::to_string(&response).unwrap();
+            debug!("response: {response}");
+            if let Err(e) = writer.send(Message::text(response)).await {
+                error!("websocket send error: {}", e);
+            }
+        }
+    });
+
+    tokio::task::spawn(async move {
+        let mut session = Session::new();
+
         while let Some(incoming) = reader.next().await {
             let message: Message = match incoming {
                 Ok(msg) => msg,
@@ -20,31 +45,60 @@ pub async fn handle_websocket_connection(websocket: WebSocket) {
                     break;
                 }
             };
+            if message.is_close() {
+                break;
+            }
 
-            let response = process(message);
-
-            writer
-                .send(response)
-                .unwrap_or_else(|e| {
-                    error!("websocket send error: {}", e);
-                })
-                .await;
+            process(&mut session, message, &pool, &sender);
         }
+
+        session.close();
+        info!("websocket connection closed");
     });
 }
 
-fn process(message: Message) -> Message {
-    let response = match message.to_str() {
-        Ok(input) => {
-            debug!("text: {input}");
-            let result = Application::create().run(input.to_string());
-
-            let result = ron::to_string(&result).unwrap();
-            debug!("result: {result}");
-            result
+fn process(
+    session: &mut Session,
+    message: Message,
+    pool: &EvaluationPool,
+    sender: &UnboundedSender<ServerMessage>,
+) {
+    let input = match message.to_str() {
+        Ok(input) => input,
+        Err(_) => {
+            debug!("ignoring non-text message: {message:?}");
+            return;
         }
-        Err(_) => format!("Can't deal with message: {message:?}"),
     };
+    debug!("text: {input}");
+
+    for reply in session.handle(input) {
+        match reply {
+            Reply::Message(response) => send(sender, response),
+            Reply::Evaluate(evaluation) => {
+                let pool = pool.clone();
+                let sender = sender.clone();
+                tokio::task::spawn(async move {
+                    for response in pool.run(evaluation).await {
+                        send(&sender, response);
+                    }
+                });
+            }
+            // waits for the running evaluation, so it takes a worker like one
+            Reply::Inspect(inspection) => {
+                let pool = pool.clone();
+                let sender = sender.clone();
+                tokio::task::spawn(async move {
+                    if let Some(response) = pool.run_with(move |_| inspection.run()).await {
+                        send(&sender, response);
+                    }
+                });
+            }
+        }
+    }
+}
 
-    Message::text(response)
+fn send(sender: &UnboundedSender<ServerMessage>, response: ServerMessage) {
+    // fails only if the connection is already closed
+    let _ = sender.unbounded_send(response);
 }
@@ -0,0 +1,178 @@
+use bigdecimal::BigDecimal;
+
+use crate::math::ast::parser::lexer::{Token, TokenKind};
+use crate::math::ast::parser::{split_statements, StatementSource};
+use crate::math::ast::{Localization, Span};
+
+/// Finds where the name or reference at `position` is defined, for editors:
+/// - a variable in the body of an operator definition is defined by the parameter of that name,
+/// - a user defined operator by the latest definition of its symbol before the statement,
+/// - `ans`, `%n` and `out[n]` by the statement they refer to.
+///
+/// `values` are the values of the statements of the document, see [HistoryReference::resolve](crate::math::ast::HistoryReference::resolve).
+pub fn definition_at(
+    input: &str,
+    position: Localization,
+    values: &[Option<BigDecimal>],
+) -> Option<Span> {
+    let sources = split_statements(input).ok()?;
+    let index = sources
+        .iter()
+        .position(|source| touches(source.text_span, position))?;
+    let tokens = sources[index].tokens();
+    // of two adjacent tokens, the one that can be defined somewhere
+    let token_index = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| touches(token.span, position))
+        .map(|(token_index, _)| token_index)
+        .max_by_key(|&token_index| is_name(&tokens[token_index]))?;
+    let token = tokens[token_index];
+    let following = |offset: usize| tokens.get(token_index + offset);
+
+    match (token.kind, token.text) {
+        (TokenKind::Identifier, "ans") => (0..index)
+            .rev()
+            .find(|&earlier| matches!(values.get(earlier), Some(Some(_))))
+            .map(|earlier| sources[earlier].span),
+        (TokenKind::Identifier, "out")
+            if following(1).map(|token| token.kind) == Some(TokenKind::OpeningBracket) =>
+        {
+            referenced_statement(&sources[..index], following(2)?)
+        }
+        (TokenKind::Percent, _) => referenced_statement(&sources[..index], following(1)?),
+        (TokenKind::Number, _) if token_index > 0 => {
+            let previous = tokens[token_index - 1];
+            let is_reference = previous.kind == TokenKind::Percent
+                || (previous.kind == TokenKind::OpeningBracket
+                    && token_index > 1
+                    && tokens[token_index - 2].text == "out");
+            if is_reference {
+                referenced_statement(&sources[..index], &token)
+            } else {
+                None
+            }
+        }
+        (TokenKind::Identifier | TokenKind::Operator, name) => {
+            if let Some([left, symbol, right]) = definition_header(tokens) {
+                if token_index == 3 {
+                    return Some(symbol.span);
+                }
+                if let Some(parameter) = [left, right]
+                    .into_iter()
+                    .find(|parameter| parameter.text == name)
+                {
+                    return Some(parameter.span);
+                }
+            }
+            sources[..index]
+                .iter()
+                .rev()
+                .filter_map(|source| definition_header(source.tokens()))
+                .find(|[_, symbol, _]| symbol.text == name)
+                .map(|[_, symbol, _]| symbol.span)
+        }
+        _ => None,
+    }
+}
+
+/// The left operand, the symbol and the right operand of a statement like `infixl 6 a <+> b = a + b`.
+pub(crate) fn definition_header<'a>(tokens: &[Token<'a>]) -> Option<[Token<'a>; 3]> {
+    match tokens {
+        [keyword, _, left, symbol, right, equals, ..]
+            if matches!(keyword.text, "infixl" | "infixr") && equals.text == "=" =>
+        {
+            Some([*left, *symbol, *right])
+        }
+        _ => None,
+    }
+}
+
+/// The statement numbered by `number`, counted from 1, among the statements before a reference.
+fn referenced_statement(earlier: &[StatementSource], number: &Token) -> Option<Span> {
+    let number: usize = number.text.parse().ok()?;
+    Some(earlier.get(number.checked_sub(1)?)?.span)
+}
+
+fn is_name(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Identifier | TokenKind::Operator)
+}
+
+/// Whether `position` is in `span` or directly after it, where the cursor is after typing it.
+fn touches(span: Span, position: Localization) -> bool {
+    let key = |localization: Localization| (localization.line(), localization.column());
+    key(span.start) <= key(position) && key(position) <= key(span.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use crate::math::ast::Localization;
+    use crate::math::navigation::definition_at;
+
+    /// The text of the definition of what is at `column` in the last line.
+    fn definition_text(input: &str, column: usize, values: &[Option<i32>]) -> Option<String> {
+        let values: Vec<_> = values
+            .iter()
+            .map(|value| value.map(BigDecimal::from))
+            .collect();
+        let line = input.lines().count() - 1;
+        definition_at(input, Localization::at(line, column), &values)
+            .map(|span| input[span.start_offset..span.end_offset].to_string())
+    }
+
+    #[test]
+    fn parameters_of_the_enclosing_definition() {
+        let input = "infixl 6 a <+> b = (a + b) / 2";
+
+        assert_eq!(Some("a".to_string()), definition_text(input, 20, &[]));
+        assert_eq!(Some("b".to_string()), definition_text(input, 25, &[]));
+        assert_eq!(None, definition_text(input, 29, &[]));
+    }
+
+    #[test]
+    fn operators_refer_to_their_latest_definition() {
+        let input = "infixl 6 a <+> b = a;\ninfixl 6 a <+> b = b;\n1 <+> 2 div 3";
+
+        let result = definition_at(input, Localization::at(2, 3), &[]).unwrap();
+
+        assert_eq!(Localization::at(1, 11), result.start);
+        assert_eq!(None, definition_text(input, 9, &[]));
+    }
+
+    #[test]
+    fn named_operators() {
+        let input = "infixl 7 a div b = (a - a % b) / b;\n7 div 2";
+
+        assert_eq!(Some("div".to_string()), definition_text(input, 3, &[]));
+    }
+
+    #[test]
+    fn history_references_refer_to_statements() {
+        let input = "1 + 2;\nx;\n%1 * out[1] + ans";
+
+        assert_eq!(
+            Some("1 + 2".to_string()),
+            definition_text(input, 0, &[Some(3), None])
+        );
+        assert_eq!(
+            Some("1 + 2".to_string()),
+            definition_text(input, 2, &[Some(3), None])
+        );
+        assert_eq!(
+            Some("1 + 2".to_string()),
+            definition_text(input, 6, &[Some(3), None])
+        );
+        assert_eq!(
+            Some("1 + 2".to_string()),
+            definition_text(input, 16, &[Some(3), None])
+        );
+        assert_eq!(None, definition_text("1;\n%5", 0, &[Some(1)]));
+    }
+
+    #[test]
+    fn free_variables_have_no_definition() {
+        assert_eq!(None, definition_text("x * 2", 0, &[]));
+    }
+}
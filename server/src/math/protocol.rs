@@ -0,0 +1,869 @@
+//! The messages exchanged over the `/api/math` websocket, see `docs/math-protocol.md`.
+//!
+//! Every message is a JSON object with a `type` field. A connection starts with a handshake:
+//! the client sends `hello` with the protocol version it speaks and the server answers `welcome`.
+//! Requests carry an `id` chosen by the client, responses repeat it.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::application::diagnostic::Diagnostic;
+use crate::math::application::{Application, HistoryEntry, Output, Settings, StatementResult};
+use crate::math::ast::budget::{Budget, CancellationToken, Limits};
+use crate::math::ast::parser::highlighting::{classify_tokens, ClassifiedToken};
+use crate::math::ast::{Localization, Span};
+use crate::math::completion::{completions_at, Completion, Completions};
+use crate::math::document::{Document, TextEdit};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub type RequestId = u64;
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello {
+        version: u32,
+    },
+    Evaluate {
+        id: RequestId,
+        document: String,
+    },
+    /// Starts editing a document on the server, it is evaluated like with `evaluate`.
+    Open {
+        id: RequestId,
+        version: u64,
+        document: String,
+    },
+    /// Changes the open document, `version` has to follow the version of the previous change.
+    Edit {
+        id: RequestId,
+        version: u64,
+        edits: Vec<TextEdit>,
+    },
+    Cancel {
+        id: RequestId,
+    },
+    Settings {
+        id: RequestId,
+        #[serde(default)]
+        precision: Option<u64>,
+    },
+    /// Asks for the state of the session.
+    Inspect {
+        id: RequestId,
+    },
+    /// Starts over with the default settings, without statements, definitions and open document.
+    Reset {
+        id: RequestId,
+    },
+    /// Asks for the classified tokens of the open document, for highlighting.
+    Tokens {
+        id: RequestId,
+        version: u64,
+    },
+    /// Asks what can be written at `position` in the open document.
+    Complete {
+        id: RequestId,
+        version: u64,
+        position: Localization,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome {
+        version: u32,
+    },
+    Result {
+        id: RequestId,
+        statements: Vec<StatementOutput>,
+    },
+    /// Sent after every `result`, an empty list means that the document is free of errors.
+    Diagnostic {
+        id: RequestId,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Settings {
+        id: RequestId,
+        precision: Option<u64>,
+    },
+    /// The state of the session after the latest evaluation, the response to `inspect`.
+    State {
+        id: RequestId,
+        precision: Option<u64>,
+        /// the operators defined by the latest document
+        definitions: Vec<String>,
+        /// the statements of the latest document in order
+        history: Vec<HistoryOutput>,
+    },
+    Reset {
+        id: RequestId,
+    },
+    /// The tokens of the open document in the order of the text, the response to `tokens`.
+    Tokens {
+        id: RequestId,
+        version: u64,
+        tokens: Vec<ClassifiedToken>,
+    },
+    /// The response to `complete`, `span` is the text the completions replace.
+    Completions {
+        id: RequestId,
+        version: u64,
+        span: Span,
+        items: Vec<Completion>,
+    },
+    /// The request itself was invalid, problems in the document are reported as `diagnostic`.
+    Error {
+        id: Option<RequestId>,
+        message: String,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct StatementOutput {
+    pub span: Span,
+    pub kind: OutputKind,
+    pub text: String,
+    /// false if the output was taken from the previous evaluation of the document
+    pub recomputed: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct HistoryOutput {
+    pub statement: String,
+    /// the exact value, `null` if the statement failed or has no value
+    pub value: Option<String>,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputKind {
+    Value,
+    Comment,
+    Error,
+}
+
+/// The state of one websocket connection.
+#[derive(Default)]
+pub struct Session {
+    handshake_completed: bool,
+    settings: Settings,
+    /// the latest evaluation, which may still be running
+    latest_evaluation: Option<(RequestId, CancellationToken)>,
+    /// the settings, statements and definitions of the session,
+    /// it is shared with the evaluations and reclaimed when the last of them is done
+    application: Arc<Mutex<Application>>,
+    /// the document opened with `open` and changed with `edit`
+    document: Option<Document>,
+}
+
+/// What the connection has to do in response to a client message.
+pub enum Reply {
+    Message(ServerMessage),
+    /// An evaluation that is too expensive to run on the async runtime.
+    Evaluate(Evaluation),
+    /// Has to wait for the running evaluation, so it must not run on the async runtime either.
+    Inspect(Inspection),
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    pub fn handle(&mut self, message: &str) -> Vec<Reply> {
+        let message = match serde_json::from_str(message) {
+            Ok(message) => message,
+            Err(error) => return vec![Reply::error(None, format!("invalid message: {error}"))],
+        };
+
+        match message {
+            ClientMessage::Hello { version } if version == PROTOCOL_VERSION => {
+                self.handshake_completed = true;
+                vec![Reply::Message(ServerMessage::Welcome { version })]
+            }
+            ClientMessage::Hello { version } => vec![Reply::error(
+                None,
+                format!("unsupported protocol version {version}, the server speaks version {PROTOCOL_VERSION}"),
+            )],
+            message if !self.handshake_completed => vec![Reply::error(
+                message.id(),
+                "the connection has to start with a hello message".to_string(),
+            )],
+            ClientMessage::Evaluate { id, document } => self.evaluate(id, document),
+            ClientMessage::Open {
+                id,
+                version,
+                document,
+            } => {
+                self.document = Some(Document::new(version, document.clone()));
+                self.evaluate(id, document)
+            }
+            ClientMessage::Edit { id, version, edits } => {
+                let document = match &mut self.document {
+                    Some(document) => document,
+                    None => {
+                        return vec![Reply::error(
+                            Some(id),
+                            "no document was opened".to_string(),
+                        )]
+                    }
+                };
+                match document.apply(version, &edits) {
+                    Ok(()) => {
+                        let text = document.text().to_string();
+                        self.evaluate(id, text)
+                    }
+                    Err(message) => vec![Reply::error(Some(id), message)],
+                }
+            }
+            ClientMessage::Cancel { id } => {
+                if let Some((latest_id, cancellation)) = &self.latest_evaluation {
+                    if *latest_id == id {
+                        cancellation.cancel();
+                    }
+                }
+                vec![]
+            }
+            ClientMessage::Settings { id, precision } => {
+                self.settings = Settings { precision };
+                vec![Reply::Message(ServerMessage::Settings { id, precision })]
+            }
+            ClientMessage::Inspect { id } => vec![Reply::Inspect(Inspection {
+                id,
+                settings: self.settings,
+                application: self.application.clone(),
+            })],
+            ClientMessage::Reset { id } => {
+                self.close();
+                *self = Session {
+                    handshake_completed: true,
+                    ..Session::new()
+                };
+                vec![Reply::Message(ServerMessage::Reset { id })]
+            }
+            ClientMessage::Tokens { id, version } => match self.open_document(id, version) {
+                Ok(document) => vec![Reply::Message(ServerMessage::Tokens {
+                    id,
+                    version,
+                    tokens: classify_tokens(document.text()),
+                })],
+                Err(error) => vec![error],
+            },
+            ClientMessage::Complete {
+                id,
+                version,
+                position,
+            } => {
+                let completions = self
+                    .open_document(id, version)
+                    .and_then(|document| {
+                        completions_at(document.text(), position)
+                            .map_err(|message| Reply::error(Some(id), message))
+                    });
+                match completions {
+                    Ok(Completions { span, items }) => {
+                        vec![Reply::Message(ServerMessage::Completions {
+                            id,
+                            version,
+                            span,
+                            items,
+                        })]
+                    }
+                    Err(error) => vec![error],
+                }
+            }
+        }
+    }
+
+    /// The open document, if it has `version`.
+    fn open_document(&self, id: RequestId, version: u64) -> Result<&Document, Reply> {
+        match &self.document {
+            Some(document) if document.version() == version => Ok(document),
+            Some(document) => Err(Reply::error(
+                Some(id),
+                format!(
+                    "the open document has version {}, not {version}",
+                    document.version()
+                ),
+            )),
+            None => Err(Reply::error(Some(id), "no document was opened".to_string())),
+        }
+    }
+
+    /// Stops the running evaluation, so that the state of the session can be reclaimed.
+    pub fn close(&mut self) {
+        if let Some((_, cancellation)) = self.latest_evaluation.take() {
+            cancellation.cancel();
+        }
+    }
+
+    fn evaluate(&mut self, id: RequestId, document: String) -> Vec<Reply> {
+        // a newer version of the document makes the result of the previous one useless
+        self.close();
+        let cancellation = CancellationToken::new();
+        self.latest_evaluation = Some((id, cancellation.clone()));
+        vec![Reply::Evaluate(Evaluation::new(
+            id,
+            document,
+            self.settings,
+            cancellation,
+            self.application.clone(),
+        ))]
+    }
+}
+
+/// One `inspect` request, detached from its session like [Evaluation].
+pub struct Inspection {
+    id: RequestId,
+    settings: Settings,
+    application: Arc<Mutex<Application>>,
+}
+
+impl Inspection {
+    /// Waits for the running evaluation of the session.
+    pub fn run(self) -> ServerMessage {
+        let application = self
+            .application
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let history = application
+            .history()
+            .iter()
+            .map(|HistoryEntry { statement, value }| HistoryOutput {
+                statement: statement.clone(),
+                value: value.as_ref().map(ToString::to_string),
+            })
+            .collect();
+        ServerMessage::State {
+            id: self.id,
+            precision: self.settings.precision,
+            definitions: application
+                .definitions()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            history,
+        }
+    }
+}
+
+/// One `evaluate` request, detached from its session so that it can run on another thread.
+pub struct Evaluation {
+    id: RequestId,
+    document: String,
+    settings: Settings,
+    cancellation: CancellationToken,
+    application: Arc<Mutex<Application>>,
+}
+
+impl Evaluation {
+    /// The evaluation of `document` with the statements and definitions of `application`,
+    /// `id` is the id of its responses.
+    pub fn new(
+        id: RequestId,
+        document: String,
+        settings: Settings,
+        cancellation: CancellationToken,
+        application: Arc<Mutex<Application>>,
+    ) -> Self {
+        Evaluation {
+            id,
+            document,
+            settings,
+            cancellation,
+            application,
+        }
+    }
+
+    /// Evaluates the document within `limits`, a cancelled evaluation has no response.
+    pub fn run(self, limits: Limits) -> Vec<ServerMessage> {
+        if self.cancellation.is_cancelled() {
+            return vec![];
+        }
+        let budget = Budget::new(limits, self.cancellation.clone());
+        // a cancelled evaluation still holds the lock, but it finishes quickly
+        let results = {
+            let mut application = self
+                .application
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            application.settings = self.settings;
+            application.run_within(self.document, &budget)
+        };
+        if self.cancellation.is_cancelled() {
+            return vec![];
+        }
+
+        let mut statements = vec![];
+        let mut diagnostics = vec![];
+        for StatementResult {
+            span,
+            output,
+            recomputed,
+        } in results
+        {
+            let (kind, text) = match output {
+                Output::Value(text) => (OutputKind::Value, text),
+                Output::Comment(text) => (OutputKind::Comment, text),
+                Output::Error(diagnostic) => {
+                    let message = diagnostic.message.clone();
+                    diagnostics.push(diagnostic);
+                    (OutputKind::Error, message)
+                }
+            };
+            statements.push(StatementOutput {
+                span,
+                kind,
+                text,
+                recomputed,
+            });
+        }
+
+        let id = self.id;
+        vec![
+            ServerMessage::Result { id, statements },
+            ServerMessage::Diagnostic { id, diagnostics },
+        ]
+    }
+}
+
+impl ClientMessage {
+    fn id(&self) -> Option<RequestId> {
+        match self {
+            ClientMessage::Hello { .. } => None,
+            ClientMessage::Evaluate { id, .. }
+            | ClientMessage::Open { id, .. }
+            | ClientMessage::Edit { id, .. }
+            | ClientMessage::Cancel { id }
+            | ClientMessage::Settings { id, .. }
+            | ClientMessage::Inspect { id }
+            | ClientMessage::Reset { id }
+            | ClientMessage::Tokens { id, .. }
+            | ClientMessage::Complete { id, .. } => Some(*id),
+        }
+    }
+}
+
+impl Reply {
+    fn error(id: Option<RequestId>, message: String) -> Self {
+        Reply::Message(ServerMessage::Error { id, message })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::math::ast::budget::Limits;
+    use crate::math::protocol::{Evaluation, Reply, ServerMessage, Session};
+
+    fn handshaken_session() -> Session {
+        let mut session = Session::new();
+        session.handle(r#"{"type": "hello", "version": 1}"#);
+        session
+    }
+
+    fn to_values(messages: Vec<ServerMessage>) -> Vec<Value> {
+        messages
+            .iter()
+            .map(|message| serde_json::to_value(message).unwrap())
+            .collect()
+    }
+
+    /// Runs evaluations right away.
+    fn handle(session: &mut Session, message: Value) -> Vec<Value> {
+        let messages = session
+            .handle(&message.to_string())
+            .into_iter()
+            .flat_map(|reply| match reply {
+                Reply::Message(message) => vec![message],
+                Reply::Evaluate(evaluation) => evaluation.run(Limits::default()),
+                Reply::Inspect(inspection) => vec![inspection.run()],
+            })
+            .collect();
+        to_values(messages)
+    }
+
+    /// Returns the evaluation without running it.
+    fn start_evaluation(session: &mut Session, id: u64, document: &str) -> Evaluation {
+        let message = json!({"type": "evaluate", "id": id, "document": document});
+        match session.handle(&message.to_string()).pop() {
+            Some(Reply::Evaluate(evaluation)) => evaluation,
+            _ => panic!("expected an evaluation"),
+        }
+    }
+
+    fn span(start: usize, end: usize) -> Value {
+        json!({
+            "start": {"line": 0, "column": start},
+            "end": {"line": 0, "column": end},
+            "start_offset": start,
+            "end_offset": end,
+        })
+    }
+
+    #[test]
+    fn handshake() {
+        let mut session = Session::new();
+
+        let result = handle(&mut session, json!({"type": "hello", "version": 1}));
+
+        assert_eq!(vec![json!({"type": "welcome", "version": 1})], result);
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected() {
+        let mut session = Session::new();
+
+        let result = handle(&mut session, json!({"type": "hello", "version": 2}));
+
+        assert_eq!(
+            vec![json!({
+                "type": "error",
+                "id": null,
+                "message": "unsupported protocol version 2, the server speaks version 1",
+            })],
+            result
+        );
+    }
+
+    #[test]
+    fn requests_before_the_handshake_are_rejected() {
+        let mut session = Session::new();
+
+        let result = handle(
+            &mut session,
+            json!({"type": "evaluate", "id": 1, "document": "1 + 2"}),
+        );
+
+        assert_eq!(
+            vec![json!({
+                "type": "error",
+                "id": 1,
+                "message": "the connection has to start with a hello message",
+            })],
+            result
+        );
+    }
+
+    #[test]
+    fn evaluate_responds_with_result_and_diagnostics() {
+        let mut session = handshaken_session();
+
+        let result = handle(
+            &mut session,
+            json!({"type": "evaluate", "id": 7, "document": "1 + 2; 3 / 0"}),
+        );
+
+        assert_eq!(
+            vec![
+                json!({
+                    "type": "result",
+                    "id": 7,
+                    "statements": [
+                        {"span": span(0, 5), "kind": "value", "text": "(1 + 2) = 3", "recomputed": true},
+                        {"span": span(7, 12), "kind": "error", "text": "division by zero in (3 / 0)", "recomputed": true},
+                    ],
+                }),
+                json!({
+                    "type": "diagnostic",
+                    "id": 7,
+                    "diagnostics": [{
+                        "severity": "Error",
+                        "code": "E0013",
+                        "kind": "DivisionByZero",
+                        "span": span(7, 12),
+                        "message": "division by zero in (3 / 0)",
+                        "help": null,
+                        "notes": [],
+                        "suggestions": [],
+                    }],
+                }),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn settings_apply_to_later_evaluations() {
+        let mut session = handshaken_session();
+
+        let settings = handle(
+            &mut session,
+            json!({"type": "settings", "id": 1, "precision": 3}),
+        );
+        let result = handle(
+            &mut session,
+            json!({"type": "evaluate", "id": 2, "document": "2 / 3"}),
+        );
+
+        assert_eq!(
+            vec![json!({"type": "settings", "id": 1, "precision": 3})],
+            settings
+        );
+        assert_eq!("(2 / 3) = 0.667", result[0]["statements"][0]["text"]);
+    }
+
+    #[test]
+    fn invalid_messages_are_reported() {
+        let mut session = handshaken_session();
+
+        let result = session.handle("1 + 2");
+
+        assert_eq!(1, result.len());
+        let result = match &result[0] {
+            Reply::Message(message) => serde_json::to_value(message).unwrap(),
+            Reply::Evaluate(_) | Reply::Inspect(_) => panic!("expected an error message"),
+        };
+        assert_eq!("error", result["type"]);
+        assert!(result["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid message: "));
+    }
+
+    #[test]
+    fn a_newer_document_cancels_the_previous_evaluation() {
+        let mut session = handshaken_session();
+
+        let outdated = start_evaluation(&mut session, 1, "1 + 2");
+        let latest = start_evaluation(&mut session, 2, "1 + 3");
+
+        assert!(outdated.run(Limits::default()).is_empty());
+        let result = to_values(latest.run(Limits::default()));
+        assert_eq!(2, result[0]["id"]);
+        assert_eq!("(1 + 3) = 4", result[0]["statements"][0]["text"]);
+    }
+
+    #[test]
+    fn cancel_stops_the_evaluation_with_that_id() {
+        let mut session = handshaken_session();
+
+        let evaluation = start_evaluation(&mut session, 3, "1 + 2");
+        let other = handle(&mut session, json!({"type": "cancel", "id": 2}));
+        let cancel = handle(&mut session, json!({"type": "cancel", "id": 3}));
+
+        assert!(other.is_empty());
+        assert!(cancel.is_empty());
+        assert!(evaluation.run(Limits::default()).is_empty());
+    }
+
+    #[test]
+    fn unchanged_statements_are_taken_from_the_previous_evaluation() {
+        let mut session = handshaken_session();
+
+        handle(
+            &mut session,
+            json!({"type": "evaluate", "id": 1, "document": "1 + 2; 3 * 4"}),
+        );
+        let result = handle(
+            &mut session,
+            json!({"type": "evaluate", "id": 2, "document": "1 + 2; 3 * 5"}),
+        );
+
+        let statements = &result[0]["statements"];
+        assert_eq!(false, statements[0]["recomputed"]);
+        assert_eq!(true, statements[1]["recomputed"]);
+        assert_eq!("(3 * 5) = 15", statements[1]["text"]);
+    }
+
+    #[test]
+    fn edits_change_the_open_document() {
+        let mut session = handshaken_session();
+
+        handle(
+            &mut session,
+            json!({"type": "open", "id": 1, "version": 1, "document": "1 + 2; 3 * 4"}),
+        );
+        let edit = json!({"start": {"line": 0, "column": 11}, "end": {"line": 0, "column": 12}, "text": "5"});
+        let result = handle(
+            &mut session,
+            json!({"type": "edit", "id": 2, "version": 2, "edits": [edit]}),
+        );
+
+        assert_eq!(2, result[0]["id"]);
+        assert_eq!("(3 * 5) = 15", result[0]["statements"][1]["text"]);
+    }
+
+    #[test]
+    fn edits_of_another_version_are_rejected() {
+        let mut session = handshaken_session();
+
+        handle(
+            &mut session,
+            json!({"type": "open", "id": 1, "version": 1, "document": "1 + 2"}),
+        );
+        let result = handle(
+            &mut session,
+            json!({"type": "edit", "id": 2, "version": 3, "edits": []}),
+        );
+
+        assert_eq!(
+            vec![json!({"type": "error", "id": 2, "message": "expected version 2, got 3"})],
+            result
+        );
+    }
+
+    #[test]
+    fn edits_need_an_open_document() {
+        let mut session = handshaken_session();
+
+        let result = handle(
+            &mut session,
+            json!({"type": "edit", "id": 1, "version": 2, "edits": []}),
+        );
+
+        assert_eq!(
+            vec![json!({"type": "error", "id": 1, "message": "no document was opened"})],
+            result
+        );
+    }
+
+    #[test]
+    fn tokens_of_the_open_document() {
+        let mut session = handshaken_session();
+
+        handle(
+            &mut session,
+            json!({"type": "open", "id": 1, "version": 1, "document": "(x)"}),
+        );
+        let result = handle(
+            &mut session,
+            json!({"type": "tokens", "id": 2, "version": 1}),
+        );
+        let outdated = handle(
+            &mut session,
+            json!({"type": "tokens", "id": 3, "version": 2}),
+        );
+
+        assert_eq!(
+            vec![json!({
+                "type": "tokens",
+                "id": 2,
+                "version": 1,
+                "tokens": [
+                    {"class": "paren", "span": span(0, 1)},
+                    {"class": "identifier", "span": span(1, 2)},
+                    {"class": "paren", "span": span(2, 3)},
+                ],
+            })],
+            result
+        );
+        assert_eq!(
+            vec![
+                json!({"type": "error", "id": 3, "message": "the open document has version 1, not 2"})
+            ],
+            outdated
+        );
+    }
+
+    #[test]
+    fn completions_at_a_position_of_the_open_document() {
+        let mut session = handshaken_session();
+
+        handle(
+            &mut session,
+            json!({"type": "open", "id": 1, "version": 1, "document": "speed * 2;\nsp"}),
+        );
+        let result = handle(
+            &mut session,
+            json!({"type": "complete", "id": 2, "version": 1, "position": {"line": 1, "column": 2}}),
+        );
+
+        assert_eq!(
+            vec![json!({
+                "type": "completions",
+                "id": 2,
+                "version": 1,
+                "span": {
+                    "start": {"line": 1, "column": 0},
+                    "end": {"line": 1, "column": 2},
+                    "start_offset": 11,
+                    "end_offset": 13,
+                },
+                "items": [{
+                    "label": "speed",
+                    "kind": "variable",
+                    "signature": "speed",
+                    "documentation": "a variable without value",
+                }],
+            })],
+            result
+        );
+    }
+
+    #[test]
+    fn inspect_shows_the_state_of_the_session() {
+        let mut session = handshaken_session();
+
+        handle(
+            &mut session,
+            json!({"type": "settings", "id": 1, "precision": 2}),
+        );
+        handle(
+            &mut session,
+            json!({"type": "evaluate", "id": 2, "document": "infixl 6 a <+> b = a; 1 / 4; x"}),
+        );
+        let result = handle(&mut session, json!({"type": "inspect", "id": 3}));
+
+        assert_eq!(
+            vec![json!({
+                "type": "state",
+                "id": 3,
+                "precision": 2,
+                "definitions": ["infixl 6 a <+> b = a"],
+                "history": [
+                    {"statement": "infixl 6 a <+> b = a", "value": null},
+                    {"statement": "1 / 4", "value": "0.25"},
+                    {"statement": "x", "value": null},
+                ],
+            })],
+            result
+        );
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut session = handshaken_session();
+        handle(
+            &mut session,
+            json!({"type": "settings", "id": 1, "precision": 2}),
+        );
+        handle(
+            &mut session,
+            json!({"type": "open", "id": 2, "version": 1, "document": "1 + 2"}),
+        );
+
+        let reset = handle(&mut session, json!({"type": "reset", "id": 3}));
+        let state = handle(&mut session, json!({"type": "inspect", "id": 4}));
+        let edit = handle(
+            &mut session,
+            json!({"type": "edit", "id": 5, "version": 2, "edits": []}),
+        );
+
+        assert_eq!(vec![json!({"type": "reset", "id": 3})], reset);
+        assert_eq!(
+            json!({"type": "state", "id": 4, "precision": null, "definitions": [], "history": []}),
+            state[0]
+        );
+        assert_eq!("no document was opened", edit[0]["message"]);
+    }
+
+    #[test]
+    fn evaluations_exceeding_the_limits_report_a_diagnostic() {
+        let mut session = handshaken_session();
+        let limits = Limits {
+            max_digits: 10,
+            ..Limits::default()
+        };
+
+        let evaluation = start_evaluation(&mut session, 1, "9^9^9^9");
+        let result = to_values(evaluation.run(limits));
+
+        assert_eq!("E0016", result[1]["diagnostics"][0]["code"]);
+    }
+}